@@ -1,10 +1,10 @@
 //! Benchmarks for the block puzzle solver.
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 
 use blocker::geometry::all_orientations;
-use blocker::pieces::{BEDLAM_PUZZLE, PIECES, SOMA_PUZZLE};
-use blocker::PuzzleOps;
+use blocker::pieces::{BEDLAM_PUZZLE, PENTOMINO_PUZZLE, PIECES, SOMA_PUZZLE};
+use blocker::{CellChoice, Dedup, PuzzleOps, SolveOptions};
 
 /// Benchmark the complete Soma puzzle solving process.
 fn bench_solve(c: &mut Criterion) {
@@ -13,6 +13,13 @@ fn bench_solve(c: &mut Criterion) {
     });
 }
 
+/// Benchmark the Soma puzzle on a single thread, for comparison with `solve`.
+fn bench_solve_sequential(c: &mut Criterion) {
+    c.bench_function("solve_puzzle_sequential", |b| {
+        b.iter(|| black_box(&SOMA_PUZZLE).solve_sequential(None))
+    });
+}
+
 /// Benchmark finding 5 Bedlam solutions.
 fn bench_solve_bedlam_5(c: &mut Criterion) {
     let mut group = c.benchmark_group("bedlam");
@@ -20,9 +27,75 @@ fn bench_solve_bedlam_5(c: &mut Criterion) {
     group.bench_function("solve_5", |b| {
         b.iter(|| black_box(&BEDLAM_PUZZLE).solve(Some(5)))
     });
+    group.bench_function("solve_sequential_5", |b| {
+        b.iter(|| black_box(&BEDLAM_PUZZLE).solve_sequential(Some(5)))
+    });
+    group.bench_function("solve_dlx_5", |b| {
+        b.iter(|| black_box(&BEDLAM_PUZZLE).solve_dlx(Some(5)))
+    });
     group.finish();
 }
 
+/// Benchmark finding 5 Bedlam solutions under each combination of search
+/// options, so a change that slows one of them down shows up on its own.
+fn bench_solver_variants(c: &mut Criterion) {
+    let most_constrained = SolveOptions {
+        heuristic: CellChoice::MostConstrained,
+        ..SolveOptions::default()
+    };
+    let variants: Vec<(&str, SolveOptions)> = vec![
+        ("default", SolveOptions::default()),
+        ("most_constrained", most_constrained.clone()),
+        (
+            "prune_dead_cells",
+            SolveOptions {
+                prune_dead_cells: true,
+                ..SolveOptions::default()
+            },
+        ),
+        (
+            "most_constrained_pruned",
+            SolveOptions {
+                prune_dead_cells: true,
+                ..most_constrained
+            },
+        ),
+        (
+            "bloom_dedup",
+            SolveOptions {
+                dedup: Dedup::Bloom {
+                    bits: 1 << 24,
+                    hashes: 4,
+                },
+                ..SolveOptions::default()
+            },
+        ),
+        (
+            "index_order",
+            SolveOptions {
+                piece_order: (0..13).collect(),
+                ..SolveOptions::default()
+            },
+        ),
+    ];
+
+    let mut group = c.benchmark_group("bedlam_variants");
+    group.sample_size(10);
+    for (name, options) in variants {
+        group.bench_with_input(BenchmarkId::new("solve_5", name), &options, |b, options| {
+            b.iter(|| black_box(&BEDLAM_PUZZLE).solve_with_options(Some(5), options.clone()))
+        });
+    }
+    group.finish();
+}
+
+/// Benchmark the Soma puzzle with dancing links, for comparison with `solve`.
+fn bench_solve_dlx(c: &mut Criterion) {
+    c.bench_function("solve_puzzle_dlx", |b| {
+        b.iter(|| black_box(&SOMA_PUZZLE).solve_dlx(None))
+    });
+}
+
 /// Benchmark computing all orientations for a single piece.
 fn bench_orientations(c: &mut Criterion) {
     let piece = PIECES[0];
@@ -42,6 +115,32 @@ fn bench_canonical_key(c: &mut Criterion) {
     });
 }
 
+/// Benchmark solving with dedup keys packed and kept a byte per cell: all
+/// of Soma (two-word keys) and the first 100 pentomino fills (four words).
+fn bench_dedup_keys(c: &mut Criterion) {
+    let bytes = SolveOptions {
+        dedup: Dedup::ExactBytes,
+        ..SolveOptions::default()
+    };
+    let mut group = c.benchmark_group("dedup_keys");
+    group.sample_size(10);
+    for (name, options) in [("packed", SolveOptions::default()), ("bytes", bytes)] {
+        group.bench_with_input(BenchmarkId::new("soma", name), &options, |b, options| {
+            b.iter(|| black_box(&SOMA_PUZZLE).solve_with_options(None, options.clone()))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("pentomino_100", name),
+            &options,
+            |b, options| {
+                b.iter(|| {
+                    black_box(&PENTOMINO_PUZZLE).solve_with_options(Some(100), options.clone())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
 /// Benchmark formatting a solution for display.
 fn bench_format_solution(c: &mut Criterion) {
     let solutions = SOMA_PUZZLE.solve(None);
@@ -55,9 +154,13 @@ fn bench_format_solution(c: &mut Criterion) {
 criterion_group!(
     benches,
     bench_solve,
+    bench_solve_sequential,
+    bench_solve_dlx,
     bench_solve_bedlam_5,
+    bench_solver_variants,
     bench_orientations,
     bench_canonical_key,
+    bench_dedup_keys,
     bench_format_solution
 );
 criterion_main!(benches);
@@ -0,0 +1,96 @@
+//! Piece colors shared by the 3D viewer and the SVG and terminal exporters.
+//!
+//! Colors are RGB triples with each channel in `0.0..=1.0`. By default each
+//! piece gets an evenly spaced hue; a palette overrides that when it has an
+//! entry for every piece.
+
+/// An RGB color, each channel in `0.0..=1.0`.
+pub type Rgb = [f32; 3];
+
+/// Thirteen colors kept apart for viewers with red-green color blindness,
+/// enough for one per Bedlam piece.
+///
+/// Paul Tol's muted scheme, followed by orange, blue and vermillion from
+/// the Okabe-Ito palette and a pale grey. Thirteen hues cannot all stay
+/// distinct under every kind of color blindness, so the rarer kinds may
+/// still confuse a pair.
+pub const COLORBLIND_PALETTE: [Rgb; 13] = [
+    hex(0xcc6677),
+    hex(0x332288),
+    hex(0xddcc77),
+    hex(0x117733),
+    hex(0x88ccee),
+    hex(0x882255),
+    hex(0x44aa99),
+    hex(0x999933),
+    hex(0xaa4499),
+    hex(0xe69f00),
+    hex(0x0072b2),
+    hex(0xd55e00),
+    hex(0xdddddd),
+];
+
+/// Converts a `0xRRGGBB` color to channels in `0.0..=1.0`.
+const fn hex(rgb: u32) -> Rgb {
+    [
+        ((rgb >> 16) & 0xff) as f32 / 255.0,
+        ((rgb >> 8) & 0xff) as f32 / 255.0,
+        (rgb & 0xff) as f32 / 255.0,
+    ]
+}
+
+/// Returns a distinct RGB color, each channel in `0.0..=1.0`, for a piece
+/// index by spacing hues evenly.
+pub fn piece_color(piece_index: usize, num_pieces: usize) -> Rgb {
+    let hue = (piece_index as f32) / (num_pieces as f32);
+
+    // HSL to RGB with saturation=0.8, lightness=0.5
+    let s: f32 = 0.8;
+    let l: f32 = 0.5;
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h_prime = hue * 6.0;
+    let x = c * (1.0 - (h_prime % 2.0 - 1.0).abs());
+    let m = l - c / 2.0;
+
+    let (r, g, b) = match h_prime as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    [r + m, g + m, b + m]
+}
+
+/// A piece's color from `palette`, or its [`piece_color`] when the palette
+/// has fewer than `num_pieces` entries.
+///
+/// A short palette is ignored as a whole rather than mixed with computed
+/// hues, which could land close to one of its colors.
+pub fn palette_color(palette: &[Rgb], piece_index: usize, num_pieces: usize) -> Rgb {
+    if palette.len() >= num_pieces {
+        palette[piece_index]
+    } else {
+        piece_color(piece_index, num_pieces)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_palettes_fall_back_to_hues() {
+        assert_eq!(hex(0x336699), [0.2, 0.4, 0.6]);
+        for (piece, &color) in COLORBLIND_PALETTE.iter().enumerate() {
+            assert_eq!(palette_color(&COLORBLIND_PALETTE, piece, 13), color);
+            assert_eq!(
+                palette_color(&COLORBLIND_PALETTE[..12], piece, 13),
+                piece_color(piece, 13)
+            );
+        }
+        assert_eq!(palette_color(&[], 0, 7), piece_color(0, 7));
+    }
+}
@@ -4,8 +4,21 @@
 //! These are the 6 ways to choose which face points up, times 4 rotations around
 //! the vertical axis.
 
+use std::sync::{Arc, LazyLock, Mutex};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
 use crate::pieces::Coord;
 
+/// Orientations already worked out by [`cached_orientations`], keyed by the
+/// piece's cubes as given.
+///
+/// Keyed by content rather than by slice address, since a piece loaded from
+/// a file can be freed and another allocated at the same address.
+static ORIENTATION_CACHE: LazyLock<Mutex<OrientationCache>> = LazyLock::new(Default::default);
+
+type OrientationCache = FxHashMap<Vec<Coord>, Arc<[Vec<Coord>]>>;
+
 /// All 24 rotation functions for a cube.
 ///
 /// Organized as 6 face-up choices x 4 rotations around vertical:
@@ -17,7 +30,7 @@ use crate::pieces::Coord;
 /// - Rotations 20-23: -X face up
 ///
 /// Ordering note: the index mapping must stay in sync with the formulas in
-/// `grid::build_rotation_table`, which applies the same rotations to grid cells.
+/// `grid::build_symmetry_table`, which applies the same rotations to grid cells.
 pub const ROTATIONS: [fn(Coord) -> Coord; 24] = [
     // +Z face up (identity orientation), rotate around Z axis
     |(x, y, z)| (x, y, z),      // 0 degrees
@@ -51,11 +64,77 @@ pub const ROTATIONS: [fn(Coord) -> Coord; 24] = [
     |(x, y, z)| (y, z, x),
 ];
 
+/// The rotations of [`ROTATIONS`] as integer matrices, in the same order.
+///
+/// Row `i` of a matrix gives output coordinate `i` as a combination of the
+/// input `(x, y, z)`, so `ROTATIONS[r](c)` is `ROTATION_MATRICES[r]` times
+/// `c` as a column vector. Unlike the closures these can be multiplied and
+/// transposed; see [`compose`] and [`inverse`].
+pub const ROTATION_MATRICES: [[[i32; 3]; 3]; 24] = [
+    [[1, 0, 0], [0, 1, 0], [0, 0, 1]],
+    [[0, -1, 0], [1, 0, 0], [0, 0, 1]],
+    [[-1, 0, 0], [0, -1, 0], [0, 0, 1]],
+    [[0, 1, 0], [-1, 0, 0], [0, 0, 1]],
+    [[1, 0, 0], [0, 0, -1], [0, 1, 0]],
+    [[0, 0, 1], [1, 0, 0], [0, 1, 0]],
+    [[-1, 0, 0], [0, 0, 1], [0, 1, 0]],
+    [[0, 0, -1], [-1, 0, 0], [0, 1, 0]],
+    [[1, 0, 0], [0, -1, 0], [0, 0, -1]],
+    [[0, 1, 0], [1, 0, 0], [0, 0, -1]],
+    [[-1, 0, 0], [0, 1, 0], [0, 0, -1]],
+    [[0, -1, 0], [-1, 0, 0], [0, 0, -1]],
+    [[1, 0, 0], [0, 0, 1], [0, -1, 0]],
+    [[0, 0, -1], [1, 0, 0], [0, -1, 0]],
+    [[-1, 0, 0], [0, 0, -1], [0, -1, 0]],
+    [[0, 0, 1], [-1, 0, 0], [0, -1, 0]],
+    [[0, 0, 1], [0, 1, 0], [-1, 0, 0]],
+    [[0, -1, 0], [0, 0, 1], [-1, 0, 0]],
+    [[0, 0, -1], [0, -1, 0], [-1, 0, 0]],
+    [[0, 1, 0], [0, 0, -1], [-1, 0, 0]],
+    [[0, 0, -1], [0, 1, 0], [1, 0, 0]],
+    [[0, -1, 0], [0, 0, -1], [1, 0, 0]],
+    [[0, 0, 1], [0, -1, 0], [1, 0, 0]],
+    [[0, 1, 0], [0, 0, 1], [1, 0, 0]],
+];
+
+/// Index of the rotation that applies rotation `b` and then rotation `a`.
+///
+/// `ROTATIONS[compose(a, b)](c)` equals `ROTATIONS[a](ROTATIONS[b](c))`.
+pub fn compose(a: usize, b: usize) -> usize {
+    let (a, b) = (&ROTATION_MATRICES[a], &ROTATION_MATRICES[b]);
+    let product: [[i32; 3]; 3] =
+        std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()));
+    matrix_index(&product)
+}
+
+/// Index of the rotation that undoes rotation `i`.
+///
+/// Rotation matrices are orthogonal, so the inverse is the transpose.
+pub fn inverse(i: usize) -> usize {
+    let matrix = &ROTATION_MATRICES[i];
+    let transpose: [[i32; 3]; 3] = std::array::from_fn(|r| std::array::from_fn(|c| matrix[c][r]));
+    matrix_index(&transpose)
+}
+
+/// Position of `matrix` in [`ROTATION_MATRICES`].
+fn matrix_index(matrix: &[[i32; 3]; 3]) -> usize {
+    ROTATION_MATRICES
+        .iter()
+        .position(|candidate| candidate == matrix)
+        .expect("the rotations are closed under products and inverses")
+}
+
 /// Generates all unique orientations of a piece.
 ///
 /// Applies all 24 rotations to the piece, normalizes each result so that
 /// the minimum coordinates are at the origin, then removes duplicates.
 /// Symmetric pieces will have fewer than 24 unique orientations.
+///
+/// The orientations come back sorted by their cube lists, compared cube by
+/// cube, with each `(x, y, z)` ordered by `x`, then `y`, then `z`. Of the
+/// lists that hold the same cubes, only the smallest is kept. The order is
+/// therefore fixed by the piece alone, and so are the placement tables and
+/// search order built from it.
 pub fn all_orientations(piece: &[Coord]) -> Vec<Vec<Coord>> {
     let mut orientations: Vec<Vec<Coord>> = ROTATIONS
         .iter()
@@ -65,17 +144,81 @@ pub fn all_orientations(piece: &[Coord]) -> Vec<Vec<Coord>> {
         })
         .collect();
 
-    // remove duplicate orientations (symmetric pieces produce duplicates)
+    // remove duplicate orientations (symmetric pieces produce duplicates);
+    // rotations list the same cubes in a different order, so compare as sets
     orientations.sort();
-    orientations.dedup();
+    let mut seen = FxHashSet::default();
+    orientations.retain(|orientation| {
+        let mut cubes = orientation.clone();
+        cubes.sort_unstable();
+        seen.insert(cubes)
+    });
+    orientations
+}
+
+/// [`all_orientations`], remembered across calls.
+///
+/// Solvers build their placement tables from this, so repeated solves of the
+/// same pieces skip the rotate-sort-dedup work. The result is the same list,
+/// in the same order, that [`all_orientations`] returns.
+pub fn cached_orientations(piece: &[Coord]) -> Arc<[Vec<Coord>]> {
+    let mut cache = ORIENTATION_CACHE.lock().unwrap();
+    if let Some(orientations) = cache.get(piece) {
+        return Arc::clone(orientations);
+    }
+    let orientations: Arc<[Vec<Coord>]> = all_orientations(piece).into();
+    cache.insert(piece.to_vec(), Arc::clone(&orientations));
     orientations
 }
 
+/// Mirrors a piece through the yz plane and moves it back to the origin.
+///
+/// Every other reflection is this one followed by a rotation, so together
+/// with [`all_orientations`] it reaches every mirror image of the piece.
+pub fn reflect(piece: &[Coord]) -> Vec<Coord> {
+    normalize_to_origin(piece.iter().map(|&(x, y, z)| (-x, y, z)).collect())
+}
+
+/// Number of distinct orientations of a piece under the 24 rotations.
+///
+/// This is how many placement shapes the solver tries per position, so
+/// pieces with more orientations branch more.
+pub fn orientation_count(piece: &[Coord]) -> usize {
+    all_orientations(piece).len()
+}
+
+/// Number of rotations that map a piece onto itself.
+///
+/// The orientations are the cosets of this symmetry group in the rotation
+/// group, so the two counts always multiply to 24.
+pub fn symmetry_order(piece: &[Coord]) -> usize {
+    ROTATIONS.len() / orientation_count(piece)
+}
+
+/// [`all_orientations`], each paired with the extent of its bounding box,
+/// `(max_x + 1, max_y + 1, max_z + 1)`.
+///
+/// Orientations start at the origin, so the extent is also the size of the
+/// smallest box the orientation fits in.
+pub fn orientations_with_bounds(piece: &[Coord]) -> Vec<(Vec<Coord>, Coord)> {
+    all_orientations(piece)
+        .into_iter()
+        .map(|orientation| {
+            let extent = orientation
+                .iter()
+                .fold((0, 0, 0), |(ex, ey, ez), &(x, y, z)| {
+                    (ex.max(x + 1), ey.max(y + 1), ez.max(z + 1))
+                });
+            (orientation, extent)
+        })
+        .collect()
+}
+
 /// Translates coordinates so the minimum x, y, z values are all zero.
 ///
 /// This normalization ensures that two orientations that differ only by
 /// translation will be recognized as identical.
-fn normalize_to_origin(mut coords: Vec<Coord>) -> Vec<Coord> {
+pub(crate) fn normalize_to_origin(mut coords: Vec<Coord>) -> Vec<Coord> {
     let min_x = coords.iter().map(|(x, _, _)| *x).min().unwrap();
     let min_y = coords.iter().map(|(_, y, _)| *y).min().unwrap();
     let min_z = coords.iter().map(|(_, _, z)| *z).min().unwrap();
@@ -88,3 +231,86 @@ fn normalize_to_origin(mut coords: Vec<Coord>) -> Vec<Coord> {
 
     coords
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::{BEDLAM_PIECES, PIECES};
+
+    #[test]
+    fn test_cached_orientations_match_fresh_ones() {
+        for piece in BEDLAM_PIECES {
+            let cached = cached_orientations(piece);
+            assert_eq!(&cached[..], &all_orientations(piece)[..]);
+            assert!(Arc::ptr_eq(&cached, &cached_orientations(piece)));
+        }
+    }
+
+    #[test]
+    fn test_orientation_counts_divide_rotations() {
+        // a single cube looks the same every way up
+        assert_eq!(orientation_count(&[(0, 0, 0)]), 1);
+        assert_eq!(symmetry_order(&[(0, 0, 0)]), 24);
+        // a straight tricube only points along one of three axes
+        assert_eq!(orientation_count(&[(0, 0, 0), (1, 0, 0), (2, 0, 0)]), 3);
+        assert_eq!(symmetry_order(&[(0, 0, 0), (1, 0, 0), (2, 0, 0)]), 8);
+
+        for piece in PIECES.iter().chain(BEDLAM_PIECES) {
+            assert_eq!(orientation_count(piece) * symmetry_order(piece), 24);
+        }
+    }
+
+    #[test]
+    fn test_orientation_bounds_fit_the_piece() {
+        let rod = [(0, 0, 0), (1, 0, 0), (2, 0, 0)];
+        let mut extents: Vec<Coord> = orientations_with_bounds(&rod)
+            .into_iter()
+            .map(|(_, extent)| extent)
+            .collect();
+        extents.sort();
+        assert_eq!(extents, [(1, 1, 3), (1, 3, 1), (3, 1, 1)]);
+
+        for piece in PIECES.iter().chain(BEDLAM_PIECES) {
+            let size = piece.len() as i32;
+            let bounded = orientations_with_bounds(piece);
+            assert_eq!(bounded.len(), orientation_count(piece));
+            for (orientation, (x, y, z)) in bounded {
+                assert!(x > 0 && y > 0 && z > 0);
+                assert!(x <= size && y <= size && z <= size);
+                assert!(x * y * z >= size);
+                assert!(orientation
+                    .iter()
+                    .all(|&(cx, cy, cz)| cx < x && cy < y && cz < z));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotation_matrices_form_a_group() {
+        let coords: Vec<Coord> = (-2..=2)
+            .flat_map(|x| (-2..=2).flat_map(move |y| (-2..=2).map(move |z| (x, y, z))))
+            .collect();
+        let apply = |m: &[[i32; 3]; 3], (x, y, z): Coord| {
+            let row = |r: [i32; 3]| r[0] * x + r[1] * y + r[2] * z;
+            (row(m[0]), row(m[1]), row(m[2]))
+        };
+        for (rotate, matrix) in ROTATIONS.iter().zip(&ROTATION_MATRICES) {
+            for &coord in &coords {
+                assert_eq!(rotate(coord), apply(matrix, coord));
+            }
+        }
+
+        for a in 0..24 {
+            assert_eq!(compose(a, 0), a);
+            assert_eq!(compose(0, a), a);
+            assert_eq!(compose(a, inverse(a)), 0);
+            assert_eq!(inverse(inverse(a)), a);
+            for b in 0..24 {
+                let ab = ROTATIONS[compose(a, b)];
+                assert!(coords
+                    .iter()
+                    .all(|&c| ab(c) == ROTATIONS[a](ROTATIONS[b](c))));
+            }
+        }
+    }
+}
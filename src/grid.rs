@@ -1,38 +1,123 @@
 //! Grid representation and operations for cube packing puzzles.
 //!
-//! Generic over grid dimension (`DIM`) and total cell count (`GRID_SIZE = DIM^3`).
-//! The grid is represented as a flat array where each cell contains a piece
-//! number (1-based) or 0 for empty.
+//! Generic over the box extents (`DIM_X`, `DIM_Y`, `DIM_Z`) and total cell count
+//! (`GRID_SIZE = DIM_X * DIM_Y * DIM_Z`). The grid is represented as a flat array
+//! where each cell contains a piece number (1-based) or 0 for empty.
 
-use crate::pieces::{Coord, PlacedPiece, Puzzle};
+use std::cmp::Ordering;
+use std::io::Write;
+
+use crate::colors::{palette_color, Rgb};
+use crate::geometry::ROTATIONS;
+use crate::pieces::{Coord, PlacedPiece, Puzzle, MAX_CUBES};
+use crate::Symmetry;
 
 /// Number of distinct cube orientations.
 const NUM_ROTATIONS: usize = 24;
 
-/// Builds the rotation lookup table at compile time for any grid dimension.
+/// Number of cube symmetries once reflections are included.
+const NUM_SYMMETRIES: usize = 2 * NUM_ROTATIONS;
+
+/// Grid key value for blocked cells, distinct from every piece number.
+const BLOCKED_CELL: u8 = u8::MAX;
+
+/// An axis of the box, named by the coordinate a reflection negates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// The mirror the symmetry table composes with every rotation.
+///
+/// Any one reflection would do: each reflection is a fixed mirror followed
+/// by some rotation, so one mirror and the 24 rotations reach all 24
+/// reflections of a cube. Mirrors along y and z turn up in the table as x
+/// mirrors followed by a half turn.
+const MIRROR_AXIS: Axis = Axis::X;
+
+/// Cell permutations for the symmetries that map a box onto itself.
+///
+/// Only the first `count` entries of `mappings` are valid, and entry 0 is
+/// always the identity. The first `rotations` entries are proper rotations;
+/// any after them are reflections. A cube keeps all 24 rotations, a box with
+/// two equal sides keeps 8, and a box with three distinct sides keeps 4, and
+/// each shape has as many reflections as rotations. `sources` holds the
+/// inverse of each mapping, the source cell for every destination cell.
+struct SymmetryTable<const GRID_SIZE: usize> {
+    mappings: [[u8; GRID_SIZE]; NUM_SYMMETRIES],
+    sources: [[u8; GRID_SIZE]; NUM_SYMMETRIES],
+    count: usize,
+    rotations: usize,
+}
+
+impl<const GRID_SIZE: usize> SymmetryTable<GRID_SIZE> {
+    /// Returns the valid mappings, identity first and reflections last.
+    fn mappings(&self) -> &[[u8; GRID_SIZE]] {
+        &self.mappings[..self.count]
+    }
+
+    /// Returns only the proper rotations, identity first.
+    fn rotations(&self) -> &[[u8; GRID_SIZE]] {
+        &self.mappings[..self.rotations]
+    }
+
+    /// Returns the inverses of [`mappings`](Self::mappings), in the same order.
+    fn sources(&self) -> &[[u8; GRID_SIZE]] {
+        &self.sources[..self.count]
+    }
+}
+
+/// Builds the symmetry lookup table at compile time for any box shape.
 ///
 /// For each of the 24 rotations and each cell, computes where that cell ends up
-/// after rotating the grid around its center point.
+/// after rotating the grid around its center point. Rotations that would swap
+/// two axes of different length move cells outside the box; those are dropped
+/// so only symmetries of the box itself remain. The same rotations are then
+/// applied again after mirroring through [`MIRROR_AXIS`], which yields every
+/// reflection of the box; for a cube the table is the complete 48-element
+/// symmetry group.
 ///
 /// Uses doubled coordinates to handle both odd (3x3x3) and even (4x4x4) grids
-/// without floating point: center_doubled = DIM - 1.
-const fn build_rotation_table<const DIM: usize, const GRID_SIZE: usize>(
-) -> [[u8; GRID_SIZE]; NUM_ROTATIONS] {
-    let mut table = [[0u8; GRID_SIZE]; NUM_ROTATIONS];
-    let dim_m1 = DIM as i32 - 1;
-
-    let mut rot = 0;
-    while rot < NUM_ROTATIONS {
+/// without floating point: center_doubled = DIM - 1 per axis.
+const fn build_symmetry_table<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>() -> SymmetryTable<GRID_SIZE> {
+    let mut table = SymmetryTable {
+        mappings: [[0u8; GRID_SIZE]; NUM_SYMMETRIES],
+        sources: [[0u8; GRID_SIZE]; NUM_SYMMETRIES],
+        count: 0,
+        rotations: 0,
+    };
+    let x_m1 = DIM_X as i32 - 1;
+    let y_m1 = DIM_Y as i32 - 1;
+    let z_m1 = DIM_Z as i32 - 1;
+
+    let mut symmetry = 0;
+    while symmetry < NUM_SYMMETRIES {
+        let rot = symmetry % NUM_ROTATIONS;
+        let mut mapping = [0u8; GRID_SIZE];
+        let mut keeps_box = true;
         let mut src = 0;
         while src < GRID_SIZE {
-            let x = (src / (DIM * DIM)) as i32;
-            let y = ((src / DIM) % DIM) as i32;
-            let z = (src % DIM) as i32;
+            let (x, y, z) = idx_to_coord::<DIM_Y, DIM_Z>(src);
 
-            // doubled centered coordinates: avoids half-integer centers for even DIM
-            let cx = 2 * x - dim_m1;
-            let cy = 2 * y - dim_m1;
-            let cz = 2 * z - dim_m1;
+            // doubled centered coordinates: avoids half-integer centers for even extents
+            let mut cx = 2 * x - x_m1;
+            let mut cy = 2 * y - y_m1;
+            let mut cz = 2 * z - z_m1;
+            if symmetry >= NUM_ROTATIONS {
+                // mirror through the center plane before rotating
+                match MIRROR_AXIS {
+                    Axis::X => cx = -cx,
+                    Axis::Y => cy = -cy,
+                    Axis::Z => cz = -cz,
+                }
+            }
 
             // apply rotation (same formulas as geometry::ROTATIONS, on doubled coords)
             let (rx, ry, rz) = match rot {
@@ -62,87 +147,374 @@ const fn build_rotation_table<const DIM: usize, const GRID_SIZE: usize>(
                 _ => (cy, cz, cx),
             };
 
+            // a rotation that swaps unequal axes pushes some cell outside the box
+            if rx.abs() > x_m1 || ry.abs() > y_m1 || rz.abs() > z_m1 {
+                keeps_box = false;
+                break;
+            }
+
             // convert back from doubled coords to grid indices
-            let dx = ((rx + dim_m1) / 2) as usize;
-            let dy = ((ry + dim_m1) / 2) as usize;
-            let dz = ((rz + dim_m1) / 2) as usize;
-            let dest = dx * DIM * DIM + dy * DIM + dz;
+            let dest =
+                coord_to_idx::<DIM_Y, DIM_Z>((rx + x_m1) / 2, (ry + y_m1) / 2, (rz + z_m1) / 2);
 
-            table[rot][src] = dest as u8;
+            mapping[src] = dest as u8;
             src += 1;
         }
-        rot += 1;
+        if keeps_box {
+            table.mappings[table.count] = mapping;
+            let mut src = 0;
+            while src < GRID_SIZE {
+                table.sources[table.count][mapping[src] as usize] = src as u8;
+                src += 1;
+            }
+            table.count += 1;
+            if symmetry < NUM_ROTATIONS {
+                table.rotations += 1;
+            }
+        }
+        symmetry += 1;
     }
     table
 }
 
+/// Mirrors a grid key through the center plane perpendicular to `axis`.
+///
+/// A box always maps onto itself under these mirrors, whatever its extents.
+pub fn reflect_key<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    key: &[u8; GRID_SIZE],
+    axis: Axis,
+) -> [u8; GRID_SIZE] {
+    let mut reflected = [0u8; GRID_SIZE];
+    for (cell, &value) in key.iter().enumerate() {
+        let (x, y, z) = idx_to_coord::<DIM_Y, DIM_Z>(cell);
+        let (x, y, z) = match axis {
+            Axis::X => (DIM_X as i32 - 1 - x, y, z),
+            Axis::Y => (x, DIM_Y as i32 - 1 - y, z),
+            Axis::Z => (x, y, DIM_Z as i32 - 1 - z),
+        };
+        reflected[coord_to_idx::<DIM_Y, DIM_Z>(x, y, z)] = value;
+    }
+    reflected
+}
+
+/// Mirrors a grid key through the yz center plane.
+pub fn reflect_key_x<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    key: &[u8; GRID_SIZE],
+) -> [u8; GRID_SIZE] {
+    reflect_key::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(key, Axis::X)
+}
+
+/// Mirrors a grid key through the xz center plane.
+pub fn reflect_key_y<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    key: &[u8; GRID_SIZE],
+) -> [u8; GRID_SIZE] {
+    reflect_key::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(key, Axis::Y)
+}
+
+/// Mirrors a grid key through the xy center plane.
+pub fn reflect_key_z<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    key: &[u8; GRID_SIZE],
+) -> [u8; GRID_SIZE] {
+    reflect_key::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(key, Axis::Z)
+}
+
 /// Converts (x, y, z) coordinates to a linear cell index.
 ///
-/// Index order is x-major: `idx = x * DIM * DIM + y * DIM + z`.
+/// Index order is x-major: `idx = x * DIM_Y * DIM_Z + y * DIM_Z + z`, so the
+/// x extent of the box does not affect the mapping.
 #[inline(always)]
-pub const fn coord_to_idx<const DIM: usize>(x: i32, y: i32, z: i32) -> usize {
-    (x as usize) * DIM * DIM + (y as usize) * DIM + (z as usize)
+pub const fn coord_to_idx<const DIM_Y: usize, const DIM_Z: usize>(x: i32, y: i32, z: i32) -> usize {
+    (x as usize) * DIM_Y * DIM_Z + (y as usize) * DIM_Z + (z as usize)
 }
 
 /// Converts a linear cell index to (x, y, z) coordinates.
 #[inline(always)]
-pub const fn idx_to_coord<const DIM: usize>(cell_index: usize) -> Coord {
+pub const fn idx_to_coord<const DIM_Y: usize, const DIM_Z: usize>(cell_index: usize) -> Coord {
     (
-        (cell_index / (DIM * DIM)) as i32,
-        ((cell_index / DIM) % DIM) as i32,
-        (cell_index % DIM) as i32,
+        (cell_index / (DIM_Y * DIM_Z)) as i32,
+        ((cell_index / DIM_Z) % DIM_Y) as i32,
+        (cell_index % DIM_Z) as i32,
     )
 }
 
 /// Converts a solution (list of placed pieces) to a flat grid.
 ///
 /// Each cell contains a 1-based piece number, or 0 for empty.
-pub fn solution_to_grid<const DIM: usize, const GRID_SIZE: usize>(
+pub fn solution_to_grid<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+) -> [u8; GRID_SIZE] {
+    placements_to_grid::<DIM_Y, DIM_Z, GRID_SIZE, MAX_CUBES>(solution)
+}
+
+/// Converts a solution to a 3D array indexed `[x, y, z]`.
+///
+/// Holds the same piece numbers as [`solution_to_grid`], whose flat x-major
+/// layout is already the array's row-major order, so slicing the array gives
+/// layers and cross-sections directly.
+#[cfg(feature = "ndarray")]
+pub fn solution_to_ndarray<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
     solution: &[PlacedPiece],
+) -> ndarray::Array3<u8> {
+    let grid = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    ndarray::Array3::from_shape_vec((DIM_X, DIM_Y, DIM_Z), grid.to_vec())
+        .expect("GRID_SIZE must equal DIM_X * DIM_Y * DIM_Z")
+}
+
+/// [`solution_to_grid`] for placed pieces of any size.
+fn placements_to_grid<
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const MAX_PIECE_CUBES: usize,
+>(
+    solution: &[PlacedPiece<MAX_PIECE_CUBES>],
 ) -> [u8; GRID_SIZE] {
     let mut grid = [0u8; GRID_SIZE];
 
     for placed in solution {
         let piece_number = (placed.piece_index + 1) as u8;
         for &(x, y, z) in placed.cubes() {
-            grid[coord_to_idx::<DIM>(x, y, z)] = piece_number;
+            grid[coord_to_idx::<DIM_Y, DIM_Z>(x, y, z)] = piece_number;
         }
     }
 
     grid
 }
 
+/// Converts a solution to one cell bitmask per piece, as
+/// `(piece_index, mask)` pairs in solution order.
+///
+/// Bit `coord_to_idx(x, y, z)` is set for each cube, the layout the solver
+/// uses for its own occupancy masks. Only boxes of up to 64 cells fit.
+pub fn solution_to_masks<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+) -> Vec<(usize, u64)> {
+    const { assert!(GRID_SIZE <= 64, "cell masks need GRID_SIZE <= 64") };
+    solution
+        .iter()
+        .map(|placed| {
+            let mask = placed.cubes().iter().fold(0u64, |mask, &(x, y, z)| {
+                mask | 1u64 << coord_to_idx::<DIM_Y, DIM_Z>(x, y, z)
+            });
+            (placed.piece_index, mask)
+        })
+        .collect()
+}
+
+/// The union of a solution's piece masks from [`solution_to_masks`].
+///
+/// A complete solution sets every cell, blocked cells aside.
+pub fn solution_occupied_mask<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+) -> u64 {
+    solution_to_masks::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution)
+        .iter()
+        .fold(0, |occupied, &(_, mask)| occupied | mask)
+}
+
 /// Computes the canonical form of a solution under rotations and reflections.
 ///
-/// Reflections may swap a chiral pair, so the reflected key is normalized by
-/// exchanging those piece IDs before comparison when a pair is provided.
+/// Only symmetries that map the box onto itself are considered. Reflections
+/// may swap a chiral pair, so the reflected key is normalized by exchanging
+/// those piece IDs before comparison when a pair is provided.
 #[inline]
-pub fn canonical_key<const DIM: usize, const GRID_SIZE: usize>(
+pub fn canonical_key<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
     solution: &[PlacedPiece],
     chiral_pair: Option<(usize, usize)>,
 ) -> [u8; GRID_SIZE] {
-    let grid_key = solution_to_grid::<DIM, GRID_SIZE>(solution);
-    find_smallest_rotation_with_reflection::<DIM, GRID_SIZE>(&grid_key, chiral_pair)
+    let grid_key = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    find_smallest_symmetry::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        &grid_key,
+        chiral_pair,
+        None,
+        Symmetry::RotationReflection,
+    )
 }
 
-/// Reflects a grid key across the x-axis (mirror through the yz center plane).
+/// Computes the canonical form of a solution under the full symmetry group of
+/// the box, keeping every piece ID as is.
+///
+/// For a cube this minimizes over all 48 rotations and reflections. It matches
+/// [`canonical_key`] without a chiral pair; with one, a mirrored solution
+/// relabels the pair and so keeps a different key here.
 #[inline]
-fn reflect_key_x<const DIM: usize, const GRID_SIZE: usize>(
-    original: &[u8; GRID_SIZE],
+pub fn canonical_key_full<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
 ) -> [u8; GRID_SIZE] {
-    let mut reflected = [0u8; GRID_SIZE];
+    let grid_key = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    find_smallest_symmetry::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        &grid_key,
+        None,
+        None,
+        Symmetry::RotationReflection,
+    )
+}
+
+/// A grid key packed four bits per cell into `WORDS` words.
+///
+/// Holds boxes of up to `16 * WORDS` cells whose keys only use piece numbers
+/// below 15; blocked cells pack as 15. Each word fills from its top nibble
+/// down, so packed keys order like the byte arrays they stand for, and two
+/// keys are equal exactly when their packed forms are. A packed key takes
+/// `8 * WORDS` bytes against a byte per cell, so two words save memory for
+/// boxes of 17 to 32 cells and four words for boxes of 33 to 64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackedKey<const WORDS: usize>([u64; WORDS]);
 
-    for x in 0..DIM {
-        for y in 0..DIM {
-            for z in 0..DIM {
-                let source = x * DIM * DIM + y * DIM + z;
-                let dest = (DIM - 1 - x) * DIM * DIM + y * DIM + z;
-                reflected[dest] = original[source];
+/// Nibble that stands for [`BLOCKED_CELL`] in a [`PackedKey`].
+const PACKED_BLOCKED_CELL: u8 = 0xf;
+
+impl<const WORDS: usize> PackedKey<WORDS> {
+    /// Whether keys for a box of `grid_size` cells and `num_pieces` pieces
+    /// can be packed.
+    pub const fn fits(grid_size: usize, num_pieces: usize) -> bool {
+        grid_size <= 16 * WORDS && num_pieces < PACKED_BLOCKED_CELL as usize
+    }
+
+    /// Packs a grid key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is too long or holds a piece number that does not
+    /// fit in four bits; check [`fits`](Self::fits) first.
+    pub fn pack<const GRID_SIZE: usize>(key: &[u8; GRID_SIZE]) -> Self {
+        assert!(GRID_SIZE <= 16 * WORDS, "too many cells for the packed key");
+        let nibbles = key.map(pack_cell);
+        let mut packed = Self([0; WORDS]);
+        for (cell, &nibble) in nibbles.iter().enumerate() {
+            packed.set(cell, nibble);
+        }
+        packed
+    }
+
+    /// Recovers the grid key [`pack`](Self::pack) was given.
+    pub fn unpack<const GRID_SIZE: usize>(&self) -> [u8; GRID_SIZE] {
+        std::array::from_fn(|cell| match self.get(cell) {
+            PACKED_BLOCKED_CELL => BLOCKED_CELL,
+            nibble => nibble,
+        })
+    }
+
+    /// The packed image of a key already turned into nibbles by
+    /// [`pack_cell`], with `sources` giving the source cell of each
+    /// destination cell, as [`apply_mapping`] and [`renumber_copies`] would
+    /// give it.
+    ///
+    /// Words are built in order and compared with `bound` as they complete,
+    /// so an image is dropped at its first word above `bound`. Returns
+    /// `None` unless the image is smaller than `bound`.
+    #[inline]
+    fn image_below<const GRID_SIZE: usize>(
+        sources: &[u8; GRID_SIZE],
+        nibbles: &[u8; GRID_SIZE],
+        groups: Option<&CopyGroups>,
+        bound: Option<&Self>,
+    ) -> Option<Self> {
+        let mut labels = groups.map(CopyLabels::new);
+        let mut words = [0u64; WORDS];
+        let mut below = bound.is_none();
+        for (word, cells) in sources.chunks(16).enumerate() {
+            let mut packed = 0u64;
+            for &source in cells {
+                let mut nibble = nibbles[source as usize];
+                if let Some(labels) = &mut labels {
+                    if nibble != PACKED_BLOCKED_CELL {
+                        nibble = labels.relabel(nibble);
+                    }
+                }
+                packed = packed << 4 | u64::from(nibble);
+            }
+            // a short last word keeps its cells in the top nibbles
+            packed <<= 4 * (16 - cells.len());
+            words[word] = packed;
+            if let Some(bound) = bound.filter(|_| !below) {
+                match packed.cmp(&bound.0[word]) {
+                    Ordering::Greater => return None,
+                    Ordering::Less => below = true,
+                    Ordering::Equal => {}
+                }
             }
         }
+        below.then_some(Self(words))
     }
 
-    reflected
+    #[inline(always)]
+    fn get(&self, cell: usize) -> u8 {
+        (self.0[cell / 16] >> (60 - 4 * (cell % 16))) as u8 & 0xf
+    }
+
+    /// Overwrites the nibble for `cell`.
+    #[inline(always)]
+    fn set(&mut self, cell: usize, nibble: u8) {
+        let shift = 60 - 4 * (cell % 16);
+        let word = &mut self.0[cell / 16];
+        *word = (*word & !(0xf << shift)) | (u64::from(nibble) << shift);
+    }
+}
+
+/// The nibble a grid key value packs to.
+#[inline(always)]
+fn pack_cell(value: u8) -> u8 {
+    match value {
+        BLOCKED_CELL => PACKED_BLOCKED_CELL,
+        value => {
+            assert!(
+                value < PACKED_BLOCKED_CELL,
+                "piece number too large to pack"
+            );
+            value
+        }
+    }
 }
 
 /// Swaps the chiral pair IDs in a grid key.
@@ -166,92 +538,499 @@ fn swap_chiral_in_key<const GRID_SIZE: usize>(
     swapped
 }
 
-/// Finds the lexicographically smallest rotation of a grid key.
+/// Piece label to the label of the first copy of its shape, indexed by the
+/// 1-based labels `solution_to_grid` writes.
+type CopyGroups = [u8; 33];
+
+/// Renumbers copies of each shape in the order they first appear in `key`,
+/// so two keys that differ only by which copy sits where become equal.
 #[inline]
-fn find_smallest_rotation<const DIM: usize, const GRID_SIZE: usize>(
+fn renumber_copies<const GRID_SIZE: usize>(key: &mut [u8; GRID_SIZE], groups: &CopyGroups) {
+    let mut labels = CopyLabels::new(groups);
+    for cell in key.iter_mut() {
+        *cell = labels.relabel(*cell);
+    }
+}
+
+/// The labels handed out so far while renumbering copies in cell order.
+struct CopyLabels<'a> {
+    groups: &'a CopyGroups,
+    renamed: [u8; 33],
+    // next free label per group, indexed by the group's first label
+    next: [u8; 33],
+}
+
+impl<'a> CopyLabels<'a> {
+    fn new(groups: &'a CopyGroups) -> Self {
+        Self {
+            groups,
+            renamed: [0; 33],
+            next: [0; 33],
+        }
+    }
+
+    /// Returns the new label for the next cell holding `label`.
+    #[inline(always)]
+    fn relabel(&mut self, label: u8) -> u8 {
+        let label = label as usize;
+        if label == 0 || label >= self.groups.len() {
+            return label as u8;
+        }
+        if self.renamed[label] == 0 {
+            let first = self.groups[label] as usize;
+            if self.next[first] == 0 {
+                self.next[first] = first as u8;
+            }
+            self.renamed[label] = self.next[first];
+            self.next[first] += 1;
+        }
+        self.renamed[label]
+    }
+}
+
+/// Moves each cell value of `source` to the cell `mapping` sends it to.
+#[inline]
+fn apply_mapping<const GRID_SIZE: usize>(
+    mapping: &[u8; GRID_SIZE],
+    source: &[u8; GRID_SIZE],
+) -> [u8; GRID_SIZE] {
+    let mut transformed = [0u8; GRID_SIZE];
+    for (source_cell, &dest_cell) in mapping.iter().enumerate() {
+        transformed[dest_cell as usize] = source[source_cell];
+    }
+    transformed
+}
+
+/// Finds the lexicographically smallest image of a grid key under every
+/// rotation of the box, and every reflection too unless `symmetry` is
+/// [`Symmetry::RotationOnly`].
+///
+/// With `copy_groups`, each image has its copies renumbered before the
+/// comparison.
+///
+/// Without a `chiral_pair`, reflected images keep every label. Such an
+/// image is another valid fill only if every piece in it is its own mirror
+/// image, so reflections merge two solutions exactly when they are true
+/// mirror images of each other, and never otherwise. A piece set with
+/// chiral pieces, such as Bedlam's, is thus counted up to rotation alone;
+/// that is exact when, as for Bedlam, mirroring every piece gives a
+/// different set of shapes, but counts each solution and its mirror image
+/// apart when the set is closed under mirroring and no pair is declared.
+#[inline]
+fn find_smallest_symmetry<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
     original: &[u8; GRID_SIZE],
+    chiral_pair: Option<(usize, usize)>,
+    copy_groups: Option<&CopyGroups>,
+    symmetry: Symmetry,
 ) -> [u8; GRID_SIZE] {
-    let table: &[[u8; GRID_SIZE]; NUM_ROTATIONS] =
-        &const { build_rotation_table::<DIM, GRID_SIZE>() };
+    let table: &SymmetryTable<GRID_SIZE> =
+        &const { build_symmetry_table::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>() };
     let mut smallest = *original;
+    if let Some(groups) = copy_groups {
+        renumber_copies(&mut smallest, groups);
+    }
+
+    // a reflection turns each chiral piece into its partner, so reflected keys
+    // swap the pair's IDs back before comparing
+    let mirrored = match chiral_pair {
+        Some(pair) => swap_chiral_in_key(original, pair),
+        None => *original,
+    };
 
-    // try all rotations except identity (index 0)
-    for rotation_mapping in &table[1..] {
-        let mut rotated = [0u8; GRID_SIZE];
+    let mappings = match symmetry {
+        Symmetry::RotationReflection => table.mappings(),
+        Symmetry::RotationOnly => table.rotations(),
+    };
 
-        // move each source cell value into its rotated destination
-        for (source_cell, &dest_cell) in rotation_mapping.iter().enumerate() {
-            rotated[dest_cell as usize] = original[source_cell];
+    // try the box symmetries in play except identity (index 0)
+    for (index, mapping) in mappings.iter().enumerate().skip(1) {
+        let source = if index < table.rotations {
+            original
+        } else {
+            &mirrored
+        };
+        let mut transformed = apply_mapping(mapping, source);
+        if let Some(groups) = copy_groups {
+            renumber_copies(&mut transformed, groups);
         }
 
-        if rotated < smallest {
-            smallest = rotated;
+        if transformed < smallest {
+            smallest = transformed;
         }
     }
 
     smallest
 }
 
-/// Finds the lexicographically smallest symmetry among rotations and reflections.
+/// [`find_smallest_symmetry`], packing each image as it is built rather
+/// than building byte keys and packing the winner.
+///
+/// Packed keys order like byte keys, so this picks the same image.
 #[inline]
-fn find_smallest_rotation_with_reflection<const DIM: usize, const GRID_SIZE: usize>(
+fn find_smallest_packed<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const WORDS: usize,
+>(
     original: &[u8; GRID_SIZE],
     chiral_pair: Option<(usize, usize)>,
-) -> [u8; GRID_SIZE] {
-    let mut smallest = find_smallest_rotation::<DIM, GRID_SIZE>(original);
+    copy_groups: Option<&CopyGroups>,
+    symmetry: Symmetry,
+) -> PackedKey<WORDS> {
+    let table: &SymmetryTable<GRID_SIZE> =
+        &const { build_symmetry_table::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>() };
+    assert!(GRID_SIZE <= 16 * WORDS, "too many cells for the packed key");
+    let original = original.map(pack_cell);
 
-    // compare raw shape symmetries against reflected symmetries
-    let mut reflected = reflect_key_x::<DIM, GRID_SIZE>(original);
-    if let Some(pair) = chiral_pair {
-        // normalize mirrored chiral pieces before comparing keys
-        reflected = swap_chiral_in_key(&reflected, pair);
-    }
-    let reflected_smallest = find_smallest_rotation::<DIM, GRID_SIZE>(&reflected);
+    // pair labels are below the blocked nibble, so swapping works on nibbles
+    let mirrored = match chiral_pair {
+        Some(pair) => swap_chiral_in_key(&original, pair),
+        None => original,
+    };
+
+    let sources = match symmetry {
+        Symmetry::RotationReflection => table.sources(),
+        Symmetry::RotationOnly => &table.sources()[..table.rotations],
+    };
 
-    if reflected_smallest < smallest {
-        smallest = reflected_smallest;
+    // the identity comes first and has no bound, so this is always set
+    let mut smallest = None;
+    for (index, sources) in sources.iter().enumerate() {
+        let source = if index < table.rotations {
+            &original
+        } else {
+            &mirrored
+        };
+        if let Some(image) = PackedKey::image_below(sources, source, copy_groups, smallest.as_ref())
+        {
+            smallest = Some(image);
+        }
     }
 
-    smallest
+    smallest.expect("the identity image is always kept")
+}
+
+/// Every distinct grid key a solution takes under the box's rotations and
+/// reflections, starting with the solution's own key.
+///
+/// Reflected images swap the chiral pair's labels, as [`canonical_key`]
+/// does, so each key is the same set of pieces laid out another way. A
+/// solution with symmetries of its own has fewer images than the box has
+/// symmetries. Grouping solutions into orbits needs no call to this: two
+/// solutions share an orbit exactly when their canonical keys match.
+pub fn symmetry_orbit<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    chiral_pair: Option<(usize, usize)>,
+) -> Vec<[u8; GRID_SIZE]> {
+    let table: &SymmetryTable<GRID_SIZE> =
+        &const { build_symmetry_table::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>() };
+    let original = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    let mirrored = match chiral_pair {
+        Some(pair) => swap_chiral_in_key(&original, pair),
+        None => original,
+    };
+
+    let mut orbit: Vec<[u8; GRID_SIZE]> = Vec::with_capacity(table.count);
+    for (symmetry, mapping) in table.mappings().iter().enumerate() {
+        let source = if symmetry < table.rotations {
+            &original
+        } else {
+            &mirrored
+        };
+        let transformed = apply_mapping(mapping, source);
+        if !orbit.contains(&transformed) {
+            orbit.push(transformed);
+        }
+    }
+    orbit
 }
 
 /// Formats a solution as a human-readable string.
 ///
-/// Displays DIM z-slices side by side, with piece numbers.
-/// Empty cells show as '.'.
-pub fn format_solution<const DIM: usize, const GRID_SIZE: usize>(
+/// Displays DIM_Z z-slices side by side, each DIM_X columns wide and DIM_Y
+/// rows tall, with piece numbers. Empty cells show as '.'.
+pub fn format_solution<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+) -> String {
+    format_solution_with::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution, FormatOptions::default())
+}
+
+/// How [`format_solution_with`] lays out the slices of a box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// The axis the box is cut across; one slice per coordinate along it,
+    /// side by side.
+    ///
+    /// The two other axes run across each slice, the first of them (in x, y,
+    /// z order) along the columns and the second along the rows.
+    pub slice_axis: Axis,
+    /// Print the lowest row first instead of the highest.
+    pub rows_ascending: bool,
+    /// Print each row from its highest column to its lowest.
+    pub columns_descending: bool,
+}
+
+/// The layout of [`format_solution`]: z-slices with x across and y up.
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            slice_axis: Axis::Z,
+            rows_ascending: false,
+            columns_descending: false,
+        }
+    }
+}
+
+/// Formats a solution as [`format_solution`] does, sliced and ordered as
+/// `options` choose.
+pub fn format_solution_with<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    options: FormatOptions,
+) -> String {
+    format_slices::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution, options, None)
+}
+
+/// Like [`format_solution`], with each piece number in its piece's color.
+///
+/// Colors are ANSI 256-color escapes nearest to
+/// [`piece_color`](crate::colors::piece_color), so they match the 3D viewer
+/// and SVG output. Every row ends with a reset. Whether the terminal
+/// supports color is for the caller to decide.
+pub fn format_solution_colored<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+) -> String {
+    format_solution_colored_with_palette::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution, &[])
+}
+
+/// [`format_solution_colored`] with colors taken from `palette`, as
+/// [`palette_color`] picks them.
+pub fn format_solution_colored_with_palette<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    palette: &[Rgb],
+) -> String {
+    let num_pieces = solution.iter().map(|placed| placed.piece_index + 1).max();
+    format_slices::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        solution,
+        FormatOptions::default(),
+        Some((num_pieces.unwrap_or(0), palette)),
+    )
+}
+
+/// Returns the ANSI 256-color palette index nearest to a piece's color.
+fn ansi_color(palette: &[Rgb], piece_index: usize, num_pieces: usize) -> u8 {
+    // the palette's 6x6x6 color cube starts at index 16
+    let [r, g, b] =
+        palette_color(palette, piece_index, num_pieces).map(|c| (c * 5.0).round() as u8);
+    16 + 36 * r + 6 * g + b
+}
+
+/// Shared layout of [`format_solution_with`] and [`format_solution_colored`],
+/// coloring cells when given the piece count and palette.
+fn format_slices<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    options: FormatOptions,
+    colors: Option<(usize, &[Rgb])>,
+) -> String {
+    let grid = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    let extents = [DIM_X, DIM_Y, DIM_Z];
+    // indices into a coordinate triple for the slice, column and row axes
+    let (slice_axis, column_axis, row_axis) = match options.slice_axis {
+        Axis::X => (0, 1, 2),
+        Axis::Y => (1, 0, 2),
+        Axis::Z => (2, 0, 1),
+    };
+    let mut output = slice_header(
+        options.slice_axis,
+        extents[slice_axis],
+        extents[column_axis],
+    );
+
+    for row in axis_steps(extents[row_axis], !options.rows_ascending) {
+        for slice in 0..extents[slice_axis] {
+            if slice > 0 {
+                output.push_str("  ");
+            }
+            for column in axis_steps(extents[column_axis], options.columns_descending) {
+                let mut coord = [0; 3];
+                coord[slice_axis] = slice;
+                coord[column_axis] = column;
+                coord[row_axis] = row;
+                let piece_number = grid[coord[0] * DIM_Y * DIM_Z + coord[1] * DIM_Z + coord[2]];
+                let display_char = piece_char(piece_number);
+                match colors {
+                    Some((num_pieces, palette)) if piece_number != 0 => {
+                        let color = ansi_color(palette, piece_number as usize - 1, num_pieces);
+                        output.push_str(&format!("\x1b[38;5;{color}m{display_char}"));
+                    }
+                    // empty cells drop any color left from the previous cell
+                    Some(_) => output.push_str("\x1b[0m."),
+                    None => output.push(display_char),
+                }
+            }
+        }
+        if colors.is_some() {
+            output.push_str("\x1b[0m");
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Formats a solution as [`format_solution`] does, drawing each z-slice as
+/// a grid of box-drawing lines with the piece number centered in each cell.
+///
+/// Every character is one column wide in a monospace font, so slices placed
+/// side by side stay aligned.
+pub fn format_solution_unicode<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
     solution: &[PlacedPiece],
 ) -> String {
-    let grid = solution_to_grid::<DIM, GRID_SIZE>(solution);
+    let grid = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    // a slice is 4 columns per cell plus its right edge; labels fill that width
+    let mut output = slice_header(Axis::Z, DIM_Z, 4 * DIM_X - 1);
+
+    let border = |left: char, middle: char, right: char| {
+        let slice = format!(
+            "{left}───{}{right}",
+            format!("{middle}───").repeat(DIM_X - 1)
+        );
+        let mut line = vec![slice; DIM_Z].join("  ");
+        line.push('\n');
+        line
+    };
+
+    output.push_str(&border('┌', '┬', '┐'));
+    // rows from top (y=DIM_Y-1) to bottom (y=0)
+    for y in (0..DIM_Y).rev() {
+        for z in 0..DIM_Z {
+            if z > 0 {
+                output.push_str("  ");
+            }
+            for x in 0..DIM_X {
+                let piece_number = grid[x * DIM_Y * DIM_Z + y * DIM_Z + z];
+                output.push_str(&format!("│ {} ", piece_char(piece_number)));
+            }
+            output.push('│');
+        }
+        output.push('\n');
+        if y > 0 {
+            output.push_str(&border('├', '┼', '┤'));
+        }
+    }
+    output.push_str(&border('└', '┴', '┘'));
+
+    output
+}
+
+/// The coordinates `0..len` along one axis, highest first when `descending`.
+fn axis_steps(len: usize, descending: bool) -> impl Iterator<Item = usize> {
+    (0..len).map(move |step| if descending { len - 1 - step } else { step })
+}
 
-    // header: z=0, z=1, ..., z=DIM-1
+/// The `z=0  z=1 ...` header line above the slices, one label per slice
+/// along `axis`, each padded to the `width` of a slice.
+fn slice_header(axis: Axis, slices: usize, width: usize) -> String {
+    let name = match axis {
+        Axis::X => 'x',
+        Axis::Y => 'y',
+        Axis::Z => 'z',
+    };
     let mut output = String::new();
-    for z in 0..DIM {
-        if z > 0 {
-            // padding between slices: DIM chars for the slice content, plus separator
+    for slice in 0..slices {
+        if slice > 0 {
+            // padding between slices: width chars for the slice content, plus separator
             output.push_str("  ");
         }
-        output.push_str(&format!("z={:<width$}", z, width = DIM));
+        output.push_str(&format!("{name}={slice:<width$}"));
     }
     output.push('\n');
+    output
+}
 
-    // rows from top (y=DIM-1) to bottom (y=0)
-    for y in (0..DIM).rev() {
-        for z in 0..DIM {
+/// The character a cell shows for its piece number: '.' when empty, then
+/// digits and hex letters for piece numbers of 10 and up.
+fn piece_char(piece_number: u8) -> char {
+    if piece_number == 0 {
+        '.'
+    } else if piece_number < 10 {
+        char::from(b'0' + piece_number)
+    } else {
+        char::from(b'A' + piece_number - 10)
+    }
+}
+
+/// Formats solution `a` as [`format_solution`] does, with a '*' over each
+/// cell that `b` fills with a different piece.
+///
+/// Solutions are compared cell by cell, so the order of their placed
+/// pieces does not matter.
+pub fn diff_solutions<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    a: &[PlacedPiece],
+    b: &[PlacedPiece],
+) -> String {
+    let grid_a = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(a);
+    let grid_b = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(b);
+    let mut output = slice_header(Axis::Z, DIM_Z, DIM_X);
+
+    for y in (0..DIM_Y).rev() {
+        for z in 0..DIM_Z {
             if z > 0 {
                 output.push_str("  ");
             }
-            for x in 0..DIM {
-                let piece_number = grid[x * DIM * DIM + y * DIM + z];
-                let display_char = if piece_number == 0 {
-                    '.'
-                } else if piece_number < 10 {
-                    char::from(b'0' + piece_number)
+            for x in 0..DIM_X {
+                let cell_index = x * DIM_Y * DIM_Z + y * DIM_Z + z;
+                if grid_a[cell_index] == grid_b[cell_index] {
+                    output.push(piece_char(grid_a[cell_index]));
                 } else {
-                    // hex for piece numbers >= 10
-                    char::from(b'A' + piece_number - 10)
-                };
-                output.push(display_char);
+                    output.push('*');
+                }
             }
         }
         output.push('\n');
@@ -260,25 +1039,534 @@ pub fn format_solution<const DIM: usize, const GRID_SIZE: usize>(
     output
 }
 
-impl<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize>
-    Puzzle<DIM, GRID_SIZE, NUM_PIECES>
+/// Counts the cells that `a` and `b` fill with different pieces, empty
+/// cells included.
+///
+/// This is the Hamming distance between their grids, so it is 0 only for
+/// the same placement of every piece. Rotated copies of a solution are far
+/// apart; see [`Puzzle::canonical_distance`] to compare up to symmetry.
+pub fn solution_distance<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    a: &[PlacedPiece],
+    b: &[PlacedPiece],
+) -> usize {
+    let grid_a = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(a);
+    let grid_b = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(b);
+    key_distance(&grid_a, &grid_b)
+}
+
+/// Number of cells at which two grid keys differ.
+fn key_distance<const GRID_SIZE: usize>(a: &[u8; GRID_SIZE], b: &[u8; GRID_SIZE]) -> usize {
+    a.iter()
+        .zip(b)
+        .filter(|(cell_a, cell_b)| cell_a != cell_b)
+        .count()
+}
+
+/// Turns every cube of a solution in a `DIM`-sided cube by
+/// `ROTATIONS[rotation]` about the center of the box.
+///
+/// The rotated solution fills the same box, and has the same canonical key
+/// as the original. Panics if `rotation` is not below 24.
+pub fn rotate_solution<const DIM: usize>(
+    solution: &[PlacedPiece],
+    rotation: usize,
+) -> Vec<PlacedPiece> {
+    let rotate = ROTATIONS[rotation];
+    // doubled centered coordinates, as in build_symmetry_table
+    let m1 = DIM as i32 - 1;
+    solution
+        .iter()
+        .map(|placed| {
+            let mut rotated = *placed;
+            for coord in &mut rotated.positions[..placed.cube_count as usize] {
+                let (x, y, z) = *coord;
+                let (rx, ry, rz) = rotate((2 * x - m1, 2 * y - m1, 2 * z - m1));
+                *coord = ((rx + m1) / 2, (ry + m1) / 2, (rz + m1) / 2);
+            }
+            rotated
+        })
+        .collect()
+}
+
+/// Side of one cell in the SVG layout, in user units.
+const SVG_CELL: usize = 24;
+/// Outer margin and the gap between slices in the SVG layout.
+const SVG_MARGIN: usize = 12;
+/// Height of one slice heading or legend row in the SVG layout.
+const SVG_ROW: usize = 20;
+/// Width of one legend entry in the SVG layout.
+const SVG_LEGEND_ENTRY: usize = 84;
+
+/// Formats a solution as an SVG image of its z-slices.
+///
+/// Draws the same layout as [`format_solution`]: DIM_Z slices side by side,
+/// each DIM_X cells wide and DIM_Y tall with y increasing upward. Cells are
+/// filled with their piece's [`piece_color`](crate::colors::piece_color) and
+/// labeled with the piece number, empty cells are left as light outlines,
+/// and a legend of the pieces follows the slices. The view box grows with
+/// the box extents.
+pub fn format_solution_svg<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+) -> String {
+    format_solution_svg_with_palette::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution, &[])
+}
+
+/// [`format_solution_svg`] with cells filled from `palette`, as
+/// [`palette_color`] picks them.
+pub fn format_solution_svg_with_palette<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    palette: &[Rgb],
+) -> String {
+    let grid = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    let mut piece_indices: Vec<usize> = solution.iter().map(|placed| placed.piece_index).collect();
+    piece_indices.sort_unstable();
+    piece_indices.dedup();
+    let num_pieces = piece_indices.last().map_or(0, |&last| last + 1);
+    let fill = |piece_index: usize| {
+        let [r, g, b] =
+            palette_color(palette, piece_index, num_pieces).map(|c| (c * 255.0).round() as u8);
+        format!("#{r:02x}{g:02x}{b:02x}")
+    };
+
+    let slice_width = DIM_X * SVG_CELL;
+    let slices_width = DIM_Z * slice_width + DIM_Z.saturating_sub(1) * SVG_MARGIN;
+    let legend_columns = (slices_width / SVG_LEGEND_ENTRY).max(1);
+    let legend_rows = piece_indices.len().div_ceil(legend_columns);
+    let grid_top = SVG_MARGIN + SVG_ROW;
+    let legend_top = grid_top + DIM_Y * SVG_CELL + SVG_MARGIN;
+    let width = slices_width.max(SVG_LEGEND_ENTRY) + 2 * SVG_MARGIN;
+    let height = legend_top + legend_rows * SVG_ROW + SVG_MARGIN;
+
+    let mut output = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {height}\" \
+         font-family=\"sans-serif\" font-size=\"12\">\n"
+    );
+    for z in 0..DIM_Z {
+        let left = SVG_MARGIN + z * (slice_width + SVG_MARGIN);
+        output.push_str(&format!(
+            "<text x=\"{left}\" y=\"{}\">z={z}</text>\n",
+            grid_top - 6
+        ));
+
+        // rows from top (y=DIM_Y-1) to bottom (y=0), as in format_solution
+        for (row, y) in (0..DIM_Y).rev().enumerate() {
+            for x in 0..DIM_X {
+                let (cell_x, cell_y) = (left + x * SVG_CELL, grid_top + row * SVG_CELL);
+                let piece_number = grid[x * DIM_Y * DIM_Z + y * DIM_Z + z] as usize;
+                if piece_number == 0 {
+                    output.push_str(&format!(
+                        "<rect x=\"{cell_x}\" y=\"{cell_y}\" width=\"{SVG_CELL}\" \
+                         height=\"{SVG_CELL}\" fill=\"none\" stroke=\"#ccc\"/>\n"
+                    ));
+                    continue;
+                }
+                output.push_str(&format!(
+                    "<rect x=\"{cell_x}\" y=\"{cell_y}\" width=\"{SVG_CELL}\" \
+                     height=\"{SVG_CELL}\" fill=\"{}\" stroke=\"#333\"/>\n",
+                    fill(piece_number - 1)
+                ));
+                output.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{piece_number}</text>\n",
+                    cell_x + SVG_CELL / 2,
+                    cell_y + SVG_CELL / 2 + 4
+                ));
+            }
+        }
+    }
+
+    for (entry, &piece_index) in piece_indices.iter().enumerate() {
+        let left = SVG_MARGIN + (entry % legend_columns) * SVG_LEGEND_ENTRY;
+        let top = legend_top + (entry / legend_columns) * SVG_ROW;
+        output.push_str(&format!(
+            "<rect x=\"{left}\" y=\"{top}\" width=\"14\" height=\"14\" fill=\"{}\" \
+             stroke=\"#333\"/>\n",
+            fill(piece_index)
+        ));
+        output.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\">Piece {}</text>\n",
+            left + 20,
+            top + 12,
+            piece_index + 1
+        ));
+    }
+
+    output.push_str("</svg>\n");
+    output
+}
+
+/// Outward normal and corner offsets of each cube face, corners listed
+/// counter-clockwise as seen from outside so the normal follows the
+/// right-hand rule.
+const CUBE_FACES: [(Coord, [Coord; 4]); 6] = [
+    ((1, 0, 0), [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)]),
+    ((-1, 0, 0), [(0, 0, 0), (0, 0, 1), (0, 1, 1), (0, 1, 0)]),
+    ((0, 1, 0), [(0, 1, 0), (0, 1, 1), (1, 1, 1), (1, 1, 0)]),
+    ((0, -1, 0), [(0, 0, 0), (1, 0, 0), (1, 0, 1), (0, 0, 1)]),
+    ((0, 0, 1), [(0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1)]),
+    ((0, 0, -1), [(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 0, 0)]),
+];
+
+/// Encodes a solution as a binary STL model, one unit cube per occupied cell.
+///
+/// See [`write_stl`].
+pub fn export_stl<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+) -> Vec<u8> {
+    let mut stl = Vec::new();
+    write_stl::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution, &mut stl)
+        .expect("writing to a Vec cannot fail");
+    stl
+}
+
+/// Writes a solution as a binary STL model to `writer`.
+///
+/// Each piece becomes a closed surface: faces between two cubes of the same
+/// piece are left out, while faces against another piece or the outside are
+/// kept. Cubes are one unit wide with the box corner at the origin, so scale
+/// the model in the slicer to the print size.
+pub fn write_stl<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    let grid = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    let inside = |x: i32, y: i32, z: i32| {
+        (0..DIM_X as i32).contains(&x)
+            && (0..DIM_Y as i32).contains(&y)
+            && (0..DIM_Z as i32).contains(&z)
+    };
+
+    let mut faces = Vec::new();
+    for (cell, &piece_number) in grid.iter().enumerate() {
+        if piece_number == 0 {
+            continue;
+        }
+        let (x, y, z) = idx_to_coord::<DIM_Y, DIM_Z>(cell);
+        for &(normal, corners) in &CUBE_FACES {
+            let (nx, ny, nz) = (x + normal.0, y + normal.1, z + normal.2);
+            let same_piece = inside(nx, ny, nz)
+                && grid[coord_to_idx::<DIM_Y, DIM_Z>(nx, ny, nz)] == piece_number;
+            if !same_piece {
+                faces.push((normal, corners.map(|(dx, dy, dz)| (x + dx, y + dy, z + dz))));
+            }
+        }
+    }
+
+    // header is free text; the triangle count follows it
+    let mut header = [0u8; 80];
+    header[..7].copy_from_slice(b"blocker");
+    writer.write_all(&header)?;
+    writer.write_all(&(2 * faces.len() as u32).to_le_bytes())?;
+
+    for (normal, [a, b, c, d]) in faces {
+        // split each square face along the a-c diagonal
+        for triangle in [[a, b, c], [a, c, d]] {
+            write_vector(writer, normal)?;
+            for vertex in triangle {
+                write_vector(writer, vertex)?;
+            }
+            // attribute byte count, unused
+            writer.write_all(&[0, 0])?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a vector as the three little-endian `f32` components STL uses.
+fn write_vector(writer: &mut impl Write, (x, y, z): Coord) -> std::io::Result<()> {
+    for component in [x, y, z] {
+        writer.write_all(&(component as f32).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+        const MAX_PIECE_CUBES: usize,
+    > Puzzle<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>
 {
     /// Computes the canonical key for a solution, using this puzzle's chiral pair.
-    pub fn canonical_key(&self, solution: &[PlacedPiece]) -> [u8; GRID_SIZE] {
-        canonical_key::<DIM, GRID_SIZE>(solution, self.chiral_pair)
+    ///
+    /// Blocked cells are marked in the key, so only symmetries that map the
+    /// blocked cells onto themselves merge two states.
+    ///
+    /// Copies of a shape are renumbered in the order they appear in each
+    /// image, so solutions that only swap copies get the same key.
+    pub fn canonical_key(&self, solution: &[PlacedPiece<MAX_PIECE_CUBES>]) -> [u8; GRID_SIZE] {
+        self.canonical_key_under(solution, Symmetry::RotationReflection)
+    }
+
+    /// [`Self::canonical_key`] with the symmetries `symmetry` allows.
+    ///
+    /// Under [`Symmetry::RotationOnly`] a solution and its mirror image get
+    /// different keys, as they do for a physical puzzle that can be turned
+    /// but not mirrored.
+    pub fn canonical_key_under(
+        &self,
+        solution: &[PlacedPiece<MAX_PIECE_CUBES>],
+        symmetry: Symmetry,
+    ) -> [u8; GRID_SIZE] {
+        self.smallest_image(&self.key_grid(solution), symmetry)
+    }
+
+    /// The grid of `placed` with blocked cells marked, before any symmetry
+    /// is applied.
+    pub(crate) fn key_grid(&self, placed: &[PlacedPiece<MAX_PIECE_CUBES>]) -> [u8; GRID_SIZE] {
+        let mut grid_key = placements_to_grid::<DIM_Y, DIM_Z, GRID_SIZE, MAX_PIECE_CUBES>(placed);
+        for &cell in self.blocked_cells {
+            grid_key[cell] = BLOCKED_CELL;
+        }
+        grid_key
+    }
+
+    /// [`Self::key_grid`] for a search state that left cells empty on
+    /// purpose: every cell `is_closed` reports that no piece covers is
+    /// marked like a blocked cell.
+    pub(crate) fn closed_key_grid(
+        &self,
+        placed: &[PlacedPiece<MAX_PIECE_CUBES>],
+        is_closed: impl Fn(usize) -> bool,
+    ) -> [u8; GRID_SIZE] {
+        let mut grid_key = placements_to_grid::<DIM_Y, DIM_Z, GRID_SIZE, MAX_PIECE_CUBES>(placed);
+        for (cell, value) in grid_key.iter_mut().enumerate() {
+            if *value == 0 && is_closed(cell) {
+                *value = BLOCKED_CELL;
+            }
+        }
+        grid_key
+    }
+
+    /// The canonical key of a grid from [`Self::key_grid`].
+    pub(crate) fn smallest_image(
+        &self,
+        grid_key: &[u8; GRID_SIZE],
+        symmetry: Symmetry,
+    ) -> [u8; GRID_SIZE] {
+        find_smallest_symmetry::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+            grid_key,
+            self.chiral_pair,
+            self.copy_groups().as_ref(),
+            symmetry,
+        )
+    }
+
+    /// [`Self::smallest_image`] packed, built without the byte key.
+    pub(crate) fn smallest_packed_image<const WORDS: usize>(
+        &self,
+        grid_key: &[u8; GRID_SIZE],
+        symmetry: Symmetry,
+    ) -> PackedKey<WORDS> {
+        find_smallest_packed::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, WORDS>(
+            grid_key,
+            self.chiral_pair,
+            self.copy_groups().as_ref(),
+            symmetry,
+        )
+    }
+
+    /// Computes the canonical key under the full symmetry group of the box,
+    /// ignoring this puzzle's chiral pair.
+    ///
+    /// For a puzzle without a chiral pair this is the key the solver already
+    /// deduplicates by, since [`Self::canonical_key`] searches the same group.
+    pub fn canonical_key_full(&self, solution: &[PlacedPiece<MAX_PIECE_CUBES>]) -> [u8; GRID_SIZE] {
+        find_smallest_symmetry::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+            &self.key_grid(solution),
+            None,
+            self.copy_groups().as_ref(),
+            Symmetry::RotationReflection,
+        )
+    }
+
+    /// The image of `solution` whose grid is its [`Self::canonical_key`].
+    ///
+    /// The pieces are rebuilt from the key's cells, so when the smallest key
+    /// comes from a reflection the chiral pair trades places, and copies of
+    /// a shape take the numbers the key gives them. Pieces are listed by
+    /// `piece_index`, each with its cubes in cell order, so every image of a
+    /// solution comes out identical.
+    pub fn canonical_solution(
+        &self,
+        solution: &[PlacedPiece<MAX_PIECE_CUBES>],
+    ) -> Vec<PlacedPiece<MAX_PIECE_CUBES>> {
+        let key = self.canonical_key(solution);
+        let mut pieces: Vec<PlacedPiece<MAX_PIECE_CUBES>> = Vec::new();
+        for (cell, &piece_number) in key.iter().enumerate() {
+            if piece_number == 0 || piece_number == BLOCKED_CELL {
+                continue;
+            }
+            let piece_index = usize::from(piece_number - 1);
+            let placed = match pieces.iter_mut().find(|p| p.piece_index == piece_index) {
+                Some(placed) => placed,
+                None => {
+                    pieces.push(PlacedPiece {
+                        piece_index,
+                        ..PlacedPiece::EMPTY
+                    });
+                    pieces.last_mut().unwrap()
+                }
+            };
+            placed.positions[placed.cube_count as usize] = idx_to_coord::<DIM_Y, DIM_Z>(cell);
+            placed.cube_count += 1;
+        }
+        pieces.sort_by_key(|placed| placed.piece_index);
+        pieces
+    }
+
+    /// [`Self::canonical_key`] as a [`PackedKey`].
+    ///
+    /// Each image of the solution is packed as it is built, so the byte key
+    /// is never made; the result equals packing [`Self::canonical_key`].
+    ///
+    /// # Panics
+    ///
+    /// Panics unless [`PackedKey::fits`] this puzzle.
+    pub fn packed_canonical_key<const WORDS: usize>(
+        &self,
+        solution: &[PlacedPiece<MAX_PIECE_CUBES>],
+    ) -> PackedKey<WORDS> {
+        self.smallest_packed_image(&self.key_grid(solution), Symmetry::RotationReflection)
+    }
+
+    /// The [`solution_distance`] between the canonical keys of two
+    /// solutions, so images of one solution under symmetry are 0 apart.
+    ///
+    /// Each key picks its own symmetry, so this can exceed the smallest
+    /// distance between any images of the two.
+    pub fn canonical_distance(
+        &self,
+        a: &[PlacedPiece<MAX_PIECE_CUBES>],
+        b: &[PlacedPiece<MAX_PIECE_CUBES>],
+    ) -> usize {
+        key_distance(&self.canonical_key(a), &self.canonical_key(b))
+    }
+
+    /// Counts the rotations and reflections of the box that map a solution
+    /// onto itself, the identity included, so the smallest order is 1.
+    ///
+    /// Symmetries are matched the way [`Self::canonical_key`] merges
+    /// states: blocked cells must stay put, reflections swap the chiral
+    /// pair, and copies of a shape may trade places. Without copies, the
+    /// order times the length of the solution's [`symmetry_orbit`] is the
+    /// number of box symmetries.
+    pub fn solution_symmetry_order(&self, solution: &[PlacedPiece<MAX_PIECE_CUBES>]) -> usize {
+        let table: &SymmetryTable<GRID_SIZE> =
+            &const { build_symmetry_table::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>() };
+        let mut original = placements_to_grid::<DIM_Y, DIM_Z, GRID_SIZE, MAX_PIECE_CUBES>(solution);
+        for &cell in self.blocked_cells {
+            original[cell] = BLOCKED_CELL;
+        }
+        let mirrored = match self.chiral_pair {
+            Some(pair) => swap_chiral_in_key(&original, pair),
+            None => original,
+        };
+        let groups = self.copy_groups();
+        let renumbered = |mut key: [u8; GRID_SIZE]| {
+            if let Some(groups) = &groups {
+                renumber_copies(&mut key, groups);
+            }
+            key
+        };
+
+        let own_key = renumbered(original);
+        table
+            .mappings()
+            .iter()
+            .enumerate()
+            .filter(|&(symmetry, mapping)| {
+                let source = if symmetry < table.rotations {
+                    &original
+                } else {
+                    &mirrored
+                };
+                renumbered(apply_mapping(mapping, source)) == own_key
+            })
+            .count()
+    }
+
+    /// Maps each piece label to the label of the first copy of its shape,
+    /// or `None` when every shape has a single copy.
+    fn copy_groups(&self) -> Option<CopyGroups> {
+        let later_copies = self.later_copies();
+        if later_copies == 0 {
+            return None;
+        }
+        let mut groups = [0u8; 33];
+        for slot in 0..self.num_pieces() {
+            groups[slot + 1] = if later_copies & (1u32 << slot) != 0 {
+                groups[slot]
+            } else {
+                (slot + 1) as u8
+            };
+        }
+        Some(groups)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::geometry::reflect;
+    use crate::pieces::{
+        canonical_piece, BEDLAM_PIECES, BEDLAM_PUZZLE, CHIRAL_PAIR, PIECES,
+        SLOTHOUBER_GRAATSMA_PUZZLE, SOMA_PUZZLE,
+    };
+    use crate::{CellChoice, Dedup, SolveOptions};
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_ndarray_matches_flat_grid() {
+        let brick = Puzzle::<2, 3, 4, 24, 8>::with_counts(
+            &[&[(0, 0, 0), (1, 0, 0), (2, 0, 0)]],
+            &[8],
+            None,
+        );
+        let solution = brick.solve(Some(1)).remove(0);
+        let grid = solution_to_grid::<2, 3, 4, 24>(&solution);
+        let array = solution_to_ndarray::<2, 3, 4, 24>(&solution);
+        assert_eq!(array.shape(), [2, 3, 4]);
+        for ((x, y, z), &piece) in array.indexed_iter() {
+            assert_eq!(
+                piece,
+                grid[coord_to_idx::<3, 4>(x as i32, y as i32, z as i32)]
+            );
+        }
+    }
 
     #[test]
     fn test_identity_rotation_is_unchanged_3x3x3() {
-        let table = const { build_rotation_table::<3, 27>() };
-        for cell in 0..27 {
+        let table = const { build_symmetry_table::<3, 3, 3, 27>() };
+        for (cell, &dest) in table.rotations()[0].iter().enumerate() {
             assert_eq!(
-                table[0][cell], cell as u8,
+                dest, cell as u8,
                 "Identity rotation should not move cell {cell}"
             );
         }
@@ -286,10 +1574,10 @@ mod tests {
 
     #[test]
     fn test_identity_rotation_is_unchanged_4x4x4() {
-        let table = const { build_rotation_table::<4, 64>() };
-        for cell in 0..64 {
+        let table = const { build_symmetry_table::<4, 4, 4, 64>() };
+        for (cell, &dest) in table.rotations()[0].iter().enumerate() {
             assert_eq!(
-                table[0][cell], cell as u8,
+                dest, cell as u8,
                 "Identity rotation should not move cell {cell}"
             );
         }
@@ -297,11 +1585,11 @@ mod tests {
 
     #[test]
     fn test_rotations_are_permutations_3x3x3() {
-        let table = const { build_rotation_table::<3, 27>() };
-        for rot in 0..NUM_ROTATIONS {
+        let table = const { build_symmetry_table::<3, 3, 3, 27>() };
+        for (rot, rotation_mapping) in table.rotations().iter().enumerate() {
             let mut seen = [false; 27];
-            for src in 0..27 {
-                let dest = table[rot][src] as usize;
+            for (src, &dest) in rotation_mapping.iter().enumerate() {
+                let dest = dest as usize;
                 assert!(dest < 27, "Rotation {rot} maps cell {src} to out-of-bounds {dest}");
                 assert!(!seen[dest], "Rotation {rot} maps two cells to {dest}");
                 seen[dest] = true;
@@ -311,11 +1599,11 @@ mod tests {
 
     #[test]
     fn test_rotations_are_permutations_4x4x4() {
-        let table = const { build_rotation_table::<4, 64>() };
-        for rot in 0..NUM_ROTATIONS {
+        let table = const { build_symmetry_table::<4, 4, 4, 64>() };
+        for (rot, rotation_mapping) in table.rotations().iter().enumerate() {
             let mut seen = [false; 64];
-            for src in 0..64 {
-                let dest = table[rot][src] as usize;
+            for (src, &dest) in rotation_mapping.iter().enumerate() {
+                let dest = dest as usize;
                 assert!(dest < 64, "Rotation {rot} maps cell {src} to out-of-bounds {dest}");
                 assert!(!seen[dest], "Rotation {rot} maps two cells to {dest}");
                 seen[dest] = true;
@@ -326,8 +1614,8 @@ mod tests {
     #[test]
     fn test_coordinate_conversion_roundtrip_3x3x3() {
         for idx in 0..27 {
-            let (x, y, z) = idx_to_coord::<3>(idx);
-            let recovered = coord_to_idx::<3>(x, y, z);
+            let (x, y, z) = idx_to_coord::<3, 3>(idx);
+            let recovered = coord_to_idx::<3, 3>(x, y, z);
             assert_eq!(recovered, idx, "Roundtrip failed for index {idx}");
         }
     }
@@ -335,13 +1623,510 @@ mod tests {
     #[test]
     fn test_coordinate_conversion_roundtrip_4x4x4() {
         for idx in 0..64 {
-            let (x, y, z) = idx_to_coord::<4>(idx);
+            let (x, y, z) = idx_to_coord::<4, 4>(idx);
             assert!(
                 (x as usize) < 4 && (y as usize) < 4 && (z as usize) < 4,
-                "idx_to_coord::<4>({idx}) produced out-of-range ({x},{y},{z})"
+                "idx_to_coord::<4, 4>({idx}) produced out-of-range ({x},{y},{z})"
             );
-            let recovered = coord_to_idx::<4>(x, y, z);
+            let recovered = coord_to_idx::<4, 4>(x, y, z);
             assert_eq!(recovered, idx, "Roundtrip failed for index {idx}");
         }
     }
+
+    #[test]
+    fn test_rotation_count_matches_box_symmetry() {
+        assert_eq!(const { build_symmetry_table::<3, 3, 3, 27>() }.rotations, 24);
+        assert_eq!(const { build_symmetry_table::<3, 3, 4, 36>() }.rotations, 8);
+        assert_eq!(const { build_symmetry_table::<2, 3, 4, 24>() }.rotations, 4);
+    }
+
+    #[test]
+    fn test_symmetry_count_includes_reflections() {
+        let cube = const { build_symmetry_table::<3, 3, 3, 27>() };
+        assert_eq!((cube.count, cube.rotations), (48, 24));
+        let square_prism = const { build_symmetry_table::<3, 3, 4, 36>() };
+        assert_eq!((square_prism.count, square_prism.rotations), (16, 8));
+        let brick = const { build_symmetry_table::<2, 3, 4, 24>() };
+        assert_eq!((brick.count, brick.rotations), (8, 4));
+
+        // all 48 mappings are distinct permutations of the cells
+        let mut mappings = cube.mappings().to_vec();
+        for mapping in &mappings {
+            let mut cells = mapping.to_vec();
+            cells.sort_unstable();
+            assert!(cells.iter().copied().eq(0..27u8));
+        }
+        mappings.sort_unstable();
+        mappings.dedup();
+        assert_eq!(mappings.len(), 48);
+    }
+
+    #[test]
+    fn test_solution_masks_partition_the_box() {
+        let solution = SOMA_PUZZLE.solve(Some(1)).remove(0);
+        let masks = solution_to_masks::<3, 3, 3, 27>(&solution);
+        assert_eq!(masks.len(), 7);
+        let mut seen = 0u64;
+        for (&(piece_index, mask), placed) in masks.iter().zip(&solution) {
+            assert_eq!(piece_index, placed.piece_index);
+            assert_eq!(mask.count_ones() as usize, placed.cubes().len());
+            assert_eq!(seen & mask, 0);
+            seen |= mask;
+        }
+        assert_eq!(
+            solution_occupied_mask::<3, 3, 3, 27>(&solution),
+            (1 << 27) - 1
+        );
+
+        // the top bit is cell 63 of a full 64-cell box
+        let row: Puzzle<1, 1, 64, 64, 32> =
+            Puzzle::with_counts(&[&[(0, 0, 0), (1, 0, 0)]], &[32], None);
+        let filled = row.solve_sequential(Some(1)).remove(0);
+        assert_eq!(solution_occupied_mask::<1, 1, 64, 64>(&filled), u64::MAX);
+    }
+
+    #[test]
+    fn test_packed_keys_roundtrip() {
+        assert!(PackedKey::<4>::fits(64, 14));
+        assert!(!PackedKey::<4>::fits(64, 15));
+        assert!(!PackedKey::<4>::fits(65, 2));
+        assert!(!PackedKey::<2>::fits(33, 2));
+
+        // Soma has a chiral pair, Slothouber-Graatsma copies of a shape
+        let solutions = SOMA_PUZZLE.solve(None);
+        let keys: Vec<[u8; 27]> = solutions
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        for (solution, key) in solutions.iter().zip(&keys) {
+            let packed: PackedKey<2> = SOMA_PUZZLE.packed_canonical_key(solution);
+            assert_eq!(packed, PackedKey::pack(key));
+            assert_eq!(packed.unpack::<27>(), *key);
+        }
+        for solution in SLOTHOUBER_GRAATSMA_PUZZLE.solve_all(None) {
+            let packed: PackedKey<2> = SLOTHOUBER_GRAATSMA_PUZZLE.packed_canonical_key(&solution);
+            let key = SLOTHOUBER_GRAATSMA_PUZZLE.canonical_key(&solution);
+            assert_eq!(packed, PackedKey::pack(&key));
+        }
+
+        // packed keys order like the byte keys
+        for pair in keys.windows(2) {
+            let first = PackedKey::<2>::pack(&pair[0]);
+            let second = PackedKey::<2>::pack(&pair[1]);
+            assert_eq!(first.cmp(&second), pair[0].cmp(&pair[1]));
+        }
+
+        let mut blocked = [0u8; 64];
+        blocked[0] = BLOCKED_CELL;
+        blocked[63] = 14;
+        assert_eq!(PackedKey::<4>::pack(&blocked).unpack::<64>(), blocked);
+
+        let bytes = SolveOptions {
+            dedup: Dedup::ExactBytes,
+            ..SolveOptions::default()
+        };
+        let (unpacked, _) = SOMA_PUZZLE.solve_with_options(None, bytes);
+        let unpacked_keys: Vec<[u8; 27]> = unpacked
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        assert_eq!(unpacked_keys, keys);
+    }
+
+    #[test]
+    fn test_reflections_are_involutions_in_the_table() {
+        let key: [u8; 24] = std::array::from_fn(|cell| cell as u8);
+        let brick = const { build_symmetry_table::<2, 3, 4, 24>() };
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let reflected = reflect_key::<2, 3, 4, 24>(&key, axis);
+            assert_ne!(reflected, key);
+            assert_eq!(reflect_key::<2, 3, 4, 24>(&reflected, axis), key);
+
+            // every mirror is one of the table's reflections, whichever axis
+            // the table was built from
+            let reflections = &brick.mappings()[brick.rotations..];
+            assert!(reflections.iter().any(|mapping| {
+                let mut moved = [0u8; 24];
+                for (cell, &dest) in mapping.iter().enumerate() {
+                    moved[dest as usize] = key[cell];
+                }
+                moved == reflected
+            }));
+        }
+        assert_eq!(
+            reflect_key_y::<2, 3, 4, 24>(&key),
+            reflect_key::<2, 3, 4, 24>(&key, Axis::Y)
+        );
+    }
+
+    #[test]
+    fn test_symmetry_orbit_covers_each_image_once() {
+        let solutions = SOMA_PUZZLE.solve(Some(1));
+        let orbit = symmetry_orbit::<3, 3, 3, 27>(&solutions[0], Some(CHIRAL_PAIR));
+        assert_eq!(orbit.len(), 48);
+        assert_eq!(orbit[0], solution_to_grid::<3, 3, 3, 27>(&solutions[0]));
+        let canonical = SOMA_PUZZLE.canonical_key(&solutions[0]);
+        for key in &orbit {
+            assert_eq!(
+                find_smallest_symmetry::<3, 3, 3, 27>(
+                    key,
+                    Some(CHIRAL_PAIR),
+                    None,
+                    Symmetry::RotationReflection
+                ),
+                canonical
+            );
+        }
+
+        // a domino filling a 1x1x2 box looks the same every way round
+        let mut positions = [(0, 0, 0); MAX_CUBES];
+        positions[1] = (0, 0, 1);
+        let domino = PlacedPiece {
+            piece_index: 0,
+            positions,
+            cube_count: 2,
+        };
+        assert_eq!(symmetry_orbit::<1, 1, 2, 2>(&[domino], None), vec![[1, 1]]);
+    }
+
+    #[test]
+    fn test_full_key_keeps_chiral_pieces_apart() {
+        let solution = SOMA_PUZZLE.solve(Some(1)).remove(0);
+        let (first, second) = CHIRAL_PAIR;
+        let mirrored: Vec<PlacedPiece> = solution
+            .iter()
+            .map(|placed| {
+                let mut mirrored = *placed;
+                mirrored.piece_index = match placed.piece_index {
+                    index if index == first => second,
+                    index if index == second => first,
+                    index => index,
+                };
+                for position in &mut mirrored.positions {
+                    position.0 = 2 - position.0;
+                }
+                mirrored
+            })
+            .collect();
+
+        // without a chiral pair both keys search the same 48 symmetries
+        assert_eq!(
+            canonical_key::<3, 3, 3, 27>(&solution, None),
+            canonical_key_full::<3, 3, 3, 27>(&solution)
+        );
+        // the mirror image relabels the chiral pair, which only the
+        // chiral-aware key undoes
+        assert_eq!(
+            SOMA_PUZZLE.canonical_key(&solution),
+            SOMA_PUZZLE.canonical_key(&mirrored)
+        );
+        assert_ne!(
+            SOMA_PUZZLE.canonical_key_full(&solution),
+            SOMA_PUZZLE.canonical_key_full(&mirrored)
+        );
+    }
+
+    #[test]
+    fn test_rotations_are_permutations_2x3x4() {
+        let table = const { build_symmetry_table::<2, 3, 4, 24>() };
+        for (rot, rotation_mapping) in table.rotations().iter().enumerate() {
+            let mut seen = [false; 24];
+            for (src, &dest) in rotation_mapping.iter().enumerate() {
+                let dest = dest as usize;
+                assert!(
+                    dest < 24,
+                    "Rotation {rot} maps cell {src} to out-of-bounds {dest}"
+                );
+                assert!(!seen[dest], "Rotation {rot} maps two cells to {dest}");
+                seen[dest] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn test_coordinate_conversion_roundtrip_2x3x4() {
+        for idx in 0..24 {
+            let (x, y, z) = idx_to_coord::<3, 4>(idx);
+            assert!(
+                x < 2 && y < 3 && z < 4,
+                "idx_to_coord::<3, 4>({idx}) produced out-of-range ({x},{y},{z})"
+            );
+            assert_eq!(
+                coord_to_idx::<3, 4>(x, y, z),
+                idx,
+                "Roundtrip failed for index {idx}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_stl_skips_faces_inside_a_piece() {
+        const DOMINO: &[Coord] = &[(0, 0, 0), (0, 0, 1)];
+        let one: Puzzle<1, 1, 2, 2, 1> = Puzzle::new(&[DOMINO], None);
+        let stl = export_stl::<1, 1, 2, 2>(&one.solve(None)[0]);
+        // a domino shows 10 of its 12 unit faces, two triangles each
+        assert_eq!(u32::from_le_bytes(stl[80..84].try_into().unwrap()), 20);
+        assert_eq!(stl.len(), 84 + 20 * 50);
+
+        let two: Puzzle<1, 1, 2, 2, 2> = Puzzle::new(&[&[(0, 0, 0)], &[(0, 0, 0)]], None);
+        let stl = export_stl::<1, 1, 2, 2>(&two.solve(None)[0]);
+        // separate pieces keep the face they share
+        assert_eq!(u32::from_le_bytes(stl[80..84].try_into().unwrap()), 24);
+
+        // normals of a closed surface cancel out
+        let soma = SOMA_PUZZLE.solve(Some(1));
+        let stl = export_stl::<3, 3, 3, 27>(&soma[0]);
+        let mut normal_sum = [0f32; 3];
+        for triangle in stl[84..].chunks_exact(50) {
+            for (axis, sum) in normal_sum.iter_mut().enumerate() {
+                let bytes = triangle[4 * axis..4 * axis + 4].try_into().unwrap();
+                *sum += f32::from_le_bytes(bytes);
+            }
+        }
+        assert_eq!(normal_sum, [0.0; 3]);
+    }
+
+    #[test]
+    fn test_svg_scales_with_box_and_outlines_empty_cells() {
+        let soma = format_solution_svg::<3, 3, 3, 27>(&SOMA_PUZZLE.solve(Some(1))[0]);
+        assert!(soma.starts_with("<svg") && soma.ends_with("</svg>\n"));
+        assert!(soma.contains("viewBox=\"0 0 264 "));
+        assert_eq!(soma.matches("stroke=\"#333\"").count(), 27 + 7);
+        assert!(soma.contains(">Piece 7</text>"));
+        assert!(!soma.contains("fill=\"none\""));
+
+        let bedlam = format_solution_svg::<4, 4, 4, 64>(&[]);
+        assert!(bedlam.contains("viewBox=\"0 0 444 "));
+        assert_eq!(bedlam.matches("fill=\"none\"").count(), 64);
+    }
+
+    #[test]
+    fn test_colored_format_matches_plain_without_escapes() {
+        let solution = &SOMA_PUZZLE.solve(Some(1))[0];
+        let colored = format_solution_colored::<3, 3, 3, 27>(solution);
+        assert!(colored
+            .lines()
+            .skip(1)
+            .all(|line| line.ends_with("\x1b[0m")));
+
+        // stripping the escapes leaves the plain layout
+        let mut stripped = String::new();
+        let mut rest = colored.as_str();
+        while let Some(start) = rest.find('\x1b') {
+            stripped.push_str(&rest[..start]);
+            rest = &rest[start + rest[start..].find('m').unwrap() + 1..];
+        }
+        stripped.push_str(rest);
+        assert_eq!(stripped, format_solution::<3, 3, 3, 27>(solution));
+    }
+
+    #[test]
+    fn test_diff_marks_cells_filled_by_different_pieces() {
+        let solutions = SOMA_PUZZLE.solve(Some(2));
+        let (a, b) = (&solutions[0], &solutions[1]);
+
+        // placement order is irrelevant to the diff
+        let mut reordered = a.clone();
+        reordered.reverse();
+        assert_eq!(
+            diff_solutions::<3, 3, 3, 27>(a, &reordered),
+            format_solution::<3, 3, 3, 27>(a)
+        );
+
+        let diff = diff_solutions::<3, 3, 3, 27>(a, b);
+        let grid_a = solution_to_grid::<3, 3, 3, 27>(a);
+        let grid_b = solution_to_grid::<3, 3, 3, 27>(b);
+        let changed = (0..27).filter(|&i| grid_a[i] != grid_b[i]).count();
+        assert!(changed > 0);
+        assert_eq!(diff.matches('*').count(), changed);
+
+        // unchanged cells keep a's piece numbers
+        let plain = format_solution::<3, 3, 3, 27>(a);
+        assert!(diff
+            .chars()
+            .zip(plain.chars())
+            .all(|(marked, original)| marked == '*' || marked == original));
+    }
+
+    #[test]
+    fn test_solution_distance_counts_differing_cells() {
+        let solutions = SOMA_PUZZLE.solve(Some(2));
+        let (a, b) = (&solutions[0], &solutions[1]);
+        assert_eq!(solution_distance::<3, 3, 3, 27>(a, a), 0);
+        let distance = solution_distance::<3, 3, 3, 27>(a, b);
+        assert_eq!(distance, solution_distance::<3, 3, 3, 27>(b, a));
+        assert_eq!(
+            distance,
+            diff_solutions::<3, 3, 3, 27>(a, b).matches('*').count()
+        );
+
+        // an empty grid differs from a full solution everywhere
+        assert_eq!(solution_distance::<3, 3, 3, 27>(a, &[]), 27);
+
+        // canonical keys collapse symmetric images; distinct solutions stay apart
+        let mut turned = a.clone();
+        for placed in &mut turned {
+            for (x, y, _) in &mut placed.positions[..placed.cube_count as usize] {
+                (*x, *y) = (2 - *x, 2 - *y);
+            }
+        }
+        assert!(solution_distance::<3, 3, 3, 27>(a, &turned) > 0);
+        assert_eq!(SOMA_PUZZLE.canonical_distance(a, &turned), 0);
+        assert!(SOMA_PUZZLE.canonical_distance(a, b) > 0);
+    }
+
+    #[test]
+    fn test_solution_symmetry_order_counts_fixing_symmetries() {
+        // Soma's seven distinct pieces leave no solution symmetric
+        for solution in &SOMA_PUZZLE.solve(Some(5)) {
+            assert_eq!(SOMA_PUZZLE.solution_symmetry_order(solution), 1);
+            let orbit = symmetry_orbit::<3, 3, 3, 27>(solution, SOMA_PUZZLE.chiral_pair);
+            assert_eq!(orbit.len(), 48);
+        }
+
+        // a domino filling its box is fixed by all 16 symmetries
+        const DOMINO: &[Coord] = &[(0, 0, 0), (0, 0, 1)];
+        let one: Puzzle<1, 1, 2, 2, 1> = Puzzle::new(&[DOMINO], None);
+        assert_eq!(one.solution_symmetry_order(&one.solve(Some(1))[0]), 16);
+
+        // two dominoes side by side in a 2x2x1 box keep the 8 symmetries
+        // that leave both lying the same way, half of them by swapping the
+        // copies
+        let two = Puzzle::<2, 2, 1, 4, 2>::with_counts(&[DOMINO], &[2], None);
+        assert_eq!(two.solution_symmetry_order(&two.solve(Some(1))[0]), 8);
+    }
+
+    #[test]
+    fn test_rotated_solutions_keep_their_canonical_key() {
+        let solution = SOMA_PUZZLE.solve(Some(1)).remove(0);
+        let grid = |solution: &[PlacedPiece]| solution_to_grid::<3, 3, 3, 27>(solution);
+        assert_eq!(grid(&rotate_solution::<3>(&solution, 0)), grid(&solution));
+
+        let key = SOMA_PUZZLE.canonical_key(&solution);
+        let mut grids = Vec::new();
+        for rotation in 0..24 {
+            let rotated = rotate_solution::<3>(&solution, rotation);
+            assert_eq!(SOMA_PUZZLE.validate_solution(&rotated), Ok(()));
+            assert_eq!(SOMA_PUZZLE.canonical_key(&rotated), key);
+            grids.push(grid(&rotated));
+        }
+        // a Soma solution has no rotational symmetry, so every turn is new
+        grids.sort();
+        grids.dedup();
+        assert_eq!(grids.len(), 24);
+    }
+
+    #[test]
+    fn test_canonical_solution_is_shared_by_every_image() {
+        let solution = SOMA_PUZZLE.solve(Some(1)).remove(0);
+        let canonical = SOMA_PUZZLE.canonical_solution(&solution);
+        let key = SOMA_PUZZLE.canonical_key(&solution);
+        assert_eq!(solution_to_grid::<3, 3, 3, 27>(&canonical), key);
+        assert_eq!(SOMA_PUZZLE.validate_solution(&canonical), Ok(()));
+
+        for rotation in 0..24 {
+            let rotated = rotate_solution::<3>(&solution, rotation);
+            let image = SOMA_PUZZLE.canonical_solution(&rotated);
+            assert_eq!(format!("{image:?}"), format!("{canonical:?}"));
+        }
+    }
+
+    #[test]
+    fn test_reflections_without_a_chiral_pair_only_merge_true_mirrors() {
+        // Soma's chiral pieces mirror each other, so without the pair every
+        // solution and its mirror image count apart
+        let unpaired = Puzzle::<3, 3, 3, 27, 7>::new(PIECES, None);
+        assert_eq!(unpaired.solve(None).len(), 2 * 240);
+
+        // Bedlam's mirrored shapes are not Bedlam's shapes, so no mirror
+        // image of a fill is another fill and rotations alone merge states
+        let mut shapes: Vec<Vec<Coord>> = BEDLAM_PIECES
+            .iter()
+            .map(|piece| canonical_piece(piece))
+            .collect();
+        let mut mirrored: Vec<Vec<Coord>> = BEDLAM_PIECES
+            .iter()
+            .map(|piece| canonical_piece(&reflect(piece)))
+            .collect();
+        shapes.sort();
+        mirrored.sort();
+        assert_ne!(shapes, mirrored);
+
+        let options = SolveOptions {
+            heuristic: CellChoice::MostConstrained,
+            prune_dead_cells: true,
+            ..SolveOptions::default()
+        };
+        let mut solution = BEDLAM_PUZZLE
+            .solve_with_options(Some(1), options)
+            .0
+            .remove(0);
+        for placed in &mut solution {
+            for (x, _, _) in &mut placed.positions[..placed.cube_count as usize] {
+                *x = 3 - *x;
+            }
+        }
+        assert!(BEDLAM_PUZZLE.validate_solution(&solution).is_err());
+    }
+
+    /// Four dominoes filling a 2x2x2 box, each with its own layout per slice.
+    fn dominoes_in_a_cube() -> [PlacedPiece; 4] {
+        let placed = |piece_index, cubes: [Coord; 2]| {
+            let mut placed = PlacedPiece {
+                piece_index,
+                cube_count: 2,
+                ..PlacedPiece::EMPTY
+            };
+            placed.positions[..2].copy_from_slice(&cubes);
+            placed
+        };
+        [
+            placed(0, [(0, 0, 0), (1, 0, 0)]),
+            placed(1, [(0, 1, 0), (0, 1, 1)]),
+            placed(2, [(1, 1, 0), (1, 1, 1)]),
+            placed(3, [(0, 0, 1), (1, 0, 1)]),
+        ]
+    }
+
+    #[test]
+    fn test_format_options_slice_along_any_axis() {
+        let solution = dominoes_in_a_cube();
+        let format = |options| format_solution_with::<2, 2, 2, 8>(&solution, options);
+
+        assert_eq!(
+            format(FormatOptions::default()),
+            format_solution::<2, 2, 2, 8>(&solution)
+        );
+        assert_eq!(
+            format(FormatOptions::default()),
+            "z=0   z=1 \n23  23\n11  44\n"
+        );
+
+        let x_slices = FormatOptions {
+            slice_axis: Axis::X,
+            ..FormatOptions::default()
+        };
+        assert_eq!(format(x_slices), "x=0   x=1 \n42  43\n12  13\n");
+        assert_eq!(
+            format(FormatOptions {
+                rows_ascending: true,
+                columns_descending: true,
+                ..x_slices
+            }),
+            "x=0   x=1 \n21  31\n24  34\n"
+        );
+    }
+
+    #[test]
+    fn test_unicode_slices_line_up() {
+        assert_eq!(
+            format_solution_unicode::<2, 2, 2, 8>(&dominoes_in_a_cube()),
+            "z=0        z=1      \n\
+             ┌───┬───┐  ┌───┬───┐\n\
+             │ 2 │ 3 │  │ 2 │ 3 │\n\
+             ├───┼───┤  ├───┼───┤\n\
+             │ 1 │ 1 │  │ 4 │ 4 │\n\
+             └───┴───┘  └───┴───┘\n"
+        );
+    }
 }
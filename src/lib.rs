@@ -1,52 +1,292 @@
 //! Block Puzzle Solver Library
 //!
 //! Provides the core puzzle-solving functionality for cube packing puzzles.
+//!
+//! Everything that touches the filesystem (`persistence`, checkpoints and
+//! `pieces::load_puzzle`) sits behind the default `native-io` feature.
+//! Without it the crate builds for `wasm32-unknown-unknown`; see [`wasm`].
 
+pub mod colors;
 pub mod geometry;
 pub mod grid;
+#[cfg(feature = "native-io")]
 pub mod persistence;
 pub mod pieces;
 mod solver;
+pub mod wasm;
+
+pub use solver::{
+    CellChoice, Dedup, DifficultyMetrics, PreparedPuzzle, SolveOptions, SolveStats, Symmetry,
+    TableStats,
+};
+
+#[cfg(feature = "native-io")]
+use std::path::Path;
 
-use pieces::{PlacedPiece, Puzzle};
+#[cfg(feature = "native-io")]
+use persistence::{ConsistencyError, PersistenceError};
+use pieces::{Coord, PlacedPiece, Puzzle};
 
 /// Trait that erases compile-time puzzle parameters for dynamic dispatch.
 ///
-/// All const generics (`DIM`, `GRID_SIZE`, `NUM_PIECES`) are hidden behind
-/// the vtable, so callers can work with any puzzle without turbofish.
+/// All const generics (`DIM_X`, `DIM_Y`, `DIM_Z`, `GRID_SIZE`, `NUM_PIECES`)
+/// are hidden behind the vtable, so callers can work with any puzzle without
+/// turbofish.
 pub trait PuzzleOps {
     fn solve(&self, max_solutions: Option<usize>) -> Vec<Vec<PlacedPiece>>;
+    fn solve_with_stats(&self, max_solutions: Option<usize>)
+        -> (Vec<Vec<PlacedPiece>>, SolveStats);
+    fn solve_with_progress(
+        &self,
+        max_solutions: Option<usize>,
+        on_solution: &mut (dyn FnMut(usize) + Send),
+    ) -> (Vec<Vec<PlacedPiece>>, SolveStats);
+    #[cfg(feature = "native-io")]
     fn save_solutions(&self, solutions: &[Vec<PlacedPiece>]) -> std::io::Result<()>;
-    fn load_solutions(&self) -> Option<Vec<Vec<PlacedPiece>>>;
-    fn count_solutions(&self) -> Option<usize>;
+    #[cfg(feature = "native-io")]
+    fn save_solutions_to(&self, dir: &Path, solutions: &[Vec<PlacedPiece>]) -> std::io::Result<()>;
+    #[cfg(feature = "native-io")]
+    fn save_solutions_compressed(
+        &self,
+        dir: &Path,
+        solutions: &[Vec<PlacedPiece>],
+    ) -> std::io::Result<()>;
+    #[cfg(all(feature = "native-io", feature = "serde"))]
+    fn save_solutions_json(
+        &self,
+        dir: &Path,
+        solutions: &[Vec<PlacedPiece>],
+    ) -> std::io::Result<()>;
+    #[cfg(feature = "native-io")]
+    fn load_solutions(&self) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError>;
+    #[cfg(all(feature = "native-io", feature = "serde"))]
+    fn load_solutions_json(&self) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError>;
+    #[cfg(feature = "native-io")]
+    fn count_solutions(&self) -> Result<usize, PersistenceError>;
+    /// Checks that `solutions.txt` lists exactly the solutions in
+    /// `solutions.bin`, both in the working directory.
+    #[cfg(feature = "native-io")]
+    fn verify_solutions(&self) -> Result<(), ConsistencyError>;
+    fn count_live(&self) -> usize;
+    /// Counts unique solutions, stopping once `cap` are found.
+    fn solution_multiplicity(&self, cap: usize) -> usize;
+    /// Whether the puzzle has exactly one solution up to symmetry; stops
+    /// searching at the second.
+    fn has_unique_solution(&self) -> bool {
+        self.solution_multiplicity(2) == 1
+    }
     fn format_solution(&self, solution: &[PlacedPiece]) -> String;
+    /// Box extents along x, y and z.
+    fn dims(&self) -> [usize; 3];
     fn num_pieces(&self) -> usize;
+    fn piece_slots(&self) -> Vec<&[Coord]>;
 }
 
-impl<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize> PuzzleOps
-    for Puzzle<DIM, GRID_SIZE, NUM_PIECES>
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+    > PuzzleOps for Puzzle<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>
 {
     fn solve(&self, max_solutions: Option<usize>) -> Vec<Vec<PlacedPiece>> {
         Puzzle::solve(self, max_solutions)
     }
 
+    fn solve_with_stats(
+        &self,
+        max_solutions: Option<usize>,
+    ) -> (Vec<Vec<PlacedPiece>>, SolveStats) {
+        Puzzle::solve_with_stats(self, max_solutions)
+    }
+
+    fn solve_with_progress(
+        &self,
+        max_solutions: Option<usize>,
+        on_solution: &mut (dyn FnMut(usize) + Send),
+    ) -> (Vec<Vec<PlacedPiece>>, SolveStats) {
+        Puzzle::solve_with_progress(self, max_solutions, on_solution)
+    }
+
+    #[cfg(feature = "native-io")]
     fn save_solutions(&self, solutions: &[Vec<PlacedPiece>]) -> std::io::Result<()> {
-        persistence::save::<DIM, GRID_SIZE, NUM_PIECES>(solutions)
+        persistence::save(self, solutions)
     }
 
-    fn load_solutions(&self) -> Option<Vec<Vec<PlacedPiece>>> {
-        persistence::load_all::<DIM, GRID_SIZE, NUM_PIECES>()
+    #[cfg(feature = "native-io")]
+    fn save_solutions_to(&self, dir: &Path, solutions: &[Vec<PlacedPiece>]) -> std::io::Result<()> {
+        persistence::save_to(self, dir, solutions)
     }
 
-    fn count_solutions(&self) -> Option<usize> {
-        persistence::count::<DIM, GRID_SIZE, NUM_PIECES>()
+    #[cfg(feature = "native-io")]
+    fn save_solutions_compressed(
+        &self,
+        dir: &Path,
+        solutions: &[Vec<PlacedPiece>],
+    ) -> std::io::Result<()> {
+        persistence::save_compressed_to(self, dir, solutions)
+    }
+
+    #[cfg(all(feature = "native-io", feature = "serde"))]
+    fn save_solutions_json(
+        &self,
+        dir: &Path,
+        solutions: &[Vec<PlacedPiece>],
+    ) -> std::io::Result<()> {
+        persistence::save_json_pieces_to::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+            dir,
+            solutions,
+            self.num_pieces(),
+        )
+    }
+
+    #[cfg(feature = "native-io")]
+    fn load_solutions(&self) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+        persistence::load_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+            Path::new(persistence::SOLUTIONS_BIN),
+            self.num_pieces(),
+        )
+    }
+
+    #[cfg(all(feature = "native-io", feature = "serde"))]
+    fn load_solutions_json(&self) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+        persistence::load_json_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+            Path::new(persistence::SOLUTIONS_JSON),
+            self.num_pieces(),
+        )
+    }
+
+    #[cfg(feature = "native-io")]
+    fn count_solutions(&self) -> Result<usize, PersistenceError> {
+        persistence::count_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+            Path::new(persistence::SOLUTIONS_BIN),
+            self.num_pieces(),
+        )
+    }
+
+    #[cfg(feature = "native-io")]
+    fn verify_solutions(&self) -> Result<(), ConsistencyError> {
+        persistence::verify_pieces_in::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+            Path::new("."),
+            self.num_pieces(),
+        )
+    }
+
+    fn count_live(&self) -> usize {
+        Puzzle::count_live(self)
+    }
+
+    fn solution_multiplicity(&self, cap: usize) -> usize {
+        Puzzle::solution_multiplicity(self, cap)
     }
 
     fn format_solution(&self, solution: &[PlacedPiece]) -> String {
-        grid::format_solution::<DIM, GRID_SIZE>(solution)
+        grid::format_solution::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution)
+    }
+
+    fn dims(&self) -> [usize; 3] {
+        [DIM_X, DIM_Y, DIM_Z]
     }
 
     fn num_pieces(&self) -> usize {
-        self.pieces.len()
+        Puzzle::num_pieces(self)
+    }
+
+    fn piece_slots(&self) -> Vec<&[Coord]> {
+        Puzzle::piece_slots(self)
+    }
+}
+
+impl PuzzleOps for pieces::OwnedPuzzle {
+    fn solve(&self, max_solutions: Option<usize>) -> Vec<Vec<PlacedPiece>> {
+        self.with_puzzle(|puzzle| puzzle.solve(max_solutions))
+    }
+
+    fn solve_with_stats(
+        &self,
+        max_solutions: Option<usize>,
+    ) -> (Vec<Vec<PlacedPiece>>, SolveStats) {
+        self.with_puzzle(|puzzle| puzzle.solve_with_stats(max_solutions))
+    }
+
+    fn solve_with_progress(
+        &self,
+        max_solutions: Option<usize>,
+        on_solution: &mut (dyn FnMut(usize) + Send),
+    ) -> (Vec<Vec<PlacedPiece>>, SolveStats) {
+        self.with_puzzle(|puzzle| puzzle.solve_with_progress(max_solutions, on_solution))
+    }
+
+    #[cfg(feature = "native-io")]
+    fn save_solutions(&self, solutions: &[Vec<PlacedPiece>]) -> std::io::Result<()> {
+        self.with_puzzle(|puzzle| puzzle.save_solutions(solutions))
+    }
+
+    #[cfg(feature = "native-io")]
+    fn save_solutions_to(&self, dir: &Path, solutions: &[Vec<PlacedPiece>]) -> std::io::Result<()> {
+        self.with_puzzle(|puzzle| puzzle.save_solutions_to(dir, solutions))
+    }
+
+    #[cfg(feature = "native-io")]
+    fn save_solutions_compressed(
+        &self,
+        dir: &Path,
+        solutions: &[Vec<PlacedPiece>],
+    ) -> std::io::Result<()> {
+        self.with_puzzle(|puzzle| puzzle.save_solutions_compressed(dir, solutions))
+    }
+
+    #[cfg(all(feature = "native-io", feature = "serde"))]
+    fn save_solutions_json(
+        &self,
+        dir: &Path,
+        solutions: &[Vec<PlacedPiece>],
+    ) -> std::io::Result<()> {
+        self.with_puzzle(|puzzle| puzzle.save_solutions_json(dir, solutions))
+    }
+
+    #[cfg(feature = "native-io")]
+    fn load_solutions(&self) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+        self.with_puzzle(|puzzle| puzzle.load_solutions())
+    }
+
+    #[cfg(all(feature = "native-io", feature = "serde"))]
+    fn load_solutions_json(&self) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+        self.with_puzzle(|puzzle| puzzle.load_solutions_json())
+    }
+
+    #[cfg(feature = "native-io")]
+    fn count_solutions(&self) -> Result<usize, PersistenceError> {
+        self.with_puzzle(|puzzle| puzzle.count_solutions())
+    }
+
+    #[cfg(feature = "native-io")]
+    fn verify_solutions(&self) -> Result<(), ConsistencyError> {
+        self.with_puzzle(|puzzle| puzzle.verify_solutions())
+    }
+
+    fn count_live(&self) -> usize {
+        self.with_puzzle(|puzzle| puzzle.count_live())
+    }
+
+    fn solution_multiplicity(&self, cap: usize) -> usize {
+        self.with_puzzle(|puzzle| puzzle.solution_multiplicity(cap))
+    }
+
+    fn format_solution(&self, solution: &[PlacedPiece]) -> String {
+        self.with_puzzle(|puzzle| puzzle.format_solution(solution))
+    }
+
+    fn dims(&self) -> [usize; 3] {
+        [self.dim(); 3]
+    }
+
+    fn num_pieces(&self) -> usize {
+        self.pieces().len()
+    }
+
+    fn piece_slots(&self) -> Vec<&[Coord]> {
+        self.pieces().iter().map(Vec::as_slice).collect()
     }
 }
@@ -2,23 +2,40 @@
 //!
 //! Solves cube packing puzzles where shaped pieces must be arranged to
 //! completely fill a cube. Supports multiple puzzle definitions (Soma 3x3x3,
-//! Bedlam 4x4x4) and provides interactive 3D visualization.
+//! Bedlam 4x4x4, pentominoes in a 10x6 rectangle) and provides interactive 3D
+//! visualization.
 
 mod visualization;
 
+use std::path::{Path, PathBuf};
+
+use clap::builder::PossibleValuesParser;
 use clap::{Parser, Subcommand, ValueEnum};
+use kiss3d::prelude::Color;
 
-use blocker::{pieces, PuzzleOps};
-use pieces::{PlacedPiece, Puzzle, BEDLAM_PUZZLE, SOMA_PUZZLE};
+use blocker::colors::COLORBLIND_PALETTE;
+use blocker::persistence::{self, ConsistencyError, PersistenceError};
+use blocker::{geometry, pieces, PuzzleOps};
+use pieces::{Coord, OwnedPuzzle, PlacedPiece};
+use visualization::GifOptions;
 
 /// Solves cube packing puzzles and visualizes the solutions.
 #[derive(Parser)]
 #[command(name = "blocker")]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// Which puzzle to solve.
-    #[arg(long, short, default_value = "soma")]
-    puzzle: PuzzleChoice,
+    /// Which built-in puzzle to solve; `list` shows them all.
+    #[arg(
+        long,
+        short,
+        default_value = "soma",
+        value_parser = PossibleValuesParser::new(pieces::registry().iter().map(|&(name, _)| name))
+    )]
+    puzzle: String,
+
+    /// Solve a puzzle defined in a TOML file instead of a built-in one.
+    #[arg(long, value_name = "PATH", conflicts_with = "puzzle")]
+    puzzle_file: Option<PathBuf>,
 
     /// Stop after finding this many solutions.
     #[arg(long, short)]
@@ -28,132 +45,506 @@ struct Cli {
     command: Option<Command>,
 }
 
-#[derive(Clone, ValueEnum)]
-enum PuzzleChoice {
-    Soma,
-    Bedlam,
+/// File format written by `solve`.
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum OutputFormat {
+    /// solutions.bin plus a readable solutions.txt.
+    #[default]
+    Binary,
+    /// Gzip-compressed solutions.bin plus solutions.txt.
+    Gzip,
+    /// Portable solutions.json.
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+/// Piece colors for the 3D viewer.
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum PaletteChoice {
+    /// Evenly spaced hues.
+    #[default]
+    Hues,
+    /// Colors chosen to stay apart under red-green color blindness; covers
+    /// up to 13 pieces.
+    Colorblind,
+}
+
+impl PaletteChoice {
+    fn colors(self) -> Vec<Color> {
+        match self {
+            Self::Hues => Vec::new(),
+            Self::Colorblind => COLORBLIND_PALETTE
+                .into_iter()
+                .map(visualization::to_color)
+                .collect(),
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Command {
     /// Solve the puzzle and save solutions to disk.
-    Solve,
+    Solve {
+        /// Output file format.
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+        /// Directory to write the solution files into.
+        #[arg(long, short, default_value = ".")]
+        output: PathBuf,
+        /// Open the viewer on the new solutions once they are saved.
+        #[arg(long)]
+        display: bool,
+    },
     /// Display saved solutions in an interactive 3D viewer.
-    Display,
+    Display {
+        /// Colors to give the pieces.
+        #[arg(long, value_enum, default_value_t)]
+        palette: PaletteChoice,
+    },
     /// Show the number of saved solutions.
-    Count,
+    Count {
+        /// Solve now and count without saving, instead of reading solutions.bin.
+        #[arg(long)]
+        live: bool,
+    },
+    /// Check that solutions.txt lists the same solutions as solutions.bin.
+    Verify,
     /// Export solutions as JavaScript for the website.
-    ExportJs,
+    ExportJs {
+        /// File to write instead of standard output.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Show each piece's size and how many orientations it can take.
+    Analyze,
+    /// List the built-in puzzles with their box sizes.
+    List,
+    /// Render a saved solution to a PNG image without opening the viewer.
+    Render {
+        /// Solution number, as listed in solutions.txt.
+        #[arg(long, default_value_t = 1)]
+        index: usize,
+        /// PNG file to write.
+        #[arg(long)]
+        out: PathBuf,
+        /// Image width in pixels.
+        #[arg(long, default_value_t = 800)]
+        width: u32,
+        /// Image height in pixels.
+        #[arg(long, default_value_t = 600)]
+        height: u32,
+        /// Camera angle around the vertical axis, in degrees.
+        #[arg(long, default_value_t = 0.0)]
+        angle: f32,
+    },
+    /// Export a saved solution's explode animation as an animated GIF.
+    ExportGif {
+        /// Solution number, as listed in solutions.txt.
+        #[arg(long, default_value_t = 1)]
+        index: usize,
+        /// GIF file to write.
+        #[arg(long, default_value = "solution.gif")]
+        out: PathBuf,
+        /// Number of frames from assembled to fully exploded.
+        #[arg(long, default_value_t = GifOptions::default().frames)]
+        frames: usize,
+        /// Camera angle around the vertical axis, in degrees.
+        #[arg(long, default_value_t = 0.0)]
+        angle: f32,
+    },
+}
+
+/// 3D visualization for any puzzle (binary-only, not in the library).
+trait PuzzleDisplay {
+    fn display_solutions(&self, solutions: Vec<Vec<PlacedPiece>>, palette: &[Color]);
+    fn render_png(&self, solution: &[PlacedPiece], width: u32, height: u32, angle: f32) -> Vec<u8>;
+    fn export_gif(
+        &self,
+        solution: &[PlacedPiece],
+        options: &GifOptions,
+    ) -> Result<Vec<u8>, gif::EncodingError>;
 }
 
-/// Extends PuzzleOps with 3D visualization (binary-only, not in the library).
-trait PuzzleDisplay: PuzzleOps {
-    fn display_solutions(&self, solutions: Vec<Vec<PlacedPiece>>);
+/// Calls the `visualization` function built for the box `dims`, or exits
+/// when the viewer has no build for that box. A puzzle registered with a
+/// new box shape solves and saves without an arm here, but needs one to be
+/// shown.
+macro_rules! for_box {
+    ($dims:expr, $function:ident($($arg:expr),*)) => {
+        match $dims {
+            [1, 1, 1] => visualization::$function::<1, 1, 1, 1>($($arg),*),
+            [2, 2, 2] => visualization::$function::<2, 2, 2, 8>($($arg),*),
+            [3, 3, 3] => visualization::$function::<3, 3, 3, 27>($($arg),*),
+            [4, 4, 4] => visualization::$function::<4, 4, 4, 64>($($arg),*),
+            [5, 5, 5] => visualization::$function::<5, 5, 5, 125>($($arg),*),
+            [6, 6, 6] => visualization::$function::<6, 6, 6, 216>($($arg),*),
+            [10, 6, 1] => visualization::$function::<10, 6, 1, 60>($($arg),*),
+            [20, 3, 1] => visualization::$function::<20, 3, 1, 60>($($arg),*),
+            [x, y, z] => {
+                eprintln!("The viewer has no build for a {x}x{y}x{z} box");
+                std::process::exit(1);
+            }
+        }
+    };
 }
 
-impl<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize> PuzzleDisplay
-    for Puzzle<DIM, GRID_SIZE, NUM_PIECES>
-{
-    fn display_solutions(&self, solutions: Vec<Vec<PlacedPiece>>) {
-        visualization::display::<DIM, GRID_SIZE>(solutions, self.pieces.len());
+impl PuzzleDisplay for dyn PuzzleOps + '_ {
+    fn display_solutions(&self, solutions: Vec<Vec<PlacedPiece>>, palette: &[Color]) {
+        for_box!(
+            self.dims(),
+            display_with_palette(solutions, self.num_pieces(), palette)
+        )
+    }
+
+    fn render_png(&self, solution: &[PlacedPiece], width: u32, height: u32, angle: f32) -> Vec<u8> {
+        for_box!(
+            self.dims(),
+            render_to_png(solution, self.num_pieces(), width, height, angle)
+        )
+    }
+
+    fn export_gif(
+        &self,
+        solution: &[PlacedPiece],
+        options: &GifOptions,
+    ) -> Result<Vec<u8>, gif::EncodingError> {
+        for_box!(
+            self.dims(),
+            export_gif(solution, self.num_pieces(), options)
+        )
     }
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    let puzzle: &dyn PuzzleDisplay = match cli.puzzle {
-        PuzzleChoice::Soma => &SOMA_PUZZLE,
-        PuzzleChoice::Bedlam => &BEDLAM_PUZZLE,
+    let loaded;
+    let puzzle: &dyn PuzzleOps = match &cli.puzzle_file {
+        Some(path) => match pieces::load_puzzle(path) {
+            Ok(puzzle) => {
+                warn_duplicate_shapes(&puzzle);
+                loaded = puzzle;
+                &loaded
+            }
+            Err(error) => {
+                eprintln!("Could not load {}: {}", path.display(), error);
+                std::process::exit(1);
+            }
+        },
+        None => pieces::find_puzzle(&cli.puzzle).expect("clap only accepts registered names"),
     };
 
     run_with_puzzle(puzzle, cli.command, cli.limit);
 }
 
-fn run_with_puzzle(
-    puzzle: &dyn PuzzleDisplay,
-    command: Option<Command>,
-    limit: Option<usize>,
-) {
+/// Points out pieces in a loaded puzzle that are the same shape, since the
+/// solver counts every swap of them as another solution.
+fn warn_duplicate_shapes(puzzle: &OwnedPuzzle) {
+    let pieces: Vec<&[Coord]> = puzzle.pieces().iter().map(Vec::as_slice).collect();
+    for (a, b) in pieces::duplicate_shapes(&pieces) {
+        eprintln!(
+            "Warning: pieces {a} and {b} are the same shape; solutions that swap them count \
+             separately"
+        );
+    }
+}
+
+fn run_with_puzzle(puzzle: &dyn PuzzleOps, command: Option<Command>, limit: Option<usize>) {
     match command {
-        Some(Command::Solve) => {
-            run_solver(puzzle, limit);
+        Some(Command::Solve {
+            format,
+            output,
+            display,
+        }) => {
+            let solutions = run_solver(puzzle, limit, format, &output);
+            if display && !solutions.is_empty() {
+                show_solutions(puzzle, solutions, &[]);
+            }
+        }
+        Some(Command::Display { palette }) => run_display(puzzle, &palette.colors()),
+        Some(Command::Count { live }) => run_count(puzzle, live),
+        Some(Command::Verify) => run_verify(puzzle),
+        Some(Command::ExportJs { out }) => run_export_js(puzzle, limit, out.as_deref()),
+        Some(Command::Analyze) => run_analyze(puzzle),
+        Some(Command::List) => run_list(),
+        Some(Command::Render {
+            index,
+            out,
+            width,
+            height,
+            angle,
+        }) => run_render(puzzle, index, &out, width, height, angle),
+        Some(Command::ExportGif {
+            index,
+            out,
+            frames,
+            angle,
+        }) => {
+            let options = GifOptions {
+                frames,
+                camera_angle: angle.to_radians(),
+                ..GifOptions::default()
+            };
+            run_export_gif(puzzle, index, &out, &options);
         }
-        Some(Command::Display) => run_display(puzzle),
-        Some(Command::Count) => run_count(puzzle),
-        Some(Command::ExportJs) => run_export_js(puzzle, limit),
         None => {
-            let solutions = run_solver(puzzle, limit);
+            let solutions = run_solver(puzzle, limit, OutputFormat::Binary, Path::new("."));
             if !solutions.is_empty() {
-                println!("Controls: Left/Right navigate, W/S explode, R reset");
-                puzzle.display_solutions(solutions);
+                show_solutions(puzzle, solutions, &[]);
             }
         }
     }
 }
 
+/// Opens the viewer on `solutions`, or prints the first one as text when
+/// there is no display to open a window on, as over SSH.
+fn show_solutions(puzzle: &dyn PuzzleOps, solutions: Vec<Vec<PlacedPiece>>, palette: &[Color]) {
+    if has_display() {
+        println!("Controls: {}", visualization::CONTROLS);
+        puzzle.display_solutions(solutions, palette);
+        return;
+    }
+    eprintln!("No display found, so printing the first solution instead of opening the viewer");
+    eprintln!("Use the `solve` or `count` commands on machines without a display");
+    if let Some(solution) = solutions.first() {
+        print!("{}", puzzle.format_solution(solution));
+    }
+}
+
+/// Whether the viewer can open a window. Unix desktops other than macOS
+/// need an X11 or Wayland display, which a headless server lacks; the
+/// viewer would panic creating its window there.
+fn has_display() -> bool {
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()))
+}
+
+/// Exits with an error unless there is a display, which `command` needs for
+/// the hidden window it renders in; kiss3d cannot render without one.
+fn require_display(command: &str) {
+    if !has_display() {
+        eprintln!("No display found; `{command}` renders in a hidden window, which needs one");
+        eprintln!("Run it where X11 or Wayland is available, or under Xvfb");
+        std::process::exit(1);
+    }
+}
+
 /// Solves the puzzle, saves to disk, and returns the solutions.
-fn run_solver(puzzle: &dyn PuzzleDisplay, limit: Option<usize>) -> Vec<Vec<PlacedPiece>> {
-    let solutions = puzzle.solve(limit);
+fn run_solver(
+    puzzle: &dyn PuzzleOps,
+    limit: Option<usize>,
+    format: OutputFormat,
+    output: &Path,
+) -> Vec<Vec<PlacedPiece>> {
+    // live counter on stderr so stdout only carries the summary
+    let (solutions, stats) = puzzle.solve_with_progress(limit, &mut |count| {
+        eprint!("\rSolving... {} solutions found", count);
+    });
+    if !solutions.is_empty() {
+        eprintln!();
+    }
 
-    if let Err(e) = puzzle.save_solutions(&solutions) {
+    let (saved, written) = match format {
+        OutputFormat::Binary => (
+            puzzle.save_solutions_to(output, &solutions),
+            "solutions.txt and solutions.bin",
+        ),
+        OutputFormat::Gzip => (
+            puzzle.save_solutions_compressed(output, &solutions),
+            "solutions.txt and compressed solutions.bin",
+        ),
+        #[cfg(feature = "serde")]
+        OutputFormat::Json => (
+            puzzle.save_solutions_json(output, &solutions),
+            "solutions.json",
+        ),
+    };
+
+    if let Err(e) = saved {
         eprintln!("Failed to save solutions: {}", e);
     } else {
-        println!("Found {} solutions", solutions.len());
-        println!("Wrote solutions.txt and solutions.bin");
+        println!("Found {} solutions in {:.2?}", solutions.len(), stats.elapsed);
+        println!(
+            "Explored {} states ({} overlapping placements, {} symmetric duplicates skipped)",
+            stats.stack_pushes, stats.overlap_rejections, stats.dedup_hits
+        );
+        println!("Wrote {} to {}", written, output.display());
     }
 
     solutions
 }
 
 /// Loads and displays saved solutions.
-fn run_display(puzzle: &dyn PuzzleDisplay) {
+fn run_display(puzzle: &dyn PuzzleOps, palette: &[Color]) {
     match puzzle.load_solutions() {
-        Some(solutions) => {
+        Ok(solutions) => {
             println!("Loaded {} solutions", solutions.len());
-            println!("Controls: Left/Right navigate, W/S explode, R reset");
-            puzzle.display_solutions(solutions);
-        }
-        None => {
-            eprintln!("No compatible solutions.bin found. Run 'blocker solve' first.");
+            show_solutions(puzzle, solutions, palette);
         }
+        Err(error) => report_load_error(&error),
     }
 }
 
-/// Prints the count of saved solutions.
-fn run_count(puzzle: &dyn PuzzleDisplay) {
+/// Prints the count of saved solutions, or of a fresh solve with `live`.
+fn run_count(puzzle: &dyn PuzzleOps, live: bool) {
+    if live {
+        println!("{} solutions", puzzle.count_live());
+        return;
+    }
     match puzzle.count_solutions() {
-        Some(count) => println!("{} solutions", count),
-        None => eprintln!("No compatible solutions.bin found. Run 'blocker solve' first."),
+        Ok(count) => println!("{} solutions", count),
+        Err(error) => report_load_error(&error),
+    }
+}
+
+/// Checks the saved text and binary solution files against each other,
+/// exiting with an error when they differ.
+fn run_verify(puzzle: &dyn PuzzleOps) {
+    match puzzle.verify_solutions() {
+        Ok(()) => println!("solutions.txt matches solutions.bin"),
+        Err(ConsistencyError::Binary(error)) => {
+            report_load_error(&error);
+            std::process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
     }
 }
 
-/// Exports solutions as JavaScript array for the website.
-fn run_export_js(puzzle: &dyn PuzzleDisplay, limit: Option<usize>) {
+/// Renders saved solution number `index` (1-based) to a PNG file.
+fn run_render(
+    puzzle: &dyn PuzzleOps,
+    index: usize,
+    out: &Path,
+    width: u32,
+    height: u32,
+    angle: f32,
+) {
+    require_display("render");
+    let Some(solution) = load_solution(puzzle, index) else {
+        return;
+    };
+    let png = puzzle.render_png(&solution, width, height, angle.to_radians());
+    write_image(out, &png, index);
+}
+
+/// Writes the explode animation of saved solution number `index` (1-based)
+/// to a GIF file.
+fn run_export_gif(puzzle: &dyn PuzzleOps, index: usize, out: &Path, options: &GifOptions) {
+    require_display("export-gif");
+    let Some(solution) = load_solution(puzzle, index) else {
+        return;
+    };
+    match puzzle.export_gif(&solution, options) {
+        Ok(gif) => write_image(out, &gif, index),
+        Err(error) => {
+            eprintln!("Failed to encode the animation: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Loads saved solution number `index` (1-based), reporting why when it
+/// cannot.
+fn load_solution(puzzle: &dyn PuzzleOps, index: usize) -> Option<Vec<PlacedPiece>> {
+    let mut solutions = match puzzle.load_solutions() {
+        Ok(solutions) => solutions,
+        Err(error) => {
+            report_load_error(&error);
+            return None;
+        }
+    };
+    if index == 0 || index > solutions.len() {
+        eprintln!(
+            "No solution {index}; solutions.bin holds {}",
+            solutions.len()
+        );
+        std::process::exit(1);
+    }
+    Some(solutions.swap_remove(index - 1))
+}
+
+/// Writes an encoded image of solution `index`, exiting on failure.
+fn write_image(out: &Path, bytes: &[u8], index: usize) {
+    match std::fs::write(out, bytes) {
+        Ok(()) => println!("Wrote solution {index} to {}", out.display()),
+        Err(error) => {
+            eprintln!("Failed to write {}: {error}", out.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Explains why `solutions.bin` could not be loaded.
+fn report_load_error(error: &PersistenceError) {
+    eprintln!("Could not load solutions.bin: {error}");
+    // a fresh solve replaces a missing file or one saved for another puzzle
+    if matches!(
+        error,
+        PersistenceError::NotFound
+            | PersistenceError::DimensionMismatch { .. }
+            | PersistenceError::PieceCountMismatch { .. }
+    ) {
+        eprintln!("Run 'blocker solve' first.");
+    }
+}
+
+/// Exports solutions as JavaScript array for the website, to `out` or
+/// standard output.
+fn run_export_js(puzzle: &dyn PuzzleOps, limit: Option<usize>, out: Option<&Path>) {
     let solutions = puzzle.solve(limit);
 
-    println!("const SOLUTIONS = [");
-    for (i, solution) in solutions.iter().enumerate() {
-        let pieces: Vec<String> = solution
-            .iter()
-            .map(|placed| {
-                let cubes: Vec<String> = placed
-                    .cubes()
-                    .iter()
-                    .map(|&(x, y, z)| format!("[{},{},{}]", x, y, z))
-                    .collect();
-                format!("[{}, [{}]]", placed.piece_index, cubes.join(","))
-            })
-            .collect();
-        let trailing = if i < solutions.len() - 1 { "," } else { "" };
-        println!("  [{}]{}", pieces.join(", "), trailing);
-    }
-    println!("];");
+    let result = match out {
+        Some(out) => std::fs::File::create(out)
+            .and_then(|file| persistence::write_js(std::io::BufWriter::new(file), &solutions)),
+        None => persistence::write_js(std::io::stdout().lock(), &solutions),
+    };
+    if let Err(error) = result {
+        let target = out.map_or("standard output".into(), |out| out.display().to_string());
+        eprintln!("Failed to write {target}: {error}");
+        std::process::exit(1);
+    }
+}
+
+/// Prints each piece's cube count, orientation count and symmetry order.
+fn run_analyze(puzzle: &dyn PuzzleOps) {
+    println!("piece  cubes  orientations  symmetry");
+    for (index, piece) in puzzle.piece_slots().into_iter().enumerate() {
+        println!(
+            "{:>5}  {:>5}  {:>12}  {:>8}",
+            index,
+            piece.len(),
+            geometry::orientation_count(piece),
+            geometry::symmetry_order(piece)
+        );
+    }
+}
+
+/// Prints each built-in puzzle's name, box size and piece count.
+fn run_list() {
+    let registry = pieces::registry();
+    let width = registry
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    for &(name, puzzle) in registry {
+        let [x, y, z] = puzzle.dims();
+        let dims = format!("{x}x{y}x{z}");
+        println!("{name:<width$}  {dims:<8}  {} pieces", puzzle.num_pieces());
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use pieces::SOMA_PUZZLE;
 
     #[test]
     fn test_solutions_snapshot() {
@@ -3,9 +3,13 @@
 //! Binary format for `solutions.bin` (little endian):
 //! - 4 bytes: magic (`BLKR`)
 //! - u8: format version
-//! - u8: puzzle dim
+//! - 3 bytes: box extents (x, y, z)
 //! - u8: puzzle grid size
 //! - u8: puzzle piece count
+//! - u32: byte length of the piece definitions that follow
+//! - piece definitions:
+//!   - 2 bytes: chiral pair piece indices (`0xff 0xff` when there is none)
+//!   - repeat per piece: u8 cube count, then 3 bytes (x, y, z) per cube
 //! - u32: solution count
 //! - repeat per solution:
 //!   - u32: piece count
@@ -13,131 +17,759 @@
 //!     - u32: piece index (0-based)
 //!     - u32: cube count
 //!     - repeat per cube: 3 bytes (x, y, z)
+//!
+//! Version 4, the packed format, has the same header but stores each solution
+//! as `ceil(GRID_SIZE / 2)` bytes instead: one piece-id nibble per cell, low
+//! nibble first, with `0xf` for a cell no piece covers. It only holds
+//! puzzles with fewer than 16 pieces. See [`save_packed_to`].
+//!
+//! The whole stream may be gzip-compressed; readers detect this from the gzip
+//! magic bytes, so compressed and plain files load the same way.
+//!
+//! Saving writes each file to a temporary file beside it and renames that
+//! into place, so an interrupted save leaves the previous file intact.
+//!
+//! The piece definitions make a file self-describing; see [`load_definitions`].
+//! Version 2 files have no definitions block, and version 1 files additionally
+//! stored a single cube dimension byte in place of the three extents. Both are
+//! still read.
+//!
+//! [`write_csv`] exports solutions as flat CSV rows, one per cube, and
+//! [`read_csv`] loads them back.
+//!
+//! `solutions.txt` repeats the binary file's solutions as
+//! [`format_solution`] grids; [`verify_consistency`] checks that the two
+//! still agree.
+//!
+//! With the `serde` feature, `solutions.json` holds the same data as a
+//! portable document:
+//! `{"dims":[3,3,3],"grid_size":27,"num_pieces":7,"solutions":[[[index,[[x,y,z],...]],...]]}`.
 
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rustc_hash::FxHashSet;
 
-use crate::grid::format_solution;
-use crate::pieces::{PlacedPiece, MAX_CUBES};
+use crate::grid::{coord_to_idx, format_solution, idx_to_coord, solution_to_grid};
+use crate::pieces::{full_piece_mask, Coord, PlacedPiece, Puzzle, ValidationError, MAX_CUBES};
 
-const SOLUTIONS_BIN: &str = "solutions.bin";
+pub(crate) const SOLUTIONS_BIN: &str = "solutions.bin";
 const SOLUTIONS_TXT: &str = "solutions.txt";
+#[cfg(feature = "serde")]
+pub(crate) const SOLUTIONS_JSON: &str = "solutions.json";
 const FILE_MAGIC: [u8; 4] = *b"BLKR";
-const FILE_VERSION: u8 = 1;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const FILE_VERSION: u8 = 3;
+// current header, solutions as piece-id nibbles per cell
+const PACKED_FILE_VERSION: u8 = 4;
+// per-axis extents but no piece definitions
+const BOX_FILE_VERSION: u8 = 2;
+// single cube dimension instead of per-axis extents
+const CUBE_FILE_VERSION: u8 = 1;
+const NO_CHIRAL_PAIR: u8 = u8::MAX;
+// the packed format's nibble for a cell no piece covers
+const PACKED_EMPTY: u8 = 0xf;
+
+/// Why a solutions file could not be loaded.
+#[derive(Debug)]
+pub enum PersistenceError {
+    /// The file does not exist.
+    NotFound,
+    /// The file is not a solutions file.
+    BadMagic,
+    /// The file uses a format version this build cannot read.
+    VersionMismatch(u8),
+    /// The file was saved for a box with other extents.
+    DimensionMismatch {
+        expected: [usize; 3],
+        found: [usize; 3],
+    },
+    /// The file was saved for a puzzle with another number of pieces.
+    PieceCountMismatch { expected: usize, found: usize },
+    /// The file ends before the data its header announces.
+    Truncated,
+    /// The file is complete but holds data no valid solution can have.
+    Corrupt,
+    /// A strict load found a solution that does not solve the puzzle.
+    InvalidSolution {
+        index: usize,
+        error: ValidationError,
+    },
+    /// Any other I/O failure.
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for PersistenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "file not found"),
+            Self::BadMagic => write!(f, "not a solutions file"),
+            Self::VersionMismatch(version) => {
+                write!(f, "unsupported format version {version}")
+            }
+            Self::DimensionMismatch { expected, found } => write!(
+                f,
+                "saved for a {}x{}x{} box, expected {}x{}x{}",
+                found[0], found[1], found[2], expected[0], expected[1], expected[2]
+            ),
+            Self::PieceCountMismatch { expected, found } => {
+                write!(f, "saved for {found} pieces, expected {expected}")
+            }
+            Self::Truncated => write!(f, "file is truncated"),
+            Self::Corrupt => write!(f, "file is corrupt"),
+            Self::InvalidSolution { index, error } => {
+                write!(f, "solution {} is invalid: {error}", index + 1)
+            }
+            Self::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for PersistenceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PersistenceError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound,
+            std::io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::Io(error),
+        }
+    }
+}
+
+/// Saves solutions to both binary and text files in the working directory.
+pub fn save<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    solutions: &[Vec<PlacedPiece>],
+) -> std::io::Result<()> {
+    save_to(puzzle, Path::new("."), solutions)
+}
 
-/// Saves solutions to both binary and text files.
-pub fn save<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize>(
+/// Saves `solutions.txt` and `solutions.bin` into `dir`, creating it if needed.
+pub fn save_to<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    dir: &Path,
     solutions: &[Vec<PlacedPiece>],
 ) -> std::io::Result<()> {
-    save_text::<DIM, GRID_SIZE>(solutions)?;
-    save_binary::<DIM, GRID_SIZE, NUM_PIECES>(solutions)?;
+    save_files(puzzle, dir, solutions, false)
+}
+
+/// Like [`save`], but gzip-compresses the binary file.
+///
+/// Worth it for large solution sets such as Bedlam's; `load_all` and `count`
+/// read either form.
+pub fn save_compressed<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    solutions: &[Vec<PlacedPiece>],
+) -> std::io::Result<()> {
+    save_compressed_to(puzzle, Path::new("."), solutions)
+}
+
+/// Like [`save_to`], but gzip-compresses the binary file.
+pub fn save_compressed_to<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    dir: &Path,
+    solutions: &[Vec<PlacedPiece>],
+) -> std::io::Result<()> {
+    save_files(puzzle, dir, solutions, true)
+}
+
+fn save_files<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    dir: &Path,
+    solutions: &[Vec<PlacedPiece>],
+    compress: bool,
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    save_text::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(&dir.join(SOLUTIONS_TXT), solutions)?;
+    save_binary(puzzle, &dir.join(SOLUTIONS_BIN), solutions, compress)?;
     Ok(())
 }
 
-/// Saves solutions in human-readable text format.
-fn save_text<const DIM: usize, const GRID_SIZE: usize>(
+/// Saves `solutions.txt` and a packed `solutions.bin` into `dir`.
+///
+/// The packed format stores one piece-id nibble per cell rather than a list
+/// of cube coordinates, so a Soma solution takes 14 bytes instead of 141.
+/// It holds puzzles with fewer than 16 pieces; others fail with
+/// [`std::io::ErrorKind::InvalidInput`]. The loaders read it like any other
+/// version, but return each solution's pieces in index order, with their
+/// cubes in cell order.
+pub fn save_packed_to<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    dir: &Path,
     solutions: &[Vec<PlacedPiece>],
 ) -> std::io::Result<()> {
-    let mut file = File::create(SOLUTIONS_TXT)?;
-    writeln!(file, "Found {} solutions:\n", solutions.len())?;
-    for (i, solution) in solutions.iter().enumerate() {
-        writeln!(file, "Solution {}:", i + 1)?;
-        write!(
-            file,
-            "{}",
-            format_solution::<DIM, GRID_SIZE>(solution)
-        )?;
-        writeln!(file)?;
+    check_packable(puzzle.num_pieces())?;
+    std::fs::create_dir_all(dir)?;
+    save_text::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(&dir.join(SOLUTIONS_TXT), solutions)?;
+    write_atomic(&dir.join(SOLUTIONS_BIN), |file| {
+        write_packed(puzzle, file, solutions)
+    })
+}
+
+/// Rewrites the binary file at `from` in the packed format at `to` and
+/// returns how many solutions it holds.
+///
+/// Any version the loaders read can be converted, compressed or not, and
+/// `from` may be `to`.
+pub fn pack_file<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    from: &Path,
+    to: &Path,
+) -> Result<usize, PersistenceError> {
+    check_packable(puzzle.num_pieces())?;
+    let solutions = load_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(from, puzzle.num_pieces())?;
+    write_atomic(to, |file| write_packed(puzzle, file, &solutions))?;
+    Ok(solutions.len())
+}
+
+/// Fails unless every piece id fits in a nibble beside [`PACKED_EMPTY`].
+fn check_packable(num_pieces: usize) -> std::io::Result<()> {
+    if num_pieces <= PACKED_EMPTY as usize {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "the packed format holds at most 15 pieces",
+        ))
     }
-    Ok(())
+}
+
+/// Writes the file at `path` through `write` without ever leaving it half
+/// written.
+///
+/// The data goes to a temporary file beside `path`, which is renamed over it
+/// only once complete, so a reader sees either the old file or the new one.
+/// If writing fails, or the process dies first, the old file stays as it
+/// was.
+fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let temp_path = temp_path(path);
+    let written = File::create(&temp_path).and_then(|file| {
+        let mut file = BufWriter::new(file);
+        write(&mut file)?;
+        file.flush()
+    });
+    let result = written.and_then(|()| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        // the temporary file may not exist; the write error matters more
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// `path` with `.tmp` appended to its file name.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// Saves solutions in human-readable text format.
+fn save_text<const DIM_X: usize, const DIM_Y: usize, const DIM_Z: usize, const GRID_SIZE: usize>(
+    path: &Path,
+    solutions: &[Vec<PlacedPiece>],
+) -> std::io::Result<()> {
+    write_atomic(path, |file| {
+        write!(file, "{}", text_header(solutions.len()))?;
+        for (index, solution) in solutions.iter().enumerate() {
+            write!(
+                file,
+                "{}",
+                text_entry::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(index, solution)
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// The line `solutions.txt` opens with, followed by a blank line.
+fn text_header(count: usize) -> String {
+    format!("Found {count} solutions:\n\n")
+}
+
+/// Solution number `index + 1` as `solutions.txt` lists it: a heading, the
+/// [`format_solution`] grid and a blank line.
+fn text_entry<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    index: usize,
+    solution: &[PlacedPiece],
+) -> String {
+    format!(
+        "Solution {}:\n{}\n",
+        index + 1,
+        format_solution::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution)
+    )
 }
 
 /// Saves solutions in compact binary format for fast loading.
-fn save_binary<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize>(
+fn save_binary<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    path: &Path,
     solutions: &[Vec<PlacedPiece>],
+    compress: bool,
 ) -> std::io::Result<()> {
-    let mut file = File::create(SOLUTIONS_BIN)?;
-    file.write_all(&FILE_MAGIC)?;
-    file.write_all(&[FILE_VERSION, DIM as u8, GRID_SIZE as u8, NUM_PIECES as u8])?;
+    write_atomic(path, |file| {
+        if compress {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            write_binary(puzzle, &mut encoder, solutions)?;
+            encoder.finish()?;
+            Ok(())
+        } else {
+            write_binary(puzzle, file, solutions)
+        }
+    })
+}
 
+/// Writes the binary format, header included, to `file`.
+fn write_binary<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    file: &mut impl Write,
+    solutions: &[Vec<PlacedPiece>],
+) -> std::io::Result<()> {
+    write_header(puzzle, file, FILE_VERSION)?;
     file.write_all(&(solutions.len() as u32).to_le_bytes())?;
+    for solution in solutions {
+        write_solution(file, solution)?;
+    }
+    Ok(())
+}
 
+/// Writes the packed format, header included, to `file`.
+fn write_packed<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    file: &mut impl Write,
+    solutions: &[Vec<PlacedPiece>],
+) -> std::io::Result<()> {
+    write_header(puzzle, file, PACKED_FILE_VERSION)?;
+    file.write_all(&(solutions.len() as u32).to_le_bytes())?;
     for solution in solutions {
-        file.write_all(&(solution.len() as u32).to_le_bytes())?;
-        for placed in solution {
-            file.write_all(&(placed.piece_index as u32).to_le_bytes())?;
-            file.write_all(&(placed.cube_count as u32).to_le_bytes())?;
-            for &(x, y, z) in placed.cubes() {
-                file.write_all(&[x as u8, y as u8, z as u8])?;
-            }
-        }
+        write_packed_solution::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(file, solution)?;
     }
+    Ok(())
+}
 
+/// Writes everything before the solution count: magic, `version`, metadata
+/// and the piece definitions.
+fn write_header<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    file: &mut impl Write,
+    version: u8,
+) -> std::io::Result<()> {
+    file.write_all(&FILE_MAGIC)?;
+    file.write_all(&[
+        version,
+        DIM_X as u8,
+        DIM_Y as u8,
+        DIM_Z as u8,
+        GRID_SIZE as u8,
+        puzzle.num_pieces() as u8,
+    ])?;
+
+    let definitions = encode_definitions(puzzle);
+    file.write_all(&(definitions.len() as u32).to_le_bytes())?;
+    file.write_all(&definitions)
+}
+
+/// Writes one solution record.
+fn write_solution(file: &mut impl Write, solution: &[PlacedPiece]) -> std::io::Result<()> {
+    file.write_all(&(solution.len() as u32).to_le_bytes())?;
+    for placed in solution {
+        file.write_all(&(placed.piece_index as u32).to_le_bytes())?;
+        file.write_all(&(placed.cube_count as u32).to_le_bytes())?;
+        for &(x, y, z) in placed.cubes() {
+            file.write_all(&[x as u8, y as u8, z as u8])?;
+        }
+    }
     Ok(())
 }
 
+/// Writes one packed solution record.
+fn write_packed_solution<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    file: &mut impl Write,
+    solution: &[PlacedPiece],
+) -> std::io::Result<()> {
+    // grid cells hold piece numbers from 1, so empty cells wrap to 0xf
+    let nibble = |cell: u8| cell.wrapping_sub(1) & PACKED_EMPTY;
+    let grid = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
+    let bytes: Vec<u8> = grid
+        .chunks(2)
+        .map(|pair| nibble(pair[0]) | pair.get(1).map_or(PACKED_EMPTY, |&cell| nibble(cell)) << 4)
+        .collect();
+    file.write_all(&bytes)
+}
+
+/// Writes a binary solutions file one solution at a time.
+///
+/// The header goes out on creation with a solution count of zero, each
+/// [`write`](Self::write) appends a record, and [`finish`](Self::finish)
+/// seeks back to store the real count. Feeding it from
+/// [`Puzzle::solve_iter`] saves a solve without holding every solution in
+/// memory. Dropping the writer unfinished still stores the count, but
+/// ignores any error doing so.
+///
+/// The count is patched in place, so the output cannot be gzip-compressed.
+pub struct SolutionWriter<W: Write + Seek = BufWriter<File>> {
+    // None once finished
+    writer: Option<W>,
+    count_offset: u64,
+    count: u32,
+}
+
+impl SolutionWriter {
+    /// Creates the file at `path` and writes its header for `puzzle`.
+    pub fn create<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+    >(
+        puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+        path: &Path,
+    ) -> std::io::Result<Self> {
+        Self::new(puzzle, BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Seek> SolutionWriter<W> {
+    /// Writes the header for `puzzle` to `writer` at its current position.
+    pub fn new<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+    >(
+        puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+        mut writer: W,
+    ) -> std::io::Result<Self> {
+        write_header(puzzle, &mut writer, FILE_VERSION)?;
+        let count_offset = writer.stream_position()?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(Self {
+            writer: Some(writer),
+            count_offset,
+            count: 0,
+        })
+    }
+
+    /// Appends one solution.
+    pub fn write(&mut self, solution: &[PlacedPiece]) -> std::io::Result<()> {
+        let writer = self.writer.as_mut().expect("writer used after finish");
+        write_solution(writer, solution)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Number of solutions written so far.
+    pub fn count(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Stores the final solution count and returns the flushed writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let mut writer = self.writer.take().expect("writer used after finish");
+        store_count(&mut writer, self.count_offset, self.count)?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write + Seek> Drop for SolutionWriter<W> {
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.as_mut() {
+            let _ = store_count(writer, self.count_offset, self.count);
+        }
+    }
+}
+
+/// Overwrites the solution count at `offset`, then returns to the end.
+fn store_count(writer: &mut (impl Write + Seek), offset: u64, count: u32) -> std::io::Result<()> {
+    let end = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(offset))?;
+    writer.write_all(&count.to_le_bytes())?;
+    writer.seek(SeekFrom::Start(end))?;
+    writer.flush()
+}
+
+/// Encodes the chiral pair and piece cube-sets for the definitions block.
+fn encode_definitions<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+) -> Vec<u8> {
+    let mut definitions = match puzzle.chiral_pair {
+        Some((a, b)) => vec![a as u8, b as u8],
+        None => vec![NO_CHIRAL_PAIR; 2],
+    };
+    for piece in puzzle.piece_slots() {
+        definitions.push(piece.len() as u8);
+        for &(x, y, z) in piece.iter() {
+            definitions.extend_from_slice(&[x as u8, y as u8, z as u8]);
+        }
+    }
+    definitions
+}
+
+/// Wraps `reader` in a gzip decoder when the stream starts with the gzip magic.
+fn decode<'a>(mut reader: impl BufRead + 'a) -> std::io::Result<Box<dyn Read + 'a>> {
+    let compressed = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if compressed {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Opens a binary solutions file, decompressing it if needed.
+fn open_binary(path: &Path) -> Result<Box<dyn Read>, PersistenceError> {
+    Ok(decode(BufReader::new(File::open(path)?))?)
+}
+
 #[inline]
-fn read_u32<R: Read>(reader: &mut R) -> Option<u32> {
+fn read_u32<R: Read + ?Sized>(reader: &mut R) -> Result<u32, PersistenceError> {
     let mut buffer = [0u8; 4];
-    reader.read_exact(&mut buffer).ok()?;
-    Some(u32::from_le_bytes(buffer))
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
 }
 
-#[inline]
-fn expected_piece_mask(num_pieces: usize) -> u32 {
-    if num_pieces == 32 {
-        u32::MAX
+/// Reads the metadata after the magic, checks it matches a puzzle of this
+/// shape with `num_pieces` pieces and returns the format version.
+///
+/// Accepts the per-axis header of versions 2 to 4 and, for cube puzzles, the
+/// version 1 header with a single dimension byte. The definitions block of
+/// versions 3 and 4 is left unread.
+fn read_metadata<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    file: &mut dyn Read,
+    num_pieces: usize,
+) -> Result<u8, PersistenceError> {
+    let mut version = [0u8; 1];
+    file.read_exact(&mut version)?;
+
+    let extents = match version[0] {
+        PACKED_FILE_VERSION | FILE_VERSION | BOX_FILE_VERSION => {
+            let mut extents = [0u8; 3];
+            file.read_exact(&mut extents)?;
+            extents.map(usize::from)
+        }
+        CUBE_FILE_VERSION => {
+            let mut dim = [0u8; 1];
+            file.read_exact(&mut dim)?;
+            [dim[0] as usize; 3]
+        }
+        other => return Err(PersistenceError::VersionMismatch(other)),
+    };
+
+    let mut counts = [0u8; 2];
+    file.read_exact(&mut counts)?;
+    let grid_size = counts[0] as usize;
+    let piece_count = counts[1] as usize;
+
+    if extents != [DIM_X, DIM_Y, DIM_Z] {
+        return Err(PersistenceError::DimensionMismatch {
+            expected: [DIM_X, DIM_Y, DIM_Z],
+            found: extents,
+        });
+    }
+    if piece_count != num_pieces {
+        return Err(PersistenceError::PieceCountMismatch {
+            expected: num_pieces,
+            found: piece_count,
+        });
+    }
+    // matching extents with another grid size means a damaged header
+    if grid_size != GRID_SIZE {
+        return Err(PersistenceError::Corrupt);
+    }
+    Ok(version[0])
+}
+
+/// Whether files of `version` carry a piece definitions block.
+fn has_definitions(version: u8) -> bool {
+    matches!(version, FILE_VERSION | PACKED_FILE_VERSION)
+}
+
+/// Skips over the definitions block without parsing it.
+fn skip_definitions(file: &mut dyn Read) -> Result<(), PersistenceError> {
+    let length = read_u32(file)? as u64;
+    let skipped = std::io::copy(&mut file.take(length), &mut std::io::sink())?;
+    if skipped == length {
+        Ok(())
     } else {
-        (1u32 << num_pieces) - 1
+        Err(PersistenceError::Truncated)
+    }
+}
+
+/// Checks that a loaded solution uses each of `num_pieces` pieces exactly once
+/// and stays inside the box.
+fn is_valid_solution<const DIM_X: usize, const DIM_Y: usize, const DIM_Z: usize>(
+    solution: &[PlacedPiece],
+    num_pieces: usize,
+) -> bool {
+    if solution.len() != num_pieces {
+        return false;
+    }
+
+    let mut seen_pieces = 0u32;
+    for placed in solution {
+        if placed.piece_index >= num_pieces {
+            return false;
+        }
+        let piece_bit = 1u32 << placed.piece_index;
+        if (seen_pieces & piece_bit) != 0 {
+            // reject duplicated piece ids in one solution
+            return false;
+        }
+        seen_pieces |= piece_bit;
+
+        if placed.cube_count == 0 {
+            return false;
+        }
+        let in_box = placed.cubes().iter().all(|&(x, y, z)| {
+            (0..DIM_X as i32).contains(&x)
+                && (0..DIM_Y as i32).contains(&y)
+                && (0..DIM_Z as i32).contains(&z)
+        });
+        if !in_box {
+            return false;
+        }
     }
+
+    // every piece must appear exactly once
+    seen_pieces == full_piece_mask(num_pieces)
 }
 
-fn parse_solutions<const DIM: usize, const NUM_PIECES: usize>(
-    file: &mut File,
+fn parse_solutions<const DIM_X: usize, const DIM_Y: usize, const DIM_Z: usize>(
+    file: &mut dyn Read,
     solution_count: usize,
-) -> Option<Vec<Vec<PlacedPiece>>> {
-    let mut solutions = Vec::with_capacity(solution_count);
-    let dim = DIM as i32;
-    let expected_mask = expected_piece_mask(NUM_PIECES);
+    num_pieces: usize,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    // the count comes from the file, so don't trust it for a large allocation
+    let mut solutions = Vec::with_capacity(solution_count.min(1024));
 
     for _ in 0..solution_count {
         let piece_count = read_u32(file)? as usize;
-        if piece_count != NUM_PIECES {
-            return None;
+        if piece_count != num_pieces {
+            return Err(PersistenceError::Corrupt);
         }
 
-        let mut seen_pieces = 0u32;
         let mut solution = Vec::with_capacity(piece_count);
         for _ in 0..piece_count {
             let piece_index = read_u32(file)? as usize;
-            if piece_index >= NUM_PIECES {
-                return None;
-            }
-
-            let piece_bit = 1u32 << piece_index;
-            if (seen_pieces & piece_bit) != 0 {
-                // reject duplicated piece ids in one solution
-                return None;
-            }
-            seen_pieces |= piece_bit;
-
             let cube_count = read_u32(file)? as usize;
-            if cube_count == 0 || cube_count > MAX_CUBES {
-                return None;
+            if cube_count > MAX_CUBES {
+                return Err(PersistenceError::Corrupt);
             }
 
             let mut positions = [(0, 0, 0); MAX_CUBES];
-            for i in 0..cube_count {
+            for position in positions.iter_mut().take(cube_count) {
                 let mut coord_buffer = [0u8; 3];
-                file.read_exact(&mut coord_buffer).ok()?;
-                let x = coord_buffer[0] as i32;
-                let y = coord_buffer[1] as i32;
-                let z = coord_buffer[2] as i32;
-                if x >= dim || y >= dim || z >= dim {
-                    // reject out of bounds cubes for this puzzle dimension
-                    return None;
-                }
-                positions[i] = (x, y, z);
+                file.read_exact(&mut coord_buffer)?;
+                *position = (
+                    coord_buffer[0] as i32,
+                    coord_buffer[1] as i32,
+                    coord_buffer[2] as i32,
+                );
             }
 
             solution.push(PlacedPiece {
@@ -147,77 +779,1552 @@ fn parse_solutions<const DIM: usize, const NUM_PIECES: usize>(
             });
         }
 
-        if seen_pieces != expected_mask {
-            // every piece must appear exactly once
-            return None;
+        if !is_valid_solution::<DIM_X, DIM_Y, DIM_Z>(&solution, num_pieces) {
+            return Err(PersistenceError::Corrupt);
         }
         solutions.push(solution);
     }
 
-    Some(solutions)
+    Ok(solutions)
 }
 
-/// Loads all solutions from the binary file.
-pub fn load_all<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize>(
-) -> Option<Vec<Vec<PlacedPiece>>> {
-    let mut file = File::open(SOLUTIONS_BIN).ok()?;
+/// Like [`parse_solutions`], for the records of a packed file.
+fn parse_packed_solutions<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    file: &mut dyn Read,
+    solution_count: usize,
+    num_pieces: usize,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    let mut solutions = Vec::with_capacity(solution_count.min(1024));
+    let mut bytes = vec![0u8; GRID_SIZE.div_ceil(2)];
+
+    for _ in 0..solution_count {
+        file.read_exact(&mut bytes)?;
+        let mut solution: Vec<PlacedPiece> = (0..num_pieces)
+            .map(|piece_index| PlacedPiece {
+                piece_index,
+                positions: [(0, 0, 0); MAX_CUBES],
+                cube_count: 0,
+            })
+            .collect();
+
+        for cell in 0..bytes.len() * 2 {
+            let id = (bytes[cell / 2] >> (cell % 2 * 4)) & PACKED_EMPTY;
+            if id == PACKED_EMPTY {
+                continue;
+            }
+            // the padding nibble of an odd-sized grid must stay empty
+            if cell >= GRID_SIZE {
+                return Err(PersistenceError::Corrupt);
+            }
+            let placed = solution
+                .get_mut(id as usize)
+                .ok_or(PersistenceError::Corrupt)?;
+            let count = placed.cube_count as usize;
+            if count == MAX_CUBES {
+                return Err(PersistenceError::Corrupt);
+            }
+            placed.positions[count] = idx_to_coord::<DIM_Y, DIM_Z>(cell);
+            placed.cube_count += 1;
+        }
+
+        if !is_valid_solution::<DIM_X, DIM_Y, DIM_Z>(&solution, num_pieces) {
+            return Err(PersistenceError::Corrupt);
+        }
+        solutions.push(solution);
+    }
+
+    Ok(solutions)
+}
+
+/// Loads all solutions from the binary file in the working directory.
+pub fn load_all<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>() -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    load_all_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>(Path::new(SOLUTIONS_BIN))
+}
+
+/// Loads all solutions from the binary file at `path`.
+pub fn load_all_from<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    path: &Path,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    load_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(path, NUM_PIECES)
+}
+
+/// Loads all solutions from the binary file in the working directory,
+/// rejecting the file unless each one passes
+/// [`Puzzle::validate_solution`].
+pub fn load_all_strict<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    load_all_strict_from(puzzle, Path::new(SOLUTIONS_BIN))
+}
+
+/// Like [`load_all_from`], but also checks every solution against `puzzle`.
+///
+/// The plain loaders only check that pieces stay in the box and are not
+/// repeated; this also catches pieces of the wrong shape and boxes that are
+/// not exactly filled, as a tampered file might have.
+pub fn load_all_strict_from<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    path: &Path,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    let solutions = load_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(path, puzzle.num_pieces())?;
+    for (index, solution) in solutions.iter().enumerate() {
+        puzzle
+            .validate_solution(solution)
+            .map_err(|error| PersistenceError::InvalidSolution { index, error })?;
+    }
+    Ok(solutions)
+}
+
+/// Like [`load_all_from`], for a puzzle with `num_pieces` pieces.
+pub(crate) fn load_pieces_from<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    path: &Path,
+    num_pieces: usize,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    read_all::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(&mut open_binary(path)?, num_pieces)
+}
+
+/// Reads every solution from an already decoded binary stream.
+fn read_all<const DIM_X: usize, const DIM_Y: usize, const DIM_Z: usize, const GRID_SIZE: usize>(
+    file: &mut dyn Read,
+    num_pieces: usize,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
     let mut prefix = [0u8; 4];
-    file.read_exact(&mut prefix).ok()?;
+    file.read_exact(&mut prefix)?;
 
     if prefix == FILE_MAGIC {
         // current format starts with magic and metadata
-        let mut metadata = [0u8; 4];
-        file.read_exact(&mut metadata).ok()?;
-        let version = metadata[0];
-        let dim = metadata[1] as usize;
-        let grid_size = metadata[2] as usize;
-        let piece_count = metadata[3] as usize;
-
-        if version != FILE_VERSION
-            || dim != DIM
-            || grid_size != GRID_SIZE
-            || piece_count != NUM_PIECES
-        {
-            return None;
+        let version = match read_metadata::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(file, num_pieces) {
+            Err(PersistenceError::VersionMismatch(version)) => {
+                return parse_magic_legacy::<DIM_X, DIM_Y, DIM_Z>(file, version, num_pieces);
+            }
+            result => result?,
+        };
+        if has_definitions(version) {
+            skip_definitions(file)?;
+        }
+        let solution_count = read_u32(file)? as usize;
+        if version == PACKED_FILE_VERSION {
+            parse_packed_solutions::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+                file,
+                solution_count,
+                num_pieces,
+            )
+        } else {
+            parse_solutions::<DIM_X, DIM_Y, DIM_Z>(file, solution_count, num_pieces)
         }
-
-        let solution_count = read_u32(&mut file)? as usize;
-        parse_solutions::<DIM, NUM_PIECES>(&mut file, solution_count)
     } else {
         // Legacy format without a header. Keep reading but validate dimensions.
         // here prefix is the old solution count field
         let solution_count = u32::from_le_bytes(prefix) as usize;
-        parse_solutions::<DIM, NUM_PIECES>(&mut file, solution_count)
+        parse_solutions::<DIM_X, DIM_Y, DIM_Z>(file, solution_count, num_pieces)
+            .map_err(legacy_error)
     }
 }
 
+/// Parses the rest of a file that starts with the magic but has an unknown
+/// version byte, which `read_metadata` has just consumed.
+///
+/// A legacy file whose solution count happens to spell `BLKR` (1,380,666,434
+/// little endian) starts this way, with the low byte of its first piece
+/// count where the version would be. It is read as legacy from that byte
+/// on, and only when that fails is the file taken for a newer format. Legacy
+/// puzzles of one to four pieces put a known version there instead and are
+/// not recovered.
+fn parse_magic_legacy<const DIM_X: usize, const DIM_Y: usize, const DIM_Z: usize>(
+    file: &mut dyn Read,
+    version: u8,
+    num_pieces: usize,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    let version_byte = [version];
+    let mut rest = version_byte.as_slice().chain(file);
+    let solution_count = u32::from_le_bytes(FILE_MAGIC) as usize;
+    parse_solutions::<DIM_X, DIM_Y, DIM_Z>(&mut rest, solution_count, num_pieces).map_err(|error| {
+        match error {
+            PersistenceError::Io(error) => PersistenceError::Io(error),
+            _ => PersistenceError::VersionMismatch(version),
+        }
+    })
+}
+
+/// Without a magic number, a file that fails to parse as the legacy format
+/// is most likely not a solutions file at all.
+fn legacy_error(error: PersistenceError) -> PersistenceError {
+    match error {
+        PersistenceError::Io(error) => PersistenceError::Io(error),
+        _ => PersistenceError::BadMagic,
+    }
+}
+
+/// Adds to the binary file at `path` the solutions whose canonical key is
+/// not already in it, and returns how many were added.
+///
+/// Keys come from `puzzle`, so its chiral pair and blocked cells decide
+/// which solutions count as the same. A missing file is created. A plain
+/// file in the current or packed format is appended to in place, with the
+/// header count patched afterwards; compressed and older files are rewritten
+/// in the current format, keeping their compression.
+pub fn append_unique<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    puzzle: &Puzzle<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>,
+    path: &Path,
+    solutions: &[Vec<PlacedPiece>],
+) -> Result<usize, PersistenceError> {
+    let (existing, layout) =
+        match load_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(path, puzzle.num_pieces()) {
+            Ok(existing) => (existing, file_layout(path)?),
+            Err(PersistenceError::NotFound) => {
+                (Vec::new(), FileLayout::Rewrite { compressed: false })
+            }
+            Err(error) => return Err(error),
+        };
+
+    let mut keys: FxHashSet<[u8; GRID_SIZE]> = existing
+        .iter()
+        .map(|solution| puzzle.canonical_key(solution))
+        .collect();
+    let fresh: Vec<&Vec<PlacedPiece>> = solutions
+        .iter()
+        .filter(|solution| keys.insert(puzzle.canonical_key(solution)))
+        .collect();
+    if fresh.is_empty() {
+        return Ok(0);
+    }
+
+    match layout {
+        FileLayout::Plain {
+            count_offset,
+            packed,
+        } => {
+            let mut file = BufWriter::new(File::options().write(true).open(path)?);
+            file.seek(SeekFrom::End(0))?;
+            for solution in &fresh {
+                if packed {
+                    write_packed_solution::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(&mut file, solution)?;
+                } else {
+                    write_solution(&mut file, solution)?;
+                }
+            }
+            let total = (existing.len() + fresh.len()) as u32;
+            store_count(&mut file, count_offset, total)?;
+        }
+        FileLayout::Rewrite { compressed } => {
+            let mut all = existing;
+            all.extend(fresh.iter().map(|solution| solution.to_vec()));
+            save_binary(puzzle, path, &all, compressed)?;
+        }
+    }
+    Ok(fresh.len())
+}
+
+/// How [`append_unique`] can add to an existing file.
+enum FileLayout {
+    /// Uncompressed current or packed format, with the solution count at
+    /// this offset.
+    Plain { count_offset: u64, packed: bool },
+    /// Anything else, written out again in full.
+    Rewrite { compressed: bool },
+}
+
+/// Reads just enough of the file at `path` to pick its [`FileLayout`].
+fn file_layout(path: &Path) -> Result<FileLayout, PersistenceError> {
+    let mut file = File::open(path)?;
+    // magic, version, three extents, grid size, piece count, definitions length
+    let mut header = [0u8; 14];
+    let read = file.read(&mut header)?;
+    if header[..2] == GZIP_MAGIC {
+        return Ok(FileLayout::Rewrite { compressed: true });
+    }
+    if read < header.len() || header[..4] != FILE_MAGIC || !has_definitions(header[4]) {
+        return Ok(FileLayout::Rewrite { compressed: false });
+    }
+    let definitions = u32::from_le_bytes([header[10], header[11], header[12], header[13]]);
+    Ok(FileLayout::Plain {
+        count_offset: header.len() as u64 + definitions as u64,
+        packed: header[4] == PACKED_FILE_VERSION,
+    })
+}
+
 /// Returns the number of saved solutions without loading them all.
-pub fn count<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize>() -> Option<usize> {
-    let mut file = File::open(SOLUTIONS_BIN).ok()?;
+pub fn count<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>() -> Result<usize, PersistenceError> {
+    count_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>(Path::new(SOLUTIONS_BIN))
+}
+
+/// Returns the number of solutions in the binary file at `path`.
+pub fn count_from<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    path: &Path,
+) -> Result<usize, PersistenceError> {
+    count_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(path, NUM_PIECES)
+}
+
+/// Like [`count_from`], for a puzzle with `num_pieces` pieces.
+pub(crate) fn count_pieces_from<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    path: &Path,
+    num_pieces: usize,
+) -> Result<usize, PersistenceError> {
+    let mut file = open_binary(path)?;
     let mut prefix = [0u8; 4];
-    file.read_exact(&mut prefix).ok()?;
+    file.read_exact(&mut prefix)?;
 
     if prefix == FILE_MAGIC {
-        let mut metadata = [0u8; 4];
-        file.read_exact(&mut metadata).ok()?;
-        let version = metadata[0];
-        let dim = metadata[1] as usize;
-        let grid_size = metadata[2] as usize;
-        let piece_count = metadata[3] as usize;
-
-        if version != FILE_VERSION
-            || dim != DIM
-            || grid_size != GRID_SIZE
-            || piece_count != NUM_PIECES
-        {
-            return None;
+        let version = match read_metadata::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(&mut file, num_pieces) {
+            Err(PersistenceError::VersionMismatch(version)) => {
+                let solutions =
+                    parse_magic_legacy::<DIM_X, DIM_Y, DIM_Z>(&mut file, version, num_pieces)?;
+                return Ok(solutions.len());
+            }
+            result => result?,
+        };
+        if has_definitions(version) {
+            skip_definitions(&mut file)?;
         }
-
-        Some(read_u32(&mut file)? as usize)
+        Ok(read_u32(&mut file)? as usize)
     } else {
         // Legacy format without a header. Parse to ensure compatibility.
         let solution_count = u32::from_le_bytes(prefix) as usize;
-        let solutions = parse_solutions::<DIM, NUM_PIECES>(&mut file, solution_count)?;
-        Some(solutions.len())
+        let solutions =
+            parse_solutions::<DIM_X, DIM_Y, DIM_Z>(&mut file, solution_count, num_pieces)
+                .map_err(legacy_error)?;
+        Ok(solutions.len())
+    }
+}
+
+/// How `solutions.txt` disagrees with `solutions.bin`, as found by
+/// [`verify_consistency`].
+#[derive(Debug)]
+pub enum ConsistencyError {
+    /// `solutions.bin` could not be loaded.
+    Binary(PersistenceError),
+    /// `solutions.txt` could not be read.
+    Text(std::io::Error),
+    /// The text file's first line gives a count other than the binary
+    /// file's.
+    CountMismatch { binary: usize },
+    /// The text file differs from the binary file at this solution (0-based),
+    /// whether edited, reordered or cut short.
+    SolutionMismatch(usize),
+    /// The text file goes on after its last solution.
+    TrailingText,
+}
+
+impl std::fmt::Display for ConsistencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Binary(error) => write!(f, "could not load {SOLUTIONS_BIN}: {error}"),
+            Self::Text(error) => write!(f, "could not read {SOLUTIONS_TXT}: {error}"),
+            Self::CountMismatch { binary } => write!(
+                f,
+                "{SOLUTIONS_TXT} does not announce the {binary} solutions in {SOLUTIONS_BIN}"
+            ),
+            Self::SolutionMismatch(index) => write!(
+                f,
+                "solution {} in {SOLUTIONS_TXT} differs from {SOLUTIONS_BIN}",
+                index + 1
+            ),
+            Self::TrailingText => write!(f, "{SOLUTIONS_TXT} goes on after its last solution"),
+        }
+    }
+}
+
+impl std::error::Error for ConsistencyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Binary(error) => Some(error),
+            Self::Text(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Checks that `solutions.txt` in the working directory lists exactly the
+/// solutions in `solutions.bin`.
+pub fn verify_consistency<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>() -> Result<(), ConsistencyError> {
+    verify_consistency_in::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>(Path::new("."))
+}
+
+/// Checks that `solutions.txt` in `dir` lists exactly the solutions in
+/// `solutions.bin` there.
+///
+/// The two files are written one after the other, so a failed write or a
+/// hand edit can leave them apart. The binary file is taken as the truth:
+/// its solutions are rendered as [`save_to`] would write them and compared
+/// with the text, solution by solution, reporting the first that differs.
+pub fn verify_consistency_in<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    dir: &Path,
+) -> Result<(), ConsistencyError> {
+    verify_pieces_in::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(dir, NUM_PIECES)
+}
+
+/// Like [`verify_consistency_in`], for a puzzle with `num_pieces` pieces.
+pub(crate) fn verify_pieces_in<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    dir: &Path,
+    num_pieces: usize,
+) -> Result<(), ConsistencyError> {
+    let solutions =
+        load_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(&dir.join(SOLUTIONS_BIN), num_pieces)
+            .map_err(ConsistencyError::Binary)?;
+    let text = std::fs::read_to_string(dir.join(SOLUTIONS_TXT)).map_err(ConsistencyError::Text)?;
+
+    let mut rest = text.strip_prefix(&text_header(solutions.len())).ok_or(
+        ConsistencyError::CountMismatch {
+            binary: solutions.len(),
+        },
+    )?;
+    for (index, solution) in solutions.iter().enumerate() {
+        rest = rest
+            .strip_prefix(&text_entry::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+                index, solution,
+            ))
+            .ok_or(ConsistencyError::SolutionMismatch(index))?;
+    }
+    if rest.is_empty() {
+        Ok(())
+    } else {
+        Err(ConsistencyError::TrailingText)
+    }
+}
+
+/// Piece definitions stored alongside the solutions in `solutions.bin`.
+///
+/// Enough to rebuild the puzzle, e.g. to re-canonicalize loaded solutions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredDefinitions {
+    /// Chiral mirror-image pair (piece indices), if the puzzle has one.
+    pub chiral_pair: Option<(usize, usize)>,
+    /// Cube positions of each piece, in piece index order.
+    pub pieces: Vec<Vec<Coord>>,
+}
+
+/// Reads the piece definitions from the binary file in the working directory.
+///
+/// Files written before definitions were stored give
+/// [`PersistenceError::VersionMismatch`].
+pub fn load_definitions<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>() -> Result<StoredDefinitions, PersistenceError> {
+    load_definitions_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>(Path::new(SOLUTIONS_BIN))
+}
+
+/// Reads the piece definitions from the binary file at `path`.
+pub fn load_definitions_from<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    path: &Path,
+) -> Result<StoredDefinitions, PersistenceError> {
+    read_definitions::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(&mut open_binary(path)?, NUM_PIECES)
+}
+
+fn read_definitions<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    file: &mut dyn Read,
+    num_pieces: usize,
+) -> Result<StoredDefinitions, PersistenceError> {
+    let mut prefix = [0u8; 4];
+    file.read_exact(&mut prefix)?;
+    if prefix != FILE_MAGIC {
+        return Err(PersistenceError::BadMagic);
+    }
+    let version = read_metadata::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(file, num_pieces)?;
+    if !has_definitions(version) {
+        return Err(PersistenceError::VersionMismatch(version));
+    }
+
+    let length = read_u32(file)? as u64;
+    let mut block = Vec::new();
+    file.take(length).read_to_end(&mut block)?;
+    if block.len() as u64 != length {
+        return Err(PersistenceError::Truncated);
+    }
+
+    let (pair, mut rest) = block
+        .split_first_chunk::<2>()
+        .ok_or(PersistenceError::Corrupt)?;
+    let chiral_pair = match *pair {
+        [NO_CHIRAL_PAIR, NO_CHIRAL_PAIR] => None,
+        [a, b] if (a as usize) < num_pieces && (b as usize) < num_pieces => {
+            Some((a as usize, b as usize))
+        }
+        _ => return Err(PersistenceError::Corrupt),
+    };
+
+    let mut pieces = Vec::with_capacity(num_pieces);
+    for _ in 0..num_pieces {
+        let (&cube_count, tail) = rest.split_first().ok_or(PersistenceError::Corrupt)?;
+        let cube_count = cube_count as usize;
+        if cube_count == 0 || cube_count > MAX_CUBES || tail.len() < cube_count * 3 {
+            return Err(PersistenceError::Corrupt);
+        }
+        let (cubes, tail) = tail.split_at(cube_count * 3);
+        pieces.push(
+            cubes
+                .chunks_exact(3)
+                .map(|c| (c[0] as i32, c[1] as i32, c[2] as i32))
+                .collect(),
+        );
+        rest = tail;
+    }
+
+    // trailing bytes mean the block doesn't match the header's piece count
+    if !rest.is_empty() {
+        return Err(PersistenceError::Corrupt);
+    }
+    Ok(StoredDefinitions {
+        chiral_pair,
+        pieces,
+    })
+}
+
+/// Writes solutions as a JavaScript array for the website:
+/// `const SOLUTIONS = [...];`, one solution per line, each piece as
+/// `[index, [[x,y,z],...]]`.
+pub fn write_js<W: Write>(mut writer: W, solutions: &[Vec<PlacedPiece>]) -> std::io::Result<()> {
+    writeln!(writer, "const SOLUTIONS = [")?;
+    for (i, solution) in solutions.iter().enumerate() {
+        write!(writer, "  [")?;
+        for (j, placed) in solution.iter().enumerate() {
+            if j > 0 {
+                write!(writer, ", ")?;
+            }
+            write!(writer, "[{}, [", placed.piece_index)?;
+            for (k, &(x, y, z)) in placed.cubes().iter().enumerate() {
+                if k > 0 {
+                    write!(writer, ",")?;
+                }
+                write!(writer, "[{x},{y},{z}]")?;
+            }
+            write!(writer, "]]")?;
+        }
+        let trailing = if i + 1 < solutions.len() { "," } else { "" };
+        writeln!(writer, "]{trailing}")?;
+    }
+    writeln!(writer, "];")?;
+    writer.flush()
+}
+
+/// Column names on the first line of a CSV export.
+const CSV_HEADER: &str = "solution,piece,x,y,z";
+
+/// Writes solutions as CSV for spreadsheets and data frames.
+///
+/// A `solution,piece,x,y,z` header comes first, then one row per cube, with
+/// solutions and pieces numbered from 0. Rows go straight to `writer`, so a
+/// large solution set is never held as one string.
+pub fn write_csv<W: Write>(mut writer: W, solutions: &[Vec<PlacedPiece>]) -> std::io::Result<()> {
+    writeln!(writer, "{CSV_HEADER}")?;
+    for (i, solution) in solutions.iter().enumerate() {
+        for placed in solution {
+            for &(x, y, z) in placed.cubes() {
+                writeln!(writer, "{i},{},{x},{y},{z}", placed.piece_index)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Reads solutions written by [`write_csv`] one row at a time.
+///
+/// Fails with [`PersistenceError::BadMagic`] unless the header comes first,
+/// and with [`PersistenceError::Corrupt`] when a row is malformed, solutions
+/// are out of order, or a solution does not cover every cell of the box
+/// exactly once with `NUM_PIECES` pieces. A CSV file does not record its
+/// box, so an export from another puzzle is caught by its cubes landing
+/// outside the box or leaving cells empty.
+pub fn read_csv<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const NUM_PIECES: usize,
+    R: BufRead,
+>(
+    reader: R,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    let mut lines = reader.lines();
+    if lines.next().transpose()?.as_deref() != Some(CSV_HEADER) {
+        return Err(PersistenceError::BadMagic);
+    }
+
+    let mut solutions: Vec<Vec<PlacedPiece>> = Vec::new();
+    for line in lines {
+        let line = line?;
+        let fields: Vec<usize> = line
+            .split(',')
+            .map(|field| field.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| PersistenceError::Corrupt)?;
+        let [index, piece_index, x, y, z] = fields[..] else {
+            return Err(PersistenceError::Corrupt);
+        };
+        if x >= DIM_X || y >= DIM_Y || z >= DIM_Z {
+            return Err(PersistenceError::Corrupt);
+        }
+
+        // rows of one solution, and of one piece within it, are contiguous
+        if index == solutions.len() {
+            solutions.push(Vec::with_capacity(NUM_PIECES));
+        } else if index + 1 != solutions.len() {
+            return Err(PersistenceError::Corrupt);
+        }
+        let solution = solutions.last_mut().unwrap();
+        let cube = (x as i32, y as i32, z as i32);
+        match solution.last_mut() {
+            Some(placed) if placed.piece_index == piece_index => {
+                let count = placed.cube_count as usize;
+                if count == MAX_CUBES {
+                    return Err(PersistenceError::Corrupt);
+                }
+                placed.positions[count] = cube;
+                placed.cube_count += 1;
+            }
+            _ => {
+                let mut positions = [(0, 0, 0); MAX_CUBES];
+                positions[0] = cube;
+                solution.push(PlacedPiece {
+                    piece_index,
+                    positions,
+                    cube_count: 1,
+                });
+            }
+        }
+    }
+
+    if solutions.iter().any(|solution| {
+        !is_valid_solution::<DIM_X, DIM_Y, DIM_Z>(solution, NUM_PIECES)
+            || !fills_box::<DIM_X, DIM_Y, DIM_Z>(solution)
+    }) {
+        return Err(PersistenceError::Corrupt);
+    }
+    Ok(solutions)
+}
+
+/// Whether the cubes of `solution`, all inside the box, cover each of its
+/// cells exactly once.
+fn fills_box<const DIM_X: usize, const DIM_Y: usize, const DIM_Z: usize>(
+    solution: &[PlacedPiece],
+) -> bool {
+    let mut covered = vec![false; DIM_X * DIM_Y * DIM_Z];
+    for &(x, y, z) in solution.iter().flat_map(PlacedPiece::cubes) {
+        let cell = coord_to_idx::<DIM_Y, DIM_Z>(x, y, z);
+        if std::mem::replace(&mut covered[cell], true) {
+            return false;
+        }
+    }
+    covered.iter().all(|&covered| covered)
+}
+
+/// Bits that hold a piece's cube count, stored less one.
+const SHARE_COUNT_BITS: u32 = usize::BITS - (MAX_CUBES - 1).leading_zeros();
+
+/// Appends values of a fixed bit width to a byte buffer, high bits first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u32,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u32, bits: u32) {
+        for bit in (0..bits).rev() {
+            if self.used.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let byte = self.bytes.last_mut().unwrap();
+            *byte |= (((value >> bit) & 1) as u8) << (7 - self.used % 8);
+            self.used += 1;
+        }
+    }
+}
+
+/// Reads back what [`BitWriter`] wrote.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    read: usize,
+}
+
+impl BitReader<'_> {
+    fn take(&mut self, bits: u32) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..bits {
+            let byte = self.bytes.get(self.read / 8)?;
+            value = (value << 1) | u32::from((byte >> (7 - self.read % 8)) & 1);
+            self.read += 1;
+        }
+        Some(value)
+    }
+
+    /// Whether only the zero padding of the last byte is left.
+    fn at_padding(&self) -> bool {
+        let rest = (self.bytes.len() * 8 - self.read) as u32;
+        let mut padding = BitReader {
+            bytes: self.bytes,
+            read: self.read,
+        };
+        rest < 8 && padding.take(rest) == Some(0)
+    }
+}
+
+/// Bits needed to write every value up to `max`, at least one.
+fn bit_width(max: u32) -> u32 {
+    (u32::BITS - max.leading_zeros()).max(1)
+}
+
+/// Encodes one solution as a short URL-safe base64 code.
+///
+/// A header byte holds the bit widths of piece ids and coordinates, sized
+/// to the largest of each in the solution. Each piece follows as its id,
+/// its cube count less one and its cube coordinates, packed without byte
+/// alignment. A Soma solution takes 36 characters.
+pub fn encode_solution(solution: &[PlacedPiece]) -> String {
+    let max_index = solution.iter().map(|placed| placed.piece_index).max();
+    let max_coord = solution
+        .iter()
+        .flat_map(|placed| placed.cubes())
+        .flat_map(|&(x, y, z)| [x, y, z])
+        .max();
+    let index_bits = bit_width(max_index.unwrap_or(0) as u32);
+    let coord_bits = bit_width(max_coord.unwrap_or(0) as u32);
+
+    let mut bits = BitWriter::default();
+    bits.push(index_bits, 4);
+    bits.push(coord_bits, 4);
+    for placed in solution {
+        bits.push(placed.piece_index as u32, index_bits);
+        bits.push(u32::from(placed.cube_count) - 1, SHARE_COUNT_BITS);
+        for &(x, y, z) in placed.cubes() {
+            for coord in [x, y, z] {
+                bits.push(coord as u32, coord_bits);
+            }
+        }
+    }
+    URL_SAFE_NO_PAD.encode(bits.bytes)
+}
+
+/// Decodes a code from [`encode_solution`], or returns `None` unless it
+/// holds a valid solution of `NUM_PIECES` pieces inside the box.
+pub fn decode_solution<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const NUM_PIECES: usize,
+>(
+    code: &str,
+) -> Option<Vec<PlacedPiece>> {
+    let bytes = URL_SAFE_NO_PAD.decode(code).ok()?;
+    let mut bits = BitReader {
+        bytes: &bytes,
+        read: 0,
+    };
+    let index_bits = bits.take(4)?;
+    let coord_bits = bits.take(4)?;
+    if index_bits == 0 || coord_bits == 0 {
+        return None;
+    }
+
+    let mut solution = Vec::with_capacity(NUM_PIECES);
+    for _ in 0..NUM_PIECES {
+        let piece_index = bits.take(index_bits)? as usize;
+        let cube_count = bits.take(SHARE_COUNT_BITS)? as usize + 1;
+        if cube_count > MAX_CUBES {
+            return None;
+        }
+        let mut positions = [(0, 0, 0); MAX_CUBES];
+        for position in positions.iter_mut().take(cube_count) {
+            *position = (
+                bits.take(coord_bits)? as i32,
+                bits.take(coord_bits)? as i32,
+                bits.take(coord_bits)? as i32,
+            );
+        }
+        solution.push(PlacedPiece {
+            piece_index,
+            positions,
+            cube_count: cube_count as u8,
+        });
+    }
+
+    // anything past the last piece but the final byte's padding is corrupt
+    if !bits.at_padding() {
+        return None;
+    }
+    is_valid_solution::<DIM_X, DIM_Y, DIM_Z>(&solution, NUM_PIECES).then_some(solution)
+}
+
+/// JSON document layout for `solutions.json`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonDocument {
+    dims: [usize; 3],
+    grid_size: usize,
+    num_pieces: usize,
+    // each piece is (piece index, cube positions)
+    solutions: Vec<Vec<(usize, Vec<Coord>)>>,
+}
+
+/// Saves solutions as a JSON document for use outside this tool.
+#[cfg(feature = "serde")]
+pub fn save_json<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    solutions: &[Vec<PlacedPiece>],
+) -> std::io::Result<()> {
+    save_json_to::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>(Path::new("."), solutions)
+}
+
+/// Saves `solutions.json` into `dir`, creating it if needed.
+#[cfg(feature = "serde")]
+pub fn save_json_to<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    dir: &Path,
+    solutions: &[Vec<PlacedPiece>],
+) -> std::io::Result<()> {
+    save_json_pieces_to::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(dir, solutions, NUM_PIECES)
+}
+
+/// Like [`save_json_to`], for a puzzle with `num_pieces` pieces.
+#[cfg(feature = "serde")]
+pub(crate) fn save_json_pieces_to<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    dir: &Path,
+    solutions: &[Vec<PlacedPiece>],
+    num_pieces: usize,
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let json = solutions_to_json::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solutions, num_pieces);
+    write_atomic(&dir.join(SOLUTIONS_JSON), |file| {
+        file.write_all(json.as_bytes())
+    })
+}
+
+/// Loads solutions from the JSON document, rejecting other puzzles' files.
+#[cfg(feature = "serde")]
+pub fn load_json<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>() -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    load_json_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>(Path::new(SOLUTIONS_JSON))
+}
+
+/// Loads solutions from the JSON document at `path`.
+#[cfg(feature = "serde")]
+pub fn load_json_from<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+>(
+    path: &Path,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    load_json_pieces_from::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(path, NUM_PIECES)
+}
+
+/// Like [`load_json_from`], for a puzzle with `num_pieces` pieces.
+#[cfg(feature = "serde")]
+pub(crate) fn load_json_pieces_from<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    path: &Path,
+    num_pieces: usize,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    let json = std::fs::read_to_string(path)?;
+    solutions_from_json::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(&json, num_pieces)
+}
+
+#[cfg(feature = "serde")]
+fn solutions_to_json<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solutions: &[Vec<PlacedPiece>],
+    num_pieces: usize,
+) -> String {
+    let document = JsonDocument {
+        dims: [DIM_X, DIM_Y, DIM_Z],
+        grid_size: GRID_SIZE,
+        num_pieces,
+        solutions: solutions
+            .iter()
+            .map(|solution| {
+                solution
+                    .iter()
+                    .map(|placed| (placed.piece_index, placed.cubes().to_vec()))
+                    .collect()
+            })
+            .collect(),
+    };
+    // plain data with string keys cannot fail to serialize
+    serde_json::to_string(&document).expect("solutions serialize to JSON")
+}
+
+#[cfg(feature = "serde")]
+fn solutions_from_json<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    json: &str,
+    num_pieces: usize,
+) -> Result<Vec<Vec<PlacedPiece>>, PersistenceError> {
+    let document: JsonDocument =
+        serde_json::from_str(json).map_err(|_| PersistenceError::Corrupt)?;
+    if document.dims != [DIM_X, DIM_Y, DIM_Z] {
+        return Err(PersistenceError::DimensionMismatch {
+            expected: [DIM_X, DIM_Y, DIM_Z],
+            found: document.dims,
+        });
+    }
+    if document.num_pieces != num_pieces {
+        return Err(PersistenceError::PieceCountMismatch {
+            expected: num_pieces,
+            found: document.num_pieces,
+        });
+    }
+    if document.grid_size != GRID_SIZE {
+        return Err(PersistenceError::Corrupt);
+    }
+
+    let mut solutions = Vec::with_capacity(document.solutions.len());
+    for pieces in document.solutions {
+        let mut solution = Vec::with_capacity(pieces.len());
+        for (piece_index, cubes) in pieces {
+            if cubes.len() > MAX_CUBES {
+                return Err(PersistenceError::Corrupt);
+            }
+            let mut positions = [(0, 0, 0); MAX_CUBES];
+            positions[..cubes.len()].copy_from_slice(&cubes);
+            solution.push(PlacedPiece {
+                piece_index,
+                positions,
+                cube_count: cubes.len() as u8,
+            });
+        }
+
+        if !is_valid_solution::<DIM_X, DIM_Y, DIM_Z>(&solution, num_pieces) {
+            return Err(PersistenceError::Corrupt);
+        }
+        solutions.push(solution);
+    }
+
+    Ok(solutions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::pieces::{BEDLAM_PUZZLE, PIECES, SOMA_PUZZLE};
+
+    fn assert_same_solutions(loaded: &[Vec<PlacedPiece>], original: &[Vec<PlacedPiece>]) {
+        assert_eq!(loaded.len(), original.len());
+        for (loaded, original) in loaded.iter().zip(original) {
+            for (loaded, original) in loaded.iter().zip(original) {
+                assert_eq!(loaded.piece_index, original.piece_index);
+                assert_eq!(loaded.cubes(), original.cubes());
+            }
+        }
+    }
+
+    #[test]
+    fn test_share_codes_roundtrip() {
+        for solution in SOMA_PUZZLE.solve(Some(3)) {
+            let code = encode_solution(&solution);
+            assert_eq!(code.len(), 36);
+            assert!(code
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+            let decoded = decode_solution::<3, 3, 3, 7>(&code).unwrap();
+            assert_same_solutions(&[decoded], &[solution]);
+        }
+
+        let solution = &SOMA_PUZZLE.solve(Some(1))[0];
+        let code = encode_solution(solution);
+        // wrong piece count, a box too small for the coordinates, and junk
+        assert!(decode_solution::<3, 3, 3, 6>(&code).is_none());
+        assert!(decode_solution::<2, 3, 3, 7>(&code).is_none());
+        assert!(decode_solution::<3, 3, 3, 7>(&code[..20]).is_none());
+        assert!(decode_solution::<3, 3, 3, 7>(&format!("{code}AA")).is_none());
+        assert!(decode_solution::<3, 3, 3, 7>("not base64!").is_none());
+    }
+
+    #[test]
+    fn test_csv_roundtrip() {
+        let solutions = SOMA_PUZZLE.solve(Some(3));
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &solutions).unwrap();
+        let text = String::from_utf8(csv.clone()).unwrap();
+        assert!(text.starts_with("solution,piece,x,y,z\n0,"));
+        assert_eq!(text.lines().count(), 1 + 3 * 27);
+        assert_same_solutions(
+            &read_csv::<3, 3, 3, 7, _>(Cursor::new(&csv)).unwrap(),
+            &solutions,
+        );
+
+        // another box, another piece count, a missing header and a bad row
+        assert!(matches!(
+            read_csv::<4, 4, 4, 13, _>(Cursor::new(&csv)),
+            Err(PersistenceError::Corrupt)
+        ));
+        assert!(matches!(
+            read_csv::<4, 4, 4, 7, _>(Cursor::new(&csv)),
+            Err(PersistenceError::Corrupt)
+        ));
+        assert!(matches!(
+            read_csv::<3, 3, 3, 6, _>(Cursor::new(&csv)),
+            Err(PersistenceError::Corrupt)
+        ));
+        assert!(matches!(
+            read_csv::<3, 3, 3, 7, _>(Cursor::new(&csv[21..])),
+            Err(PersistenceError::BadMagic)
+        ));
+        let bad_row = format!("{text}3,0,x,0,0\n");
+        assert!(matches!(
+            read_csv::<3, 3, 3, 7, _>(Cursor::new(bad_row)),
+            Err(PersistenceError::Corrupt)
+        ));
+
+        // a piece covering one cell twice, leaving another empty
+        let lines: Vec<&str> = text.lines().collect();
+        let overlap = text.replacen(lines[2], lines[1], 1);
+        assert!(matches!(
+            read_csv::<3, 3, 3, 7, _>(Cursor::new(overlap)),
+            Err(PersistenceError::Corrupt)
+        ));
+    }
+
+    #[test]
+    fn test_write_js_snapshot() {
+        const DOMINO: &[Coord] = &[(0, 0, 0), (0, 0, 1)];
+        let puzzle: Puzzle<1, 1, 2, 2, 1> = Puzzle::new(&[DOMINO], None);
+        let solution = puzzle.solve(Some(1)).remove(0);
+
+        let mut js = Vec::new();
+        write_js(&mut js, &[solution.clone(), solution]).unwrap();
+        assert_eq!(
+            String::from_utf8(js).unwrap(),
+            "const SOLUTIONS = [\n  [[0, [[0,0,0],[0,0,1]]]],\n  [[0, [[0,0,0],[0,0,1]]]]\n];\n"
+        );
+
+        let mut empty = Vec::new();
+        write_js(&mut empty, &[]).unwrap();
+        assert_eq!(empty, b"const SOLUTIONS = [\n];\n");
+    }
+
+    #[test]
+    fn test_solution_writer_backpatches_count() {
+        let solutions: Vec<_> = SOMA_PUZZLE.solve_iter().take(4).collect();
+        let mut writer = SolutionWriter::new(&SOMA_PUZZLE, Cursor::new(Vec::new())).unwrap();
+        for solution in &solutions {
+            writer.write(solution).unwrap();
+        }
+        assert_eq!(writer.count(), 4);
+        let written = writer.finish().unwrap().into_inner();
+
+        let mut whole = Vec::new();
+        write_binary(&SOMA_PUZZLE, &mut whole, &solutions).unwrap();
+        assert_eq!(written, whole);
+
+        // an unfinished writer still leaves a readable file behind
+        let path = std::env::temp_dir().join(format!("blocker-writer-{}.bin", std::process::id()));
+        let mut writer = SolutionWriter::create(&SOMA_PUZZLE, &path).unwrap();
+        writer.write(&solutions[0]).unwrap();
+        drop(writer);
+        let loaded = load_all_from::<3, 3, 3, 27, 7>(&path).unwrap();
+        assert_same_solutions(&loaded, &solutions[..1]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_append_unique_skips_known_solutions() {
+        let dir = std::env::temp_dir().join(format!("blocker-append-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(SOLUTIONS_BIN);
+        let solutions = SOMA_PUZZLE.solve_sequential(Some(5));
+
+        assert_eq!(
+            append_unique(&SOMA_PUZZLE, &path, &solutions[..3]).unwrap(),
+            3
+        );
+        let before = std::fs::read(&path).unwrap();
+        assert_eq!(
+            append_unique(&SOMA_PUZZLE, &path, &solutions[..3]).unwrap(),
+            0
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+
+        // overlapping runs only add the solutions not seen yet
+        assert_eq!(
+            append_unique(&SOMA_PUZZLE, &path, &solutions[1..]).unwrap(),
+            2
+        );
+        let loaded = load_all_from::<3, 3, 3, 27, 7>(&path).unwrap();
+        assert_same_solutions(&loaded, &solutions);
+
+        // compressed files stay compressed
+        save_compressed_to(&SOMA_PUZZLE, &dir, &solutions[..2]).unwrap();
+        assert_eq!(append_unique(&SOMA_PUZZLE, &path, &solutions).unwrap(), 3);
+        assert!(std::fs::read(&path).unwrap().starts_with(&GZIP_MAGIC));
+        let loaded = load_all_from::<3, 3, 3, 27, 7>(&path).unwrap();
+        assert_same_solutions(&loaded, &solutions);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_strict_load_rejects_tampered_solutions() {
+        let dir = std::env::temp_dir().join(format!("blocker-strict-{}", std::process::id()));
+        let path = dir.join(SOLUTIONS_BIN);
+        let mut solutions = SOMA_PUZZLE.solve(Some(2));
+        save_to(&SOMA_PUZZLE, &dir, &solutions).unwrap();
+        assert_same_solutions(
+            &load_all_strict_from(&SOMA_PUZZLE, &path).unwrap(),
+            &solutions,
+        );
+
+        // relabel two pieces: still in the box and unrepeated, so only the
+        // strict load notices
+        let (first, second) = (solutions[1][0].piece_index, solutions[1][1].piece_index);
+        solutions[1][0].piece_index = second;
+        solutions[1][1].piece_index = first;
+        save_to(&SOMA_PUZZLE, &dir, &solutions).unwrap();
+        assert!(load_all_from::<3, 3, 3, 27, 7>(&path).is_ok());
+        assert!(matches!(
+            load_all_strict_from(&SOMA_PUZZLE, &path),
+            Err(PersistenceError::InvalidSolution {
+                index: 1,
+                error: ValidationError::WrongShape(_)
+            })
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_binary_roundtrip_plain_and_compressed() {
+        let solutions = SOMA_PUZZLE.solve(Some(3));
+
+        let mut plain = Vec::new();
+        write_binary(&SOMA_PUZZLE, &mut plain, &solutions).unwrap();
+        let loaded = read_all::<3, 3, 3, 27>(&mut decode(Cursor::new(&plain)).unwrap(), 7);
+        assert_same_solutions(&loaded.unwrap(), &solutions);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        write_binary(&SOMA_PUZZLE, &mut encoder, &solutions).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.starts_with(&GZIP_MAGIC));
+        let loaded = read_all::<3, 3, 3, 27>(&mut decode(Cursor::new(&compressed)).unwrap(), 7);
+        assert_same_solutions(&loaded.unwrap(), &solutions);
+    }
+
+    #[test]
+    fn test_repeated_solves_write_identical_files() {
+        let write = || {
+            let mut bytes = Vec::new();
+            write_binary(&SOMA_PUZZLE, &mut bytes, &SOMA_PUZZLE.solve(None)).unwrap();
+            bytes
+        };
+        assert_eq!(write(), write());
+    }
+
+    /// Rewrites a current-format file as an older version fixture.
+    fn older_version_fixture(current: &[u8], version: u8) -> Vec<u8> {
+        let definitions_len = u32::from_le_bytes(current[10..14].try_into().unwrap()) as usize;
+        let mut fixture = FILE_MAGIC.to_vec();
+        fixture.push(version);
+        match version {
+            BOX_FILE_VERSION => fixture.extend_from_slice(&current[5..10]),
+            CUBE_FILE_VERSION => fixture.extend_from_slice(&[current[5], current[8], current[9]]),
+            _ => unreachable!(),
+        }
+        fixture.extend_from_slice(&current[14 + definitions_len..]);
+        fixture
+    }
+
+    #[test]
+    fn test_reads_older_versions() {
+        let solutions = SOMA_PUZZLE.solve(Some(3));
+        let mut current = Vec::new();
+        write_binary(&SOMA_PUZZLE, &mut current, &solutions).unwrap();
+
+        for version in [CUBE_FILE_VERSION, BOX_FILE_VERSION] {
+            let fixture = older_version_fixture(&current, version);
+            let loaded = read_all::<3, 3, 3, 27>(&mut Cursor::new(&fixture), 7);
+            assert_same_solutions(&loaded.unwrap(), &solutions);
+            assert!(matches!(
+                read_definitions::<3, 3, 3, 27>(&mut Cursor::new(&fixture), 7),
+                Err(PersistenceError::VersionMismatch(v)) if v == version
+            ));
+        }
+    }
+
+    #[test]
+    fn test_definitions_roundtrip() {
+        let mut soma = Vec::new();
+        write_binary(&SOMA_PUZZLE, &mut soma, &SOMA_PUZZLE.solve(Some(1))).unwrap();
+        let definitions = read_definitions::<3, 3, 3, 27>(&mut Cursor::new(&soma), 7).unwrap();
+        assert_eq!(definitions.chiral_pair, SOMA_PUZZLE.chiral_pair);
+        let stored: Vec<&[Coord]> = definitions.pieces.iter().map(Vec::as_slice).collect();
+        assert_eq!(stored, PIECES);
+
+        let mut bedlam = Vec::new();
+        write_binary(&BEDLAM_PUZZLE, &mut bedlam, &[]).unwrap();
+        let definitions = read_definitions::<4, 4, 4, 64>(&mut Cursor::new(&bedlam), 13).unwrap();
+        assert_eq!(definitions.chiral_pair, None);
+        assert_eq!(definitions.pieces.len(), 13);
+    }
+
+    #[test]
+    fn test_legacy_count_spelling_the_magic() {
+        // a legacy file whose count bytes read `BLKR` and whose first piece
+        // count, 7, sits where the version would be
+        let solutions = SOMA_PUZZLE.solve(Some(2));
+        let mut adversarial = FILE_MAGIC.to_vec();
+        for solution in &solutions {
+            write_solution(&mut adversarial, solution).unwrap();
+        }
+        assert_eq!(adversarial[4], 7);
+
+        // it ends long before its count, so it is not misread as either format
+        assert!(matches!(
+            read_all::<3, 3, 3, 27>(&mut Cursor::new(&adversarial), 7),
+            Err(PersistenceError::VersionMismatch(7))
+        ));
+        let dir = std::env::temp_dir().join(format!("blocker-magic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(SOLUTIONS_BIN);
+        std::fs::write(&path, &adversarial).unwrap();
+        assert!(matches!(
+            count_from::<3, 3, 3, 27, 7>(&path),
+            Err(PersistenceError::VersionMismatch(7))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_errors_name_the_problem() {
+        let mut file = Vec::new();
+        write_binary(&SOMA_PUZZLE, &mut file, &SOMA_PUZZLE.solve(Some(2))).unwrap();
+        let read = |bytes: &[u8]| read_all::<3, 3, 3, 27>(&mut Cursor::new(bytes), 7);
+
+        assert!(matches!(
+            load_all_from::<3, 3, 3, 27, 7>(Path::new("no/such/solutions.bin")),
+            Err(PersistenceError::NotFound)
+        ));
+        assert!(matches!(
+            read(b"not a solutions file"),
+            Err(PersistenceError::BadMagic)
+        ));
+        assert!(matches!(
+            read(&file[..file.len() - 1]),
+            Err(PersistenceError::Truncated)
+        ));
+
+        let mut future = file.clone();
+        future[4] = PACKED_FILE_VERSION + 1;
+        assert!(matches!(
+            read(&future),
+            Err(PersistenceError::VersionMismatch(5))
+        ));
+
+        assert!(matches!(
+            read_all::<3, 3, 4, 36>(&mut Cursor::new(&file), 7),
+            Err(PersistenceError::DimensionMismatch {
+                expected: [3, 3, 4],
+                found: [3, 3, 3]
+            })
+        ));
+        assert!(matches!(
+            read_all::<3, 3, 3, 27>(&mut Cursor::new(&file), 6),
+            Err(PersistenceError::PieceCountMismatch {
+                expected: 6,
+                found: 7
+            })
+        ));
+    }
+
+    #[test]
+    fn test_save_to_and_load_from_directory() {
+        let solutions = SOMA_PUZZLE.solve(Some(3));
+        let dir = std::env::temp_dir().join(format!("blocker-save-to-{}", std::process::id()));
+
+        save_to(&SOMA_PUZZLE, &dir, &solutions).unwrap();
+        assert!(dir.join(SOLUTIONS_TXT).exists());
+        let path = dir.join(SOLUTIONS_BIN);
+        assert_eq!(count_from::<3, 3, 3, 27, 7>(&path).unwrap(), 3);
+        assert_same_solutions(&load_all_from::<3, 3, 3, 27, 7>(&path).unwrap(), &solutions);
+        assert!(matches!(
+            load_all_from::<4, 4, 4, 64, 13>(&path),
+            Err(PersistenceError::DimensionMismatch { .. })
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_consistency_reports_the_first_difference() {
+        let solutions = SOMA_PUZZLE.solve(Some(3));
+        let dir = std::env::temp_dir().join(format!("blocker-verify-{}", std::process::id()));
+        let verify = || verify_consistency_in::<3, 3, 3, 27, 7>(&dir);
+        let text_path = dir.join(SOLUTIONS_TXT);
+
+        save_to(&SOMA_PUZZLE, &dir, &solutions).unwrap();
+        assert!(verify().is_ok());
+        let text = std::fs::read_to_string(&text_path).unwrap();
+
+        // the same solutions listed in another order
+        let swapped = [&solutions[1], &solutions[0], &solutions[2]].map(|s| s.to_vec());
+        save_text::<3, 3, 3, 27>(&text_path, &swapped).unwrap();
+        assert!(matches!(
+            verify(),
+            Err(ConsistencyError::SolutionMismatch(0))
+        ));
+
+        let cut = text.rfind("Solution 3:").unwrap();
+        std::fs::write(&text_path, &text[..cut]).unwrap();
+        assert!(matches!(
+            verify(),
+            Err(ConsistencyError::SolutionMismatch(2))
+        ));
+
+        std::fs::write(&text_path, format!("{text}extra\n")).unwrap();
+        assert!(matches!(verify(), Err(ConsistencyError::TrailingText)));
+
+        save_text::<3, 3, 3, 27>(&text_path, &solutions[..2]).unwrap();
+        assert!(matches!(
+            verify(),
+            Err(ConsistencyError::CountMismatch { binary: 3 })
+        ));
+
+        std::fs::remove_file(&text_path).unwrap();
+        assert!(matches!(verify(), Err(ConsistencyError::Text(_))));
+        std::fs::remove_file(dir.join(SOLUTIONS_BIN)).unwrap();
+        assert!(matches!(
+            verify(),
+            Err(ConsistencyError::Binary(PersistenceError::NotFound))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_write_leaves_the_old_file() {
+        let solutions = SOMA_PUZZLE.solve(Some(2));
+        let dir = std::env::temp_dir().join(format!("blocker-atomic-{}", std::process::id()));
+        save_to(&SOMA_PUZZLE, &dir, &solutions).unwrap();
+        let path = dir.join(SOLUTIONS_BIN);
+        let saved = std::fs::read(&path).unwrap();
+
+        let failed = write_atomic(&path, |file| {
+            file.write_all(&FILE_MAGIC)?;
+            Err(std::io::Error::other("disk full"))
+        });
+        assert_eq!(failed.unwrap_err().to_string(), "disk full");
+        assert_eq!(std::fs::read(&path).unwrap(), saved);
+        // no temporary file is left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_packed_files_roundtrip_and_append() {
+        let solutions = SOMA_PUZZLE.solve(Some(3));
+        let dir = std::env::temp_dir().join(format!("blocker-packed-{}", std::process::id()));
+        save_to(&SOMA_PUZZLE, &dir, &solutions).unwrap();
+        let plain = dir.join(SOLUTIONS_BIN);
+        let packed = dir.join("packed.bin");
+        assert_eq!(pack_file(&SOMA_PUZZLE, &plain, &packed).unwrap(), 3);
+
+        // 141 bytes of cube lists against 14 of nibbles per solution
+        let size = |path: &Path| std::fs::metadata(path).unwrap().len();
+        assert_eq!(size(&plain) - size(&packed), 3 * (141 - 14));
+        let loaded = load_all_from::<3, 3, 3, 27, 7>(&packed).unwrap();
+        for (loaded, original) in loaded.iter().zip(&solutions) {
+            assert!(loaded.iter().map(|placed| placed.piece_index).eq(0..7));
+            assert_eq!(
+                solution_to_grid::<3, 3, 3, 27>(loaded),
+                solution_to_grid::<3, 3, 3, 27>(original)
+            );
+        }
+        assert_eq!(
+            load_definitions_from::<3, 3, 3, 27, 7>(&packed).unwrap(),
+            load_definitions_from::<3, 3, 3, 27, 7>(&plain).unwrap()
+        );
+
+        save_packed_to(&SOMA_PUZZLE, &dir, &solutions).unwrap();
+        assert_eq!(
+            std::fs::read(&plain).unwrap(),
+            std::fs::read(&packed).unwrap()
+        );
+        let more = SOMA_PUZZLE.solve(Some(5));
+        assert_eq!(append_unique(&SOMA_PUZZLE, &packed, &more).unwrap(), 2);
+        assert_eq!(size(&packed), size(&plain) + 2 * 14);
+        assert_eq!(count_from::<3, 3, 3, 27, 7>(&packed).unwrap(), 5);
+
+        const CUBE: &[Coord] = &[(0, 0, 0)];
+        let crowded: Puzzle<1, 1, 16, 16, 16> = Puzzle::new(&[CUBE; 16], None);
+        let error = save_packed_to(&crowded, &dir, &[]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_packed_files_hold_fifteen_pieces() {
+        // ids 0 to 14 all fit beside the empty nibble
+        const CUBE: &[Coord] = &[(0, 0, 0)];
+        let rod: Puzzle<1, 1, 15, 15, 15> = Puzzle::new(&[CUBE; 15], None);
+        let solution: Vec<PlacedPiece> = (0..15)
+            .map(|piece_index| {
+                let mut positions = [(0, 0, 0); MAX_CUBES];
+                positions[0] = (0, 0, 14 - piece_index as i32);
+                PlacedPiece {
+                    piece_index,
+                    positions,
+                    cube_count: 1,
+                }
+            })
+            .collect();
+        assert_eq!(rod.validate_solution(&solution), Ok(()));
+
+        let dir = std::env::temp_dir().join(format!("blocker-packed-15-{}", std::process::id()));
+        save_packed_to(&rod, &dir, std::slice::from_ref(&solution)).unwrap();
+        let loaded = load_all_from::<1, 1, 15, 15, 15>(&dir.join(SOLUTIONS_BIN)).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(
+            solution_to_grid::<1, 1, 15, 15>(&loaded[0]),
+            solution_to_grid::<1, 1, 15, 15>(&solution)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json_roundtrip() {
+        let solutions = SOMA_PUZZLE.solve(Some(3));
+        let json = solutions_to_json::<3, 3, 3, 27>(&solutions, 7);
+        assert!(json.starts_with(r#"{"dims":[3,3,3],"grid_size":27,"num_pieces":7,"#));
+
+        let loaded = solutions_from_json::<3, 3, 3, 27>(&json, 7).unwrap();
+        assert_same_solutions(&loaded, &solutions);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json_rejects_other_puzzles() {
+        let solutions = SOMA_PUZZLE.solve(Some(1));
+        let json = solutions_to_json::<3, 3, 3, 27>(&solutions, 7);
+        assert!(solutions_from_json::<4, 4, 4, 64>(&json, 13).is_err());
+        assert!(solutions_from_json::<3, 3, 3, 27>(&json, 6).is_err());
+
+        // a cube outside the box
+        let outside = json.replacen("[0,0,0]", "[0,0,3]", 1);
+        assert!(solutions_from_json::<3, 3, 3, 27>(&outside, 7).is_err());
     }
 }
@@ -3,66 +3,850 @@
 //! Each piece is defined as a set of unit cube positions in 3D space,
 //! normalized to start at the origin.
 
+#[cfg(feature = "native-io")]
+use std::path::Path;
+
+use crate::geometry::{all_orientations, normalize_to_origin, reflect};
+use crate::grid::{coord_to_idx, idx_to_coord};
+use crate::PuzzleOps;
+
 /// A 3D coordinate representing a unit cube position.
 pub type Coord = (i32, i32, i32);
 
-/// Maximum number of cubes in any single piece across all puzzles.
+/// Default limit on the number of cubes in a single piece.
+///
+/// Enough for Soma, Bedlam and the pentominoes. A puzzle with larger pieces
+/// raises its own limit through [`Puzzle`]'s `MAX_PIECE_CUBES` parameter.
 pub const MAX_CUBES: usize = 5;
 
 /// Puzzle definition with compile-time parameters.
 ///
-/// - `DIM`: grid dimension per axis (3 for Soma, 4 for Bedlam)
-/// - `GRID_SIZE`: total cells in the grid (must equal DIM^3)
-/// - `NUM_PIECES`: number of pieces in the puzzle
-pub struct Puzzle<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize> {
+/// - `DIM_X`, `DIM_Y`, `DIM_Z`: box extent along each axis (3x3x3 for Soma,
+///   4x4x4 for Bedlam, but any rectangular box such as 2x3x4 works)
+/// - `GRID_SIZE`: total cells in the grid (must equal DIM_X * DIM_Y * DIM_Z)
+/// - `NUM_PIECES`: number of pieces in the puzzle, at most [`MAX_PIECES`]
+/// - `MAX_PIECE_CUBES`: most cubes any piece may have, which sizes the
+///   [`PlacedPiece`]s in solutions; defaults to [`MAX_CUBES`], the only size
+///   that saving, checkpoints and [`PuzzleOps`](crate::PuzzleOps) support
+///
+/// The piece data is borrowed: `'static` for the built-in `const` puzzles,
+/// or from an [`OwnedPuzzle`] for puzzles defined at runtime.
+pub struct Puzzle<
+    'a,
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+    const MAX_PIECE_CUBES: usize = MAX_CUBES,
+> {
     /// The set of pieces in this puzzle.
-    pub pieces: &'static [&'static [Coord]],
+    pub pieces: &'a [&'a [Coord]],
+    /// How many interchangeable copies of each entry in `pieces` the puzzle
+    /// uses. Empty means one of each.
+    pub piece_counts: &'a [usize],
     /// Optional chiral mirror-image pair (piece indices).
     pub chiral_pair: Option<(usize, usize)>,
+    /// Cell indices that no piece may occupy.
+    pub blocked_cells: &'a [usize],
 }
 
-impl<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize>
-    Puzzle<DIM, GRID_SIZE, NUM_PIECES>
+/// A puzzle whose box is a cube with `DIM` cells per side.
+pub type CubePuzzle<'a, const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize> =
+    Puzzle<'a, DIM, DIM, DIM, GRID_SIZE, NUM_PIECES>;
+
+/// A flat puzzle: polyominoes packed into a `DIM_X` by `DIM_Y` rectangle.
+///
+/// This is a box one cell deep, with pieces lying in the z = 0 plane. Any
+/// rotation that tips a piece out of that plane leaves it two or more cells
+/// deep, so only the 8 rotations and flips of the plane (the dihedral group)
+/// ever produce placements, and the box symmetries reduce to those of the
+/// rectangle.
+pub type PlanarPuzzle<
+    'a,
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+> = Puzzle<'a, DIM_X, DIM_Y, 1, GRID_SIZE, NUM_PIECES>;
+
+impl<
+        'a,
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+        const MAX_PIECE_CUBES: usize,
+    > Puzzle<'a, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>
 {
     /// Creates a new puzzle definition with compile-time validation.
-    pub const fn new(
-        pieces: &'static [&'static [Coord]],
+    ///
+    /// The pieces must fill the box exactly: their cube counts must sum to
+    /// `GRID_SIZE`. Each piece must also start at the origin, with its
+    /// smallest x, y and z all zero; a const fn cannot move it there, and the
+    /// stored definitions and solution checks assume it. [`PuzzleBuilder`]
+    /// moves pieces given anywhere else.
+    ///
+    /// Checking that each piece holds together would need a flood fill,
+    /// which a const fn cannot run, so a disconnected piece gets through
+    /// here. Puzzles from [`PuzzleBuilder`] and [`load_puzzle`] are checked
+    /// for that too.
+    pub const fn new(pieces: &'a [&'a [Coord]], chiral_pair: Option<(usize, usize)>) -> Self {
+        assert!(
+            total_cubes(pieces, &[]) == GRID_SIZE,
+            "pieces must have GRID_SIZE cubes in total to fill the box"
+        );
+        Self::validated(pieces, &[], chiral_pair)
+    }
+
+    /// Creates a puzzle that uses `counts[i]` copies of `pieces[i]`.
+    ///
+    /// Copies of a shape are interchangeable: the solver places them in
+    /// order and gives them one label in canonical keys, so swapping two
+    /// copies never yields a new solution. `NUM_PIECES` counts every copy,
+    /// and piece indices in solutions (and in `chiral_pair`) number the
+    /// copies consecutively, shape by shape.
+    pub const fn with_counts(
+        pieces: &'a [&'a [Coord]],
+        counts: &'a [usize],
+        chiral_pair: Option<(usize, usize)>,
+    ) -> Self {
+        assert!(
+            counts.len() == pieces.len(),
+            "counts must give one multiplicity per piece"
+        );
+        assert!(
+            total_cubes(pieces, counts) == GRID_SIZE,
+            "pieces must have GRID_SIZE cubes in total to fill the box"
+        );
+        Self::validated(pieces, counts, chiral_pair)
+    }
+
+    /// Creates a puzzle whose pieces need not fill the whole box.
+    ///
+    /// Allows the pieces' cube counts to sum to less than `GRID_SIZE`, for
+    /// packing problems that leave cells empty. `solve` still looks for fills
+    /// of the whole box, so it finds none when the pieces fall short;
+    /// [`solve_partial`](Self::solve_partial) finds the packs instead.
+    pub const fn new_partial(
+        pieces: &'a [&'a [Coord]],
         chiral_pair: Option<(usize, usize)>,
     ) -> Self {
-        assert!(DIM * DIM * DIM == GRID_SIZE, "GRID_SIZE must equal DIM^3");
         assert!(
-            pieces.len() == NUM_PIECES,
+            total_cubes(pieces, &[]) <= GRID_SIZE,
+            "pieces have more cubes than the box has cells"
+        );
+        Self::validated(pieces, &[], chiral_pair)
+    }
+
+    /// Creates a puzzle whose box has cells that must stay empty.
+    ///
+    /// Solutions cover every cell except `blocked_cells` exactly once, so the
+    /// pieces' cube counts must sum to the number of open cells.
+    pub const fn with_blocked(
+        pieces: &'a [&'a [Coord]],
+        chiral_pair: Option<(usize, usize)>,
+        blocked_cells: &'a [usize],
+    ) -> Self {
+        let mut i = 0;
+        while i < blocked_cells.len() {
+            assert!(blocked_cells[i] < GRID_SIZE, "blocked cell out of range");
+            let mut j = 0;
+            while j < i {
+                assert!(
+                    blocked_cells[j] != blocked_cells[i],
+                    "blocked cell listed twice"
+                );
+                j += 1;
+            }
+            i += 1;
+        }
+        assert!(
+            total_cubes(pieces, &[]) + blocked_cells.len() == GRID_SIZE,
+            "pieces must have one cube per open cell to fill the box"
+        );
+        let mut puzzle = Self::validated(pieces, &[], chiral_pair);
+        puzzle.blocked_cells = blocked_cells;
+        puzzle
+    }
+
+    /// Checks everything but the piece volume, which each constructor
+    /// constrains differently.
+    const fn validated(
+        pieces: &'a [&'a [Coord]],
+        piece_counts: &'a [usize],
+        chiral_pair: Option<(usize, usize)>,
+    ) -> Self {
+        assert!(
+            DIM_X * DIM_Y * DIM_Z == GRID_SIZE,
+            "GRID_SIZE must equal DIM_X * DIM_Y * DIM_Z"
+        );
+        let mut copies = 0;
+        let mut i = 0;
+        while i < piece_counts.len() {
+            assert!(piece_counts[i] > 0, "each piece needs at least one copy");
+            copies += piece_counts[i];
+            i += 1;
+        }
+        assert!(
+            (piece_counts.is_empty() && pieces.len() == NUM_PIECES) || copies == NUM_PIECES,
             "pieces.len() must equal NUM_PIECES"
         );
-        assert!(GRID_SIZE <= 64, "GRID_SIZE must be <= 64 (u64 bitmask)");
+        assert!(GRID_SIZE <= 255, "GRID_SIZE must be <= 255 (u8 indices)");
         assert!(NUM_PIECES <= 32, "NUM_PIECES must be <= 32 (u32 bitmask)");
         let mut i = 0;
         while i < pieces.len() {
-            assert!(pieces[i].len() <= MAX_CUBES, "piece exceeds MAX_CUBES");
+            assert!(
+                pieces[i].len() <= MAX_PIECE_CUBES,
+                "piece exceeds MAX_PIECE_CUBES"
+            );
             i += 1;
         }
         Self {
             pieces,
+            piece_counts,
+            chiral_pair,
+            blocked_cells: &[],
+        }
+    }
+
+    /// Number of pieces placed in a solution, counting every copy.
+    pub const fn num_pieces(&self) -> usize {
+        let mut total = 0;
+        let mut i = 0;
+        while i < self.piece_counts.len() {
+            total += self.piece_counts[i];
+            i += 1;
+        }
+        if self.piece_counts.is_empty() {
+            self.pieces.len()
+        } else {
+            total
+        }
+    }
+
+    /// The shape of every piece index, with each copy listed separately.
+    pub fn piece_slots(&self) -> Vec<&'a [Coord]> {
+        if self.piece_counts.is_empty() {
+            return self.pieces.to_vec();
+        }
+        self.pieces
+            .iter()
+            .zip(self.piece_counts)
+            .flat_map(|(&piece, &count)| std::iter::repeat_n(piece, count))
+            .collect()
+    }
+
+    /// Checks that `solution` is a genuine solution of this puzzle.
+    ///
+    /// Every placed piece must be a distinct piece of the puzzle in one of
+    /// its orientations, and together the pieces must cover each open cell
+    /// of the box exactly once. Reports the first problem found.
+    pub fn validate_solution(
+        &self,
+        solution: &[PlacedPiece<MAX_PIECE_CUBES>],
+    ) -> Result<(), ValidationError> {
+        let slots = self.piece_slots();
+        let mut used = vec![false; slots.len()];
+        let mut covered = [false; GRID_SIZE];
+        for &cell in self.blocked_cells {
+            covered[cell] = true;
+        }
+
+        for placed in solution {
+            let piece_index = placed.piece_index;
+            if piece_index >= slots.len() || placed.cube_count as usize > MAX_PIECE_CUBES {
+                return Err(ValidationError::UnknownPiece(piece_index));
+            }
+            if std::mem::replace(&mut used[piece_index], true) {
+                return Err(ValidationError::DuplicatePiece(piece_index));
+            }
+
+            let in_box = |&(x, y, z): &Coord| {
+                (0..DIM_X as i32).contains(&x)
+                    && (0..DIM_Y as i32).contains(&y)
+                    && (0..DIM_Z as i32).contains(&z)
+            };
+            if !placed.cubes().iter().all(in_box) {
+                return Err(ValidationError::OutsideBox(piece_index));
+            }
+
+            if canonical_piece(placed.cubes()) != canonical_piece(slots[piece_index]) {
+                return Err(ValidationError::WrongShape(piece_index));
+            }
+
+            for &(x, y, z) in placed.cubes() {
+                let cell = coord_to_idx::<DIM_Y, DIM_Z>(x, y, z);
+                if std::mem::replace(&mut covered[cell], true) {
+                    return Err(ValidationError::Overlap((x, y, z)));
+                }
+            }
+        }
+
+        match covered.iter().position(|&covered| !covered) {
+            Some(cell) => Err(ValidationError::Gap(idx_to_coord::<DIM_Y, DIM_Z>(cell))),
+            None => Ok(()),
+        }
+    }
+
+    /// Bitmask of the piece indices that are a later copy of the index just
+    /// before them.
+    pub(crate) fn later_copies(&self) -> u32 {
+        let mut mask = 0;
+        let mut slot = 0;
+        for &count in self.piece_counts {
+            for copy in 0..count {
+                if copy > 0 {
+                    mask |= 1u32 << slot;
+                }
+                slot += 1;
+            }
+        }
+        mask
+    }
+}
+
+/// Total number of cubes across `pieces`, taking `counts[i]` copies of
+/// `pieces[i]` unless `counts` is empty.
+const fn total_cubes(pieces: &[&[Coord]], counts: &[usize]) -> usize {
+    let mut total = 0;
+    let mut i = 0;
+    while i < pieces.len() {
+        let copies = if counts.is_empty() { 1 } else { counts[i] };
+        total += pieces[i].len() * copies;
+        i += 1;
+    }
+    total
+}
+
+/// Largest cube side a runtime puzzle may have.
+///
+/// A 6x6x6 box has 216 cells, the most that fits the solver's `u8` cell
+/// indices.
+pub const MAX_RUNTIME_DIM: usize = 6;
+
+/// Most pieces a puzzle may have, counting every copy: one per bit of the
+/// solver's `u32` piece mask.
+///
+/// Constructing a [`Puzzle`] with a larger `NUM_PIECES` panics, and
+/// [`PuzzleBuilder::build`] reports [`PuzzleError::TooManyPieces`].
+pub const MAX_PIECES: usize = 32;
+
+/// The piece mask with one bit set for each of `num_pieces` pieces.
+///
+/// Shifts in `u64` so the full 32-piece mask needs no special case.
+///
+/// # Panics
+///
+/// Panics if `num_pieces` exceeds [`MAX_PIECES`].
+pub(crate) const fn full_piece_mask(num_pieces: usize) -> u32 {
+    assert!(
+        num_pieces <= MAX_PIECES,
+        "a piece mask holds at most 32 pieces"
+    );
+    ((1u64 << num_pieces) - 1) as u32
+}
+
+/// Reasons a [`PuzzleBuilder`] rejects a puzzle definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuzzleError {
+    /// The cube side is zero or larger than [`MAX_RUNTIME_DIM`].
+    UnsupportedDimension(usize),
+    /// More pieces than the solver's piece mask can track.
+    TooManyPieces(usize),
+    /// A piece has more than [`MAX_CUBES`] cubes.
+    PieceTooLarge { piece_index: usize, cubes: usize },
+    /// A piece has no cubes.
+    EmptyPiece(usize),
+    /// A piece's cubes do not form one polycube joined face to face.
+    Disconnected(usize),
+    /// The pieces' cubes don't add up to the box volume.
+    VolumeMismatch {
+        piece_cells: usize,
+        box_cells: usize,
+    },
+    /// A chiral pair index doesn't name a piece, or names the same one twice.
+    InvalidChiralPair(usize, usize),
+    /// A piece is wider than the box along some axis, so it can never be placed.
+    PieceOutsideBox(usize),
+    /// A piece is a copy of another or half of the chiral pair, so it can't
+    /// change shape on its own.
+    LinkedPiece(usize),
+    /// A puzzle file could not be read.
+    Read(std::io::ErrorKind),
+    /// A puzzle file is not valid TOML or lacks a required field.
+    InvalidFile(String),
+}
+
+/// Reasons [`Puzzle::validate_solution`] rejects a solution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// A piece index the puzzle doesn't have, or an impossible cube count.
+    UnknownPiece(usize),
+    /// The same piece is placed more than once.
+    DuplicatePiece(usize),
+    /// A piece has a cube outside the box.
+    OutsideBox(usize),
+    /// A piece's cubes are not an orientation of its shape.
+    WrongShape(usize),
+    /// Two pieces, or a piece and a blocked cell, share this cell.
+    Overlap(Coord),
+    /// No piece covers this open cell.
+    Gap(Coord),
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownPiece(piece_index) => {
+                write!(f, "piece {piece_index} is not in the puzzle")
+            }
+            Self::DuplicatePiece(piece_index) => write!(f, "piece {piece_index} is placed twice"),
+            Self::OutsideBox(piece_index) => write!(f, "piece {piece_index} leaves the box"),
+            Self::WrongShape(piece_index) => {
+                write!(f, "piece {piece_index} does not have its own shape")
+            }
+            Self::Overlap((x, y, z)) => write!(f, "cell ({x}, {y}, {z}) is covered twice"),
+            Self::Gap((x, y, z)) => write!(f, "cell ({x}, {y}, {z}) is not covered"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl std::fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedDimension(dim) => write!(
+                f,
+                "box dimension {dim} is not supported (expected 1 to {MAX_RUNTIME_DIM})"
+            ),
+            Self::TooManyPieces(count) => {
+                write!(f, "{count} pieces given, at most {MAX_PIECES} allowed")
+            }
+            Self::PieceTooLarge { piece_index, cubes } => write!(
+                f,
+                "piece {piece_index} has {cubes} cubes, at most {MAX_CUBES} allowed"
+            ),
+            Self::EmptyPiece(piece_index) => write!(f, "piece {piece_index} has no cubes"),
+            Self::Disconnected(piece_index) => {
+                write!(f, "piece {piece_index} is not a single connected piece")
+            }
+            Self::VolumeMismatch {
+                piece_cells,
+                box_cells,
+            } => write!(
+                f,
+                "pieces cover {piece_cells} cells but the box has {box_cells}"
+            ),
+            Self::InvalidChiralPair(a, b) => write!(f, "invalid chiral pair ({a}, {b})"),
+            Self::PieceOutsideBox(piece_index) => {
+                write!(f, "piece {piece_index} does not fit inside the box")
+            }
+            Self::LinkedPiece(piece_index) => write!(
+                f,
+                "piece {piece_index} is a copy or chiral partner of another piece"
+            ),
+            Self::Read(kind) => write!(f, "could not read puzzle file: {kind}"),
+            Self::InvalidFile(message) => write!(f, "invalid puzzle file: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+/// Builds a cube puzzle from piece data known only at runtime.
+///
+/// Unlike [`Puzzle::new`], which checks its input at compile time and panics,
+/// [`build`](Self::build) reports problems as a [`PuzzleError`].
+#[derive(Debug, Clone, Default)]
+pub struct PuzzleBuilder {
+    dim: usize,
+    pieces: Vec<Vec<Coord>>,
+    // None until set; `build` then detects the pair itself
+    chiral_pair: Option<Option<(usize, usize)>>,
+}
+
+impl PuzzleBuilder {
+    /// Starts a puzzle whose box is a cube with `dim` cells per side.
+    pub fn new(dim: usize) -> Self {
+        Self {
+            dim,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the pieces, replacing any added so far.
+    pub fn pieces(mut self, pieces: Vec<Vec<Coord>>) -> Self {
+        self.pieces = pieces;
+        self
+    }
+
+    /// Adds one piece after those already given.
+    pub fn piece(mut self, cubes: Vec<Coord>) -> Self {
+        self.pieces.push(cubes);
+        self
+    }
+
+    /// Sets the chiral mirror-image pair (piece indices).
+    ///
+    /// Without this, `build` uses the pair [`detect_chiral_pairs`] finds if
+    /// it finds exactly one. Passing `None` turns that detection off.
+    pub fn chiral_pair(mut self, chiral_pair: Option<(usize, usize)>) -> Self {
+        self.chiral_pair = Some(chiral_pair);
+        self
+    }
+
+    /// Validates the definition the way [`Puzzle::new`] does and returns the
+    /// puzzle, or the first problem found.
+    ///
+    /// Unlike [`Puzzle::new`], this also rejects pieces that are empty or
+    /// fall apart into cubes that share no faces, and it moves each piece to
+    /// start at the origin, so coordinates may be offset or negative.
+    pub fn build(mut self) -> Result<OwnedPuzzle, PuzzleError> {
+        if !(1..=MAX_RUNTIME_DIM).contains(&self.dim) {
+            return Err(PuzzleError::UnsupportedDimension(self.dim));
+        }
+        if self.pieces.len() > MAX_PIECES {
+            return Err(PuzzleError::TooManyPieces(self.pieces.len()));
+        }
+        if let Some(piece_index) = self.pieces.iter().position(Vec::is_empty) {
+            return Err(PuzzleError::EmptyPiece(piece_index));
+        }
+        self.pieces = self.pieces.into_iter().map(normalize_to_origin).collect();
+        if let Some((piece_index, piece)) = self
+            .pieces
+            .iter()
+            .enumerate()
+            .find(|(_, piece)| piece.len() > MAX_CUBES)
+        {
+            return Err(PuzzleError::PieceTooLarge {
+                piece_index,
+                cubes: piece.len(),
+            });
+        }
+        if let Some(piece_index) = self.pieces.iter().position(|piece| !is_connected(piece)) {
+            return Err(PuzzleError::Disconnected(piece_index));
+        }
+        if let Some(piece_index) = self
+            .pieces
+            .iter()
+            .position(|piece| !fits_in_cube(piece, self.dim))
+        {
+            return Err(PuzzleError::PieceOutsideBox(piece_index));
+        }
+
+        let piece_cells = self.pieces.iter().map(Vec::len).sum();
+        let box_cells = self.dim.pow(3);
+        if piece_cells != box_cells {
+            return Err(PuzzleError::VolumeMismatch {
+                piece_cells,
+                box_cells,
+            });
+        }
+
+        let chiral_pair = match self.chiral_pair {
+            Some(chiral_pair) => chiral_pair,
+            None => {
+                // one swapped pair can only stand in for a reflection when
+                // it is the puzzle's only mirror pair
+                let pieces: Vec<&[Coord]> = self.pieces.iter().map(Vec::as_slice).collect();
+                match detect_chiral_pairs(&pieces)[..] {
+                    [pair] => Some(pair),
+                    _ => None,
+                }
+            }
+        };
+        if let Some((a, b)) = chiral_pair {
+            if a == b || a >= self.pieces.len() || b >= self.pieces.len() {
+                return Err(PuzzleError::InvalidChiralPair(a, b));
+            }
+        }
+
+        Ok(OwnedPuzzle {
+            dim: self.dim,
+            pieces: self.pieces,
             chiral_pair,
+        })
+    }
+}
+
+/// Finds the pairs of pieces that are mirror images of each other.
+///
+/// A pair qualifies when a reflection of one piece is an orientation of the
+/// other but no rotation is, so pieces that match their own mirror image
+/// never pair up. Each pair is listed once, lower index first.
+pub fn detect_chiral_pairs(pieces: &[&[Coord]]) -> Vec<(usize, usize)> {
+    let shapes: Vec<Vec<Coord>> = pieces.iter().map(|piece| canonical_piece(piece)).collect();
+    let mirrored: Vec<Vec<Coord>> = pieces
+        .iter()
+        .map(|piece| {
+            if piece.is_empty() {
+                Vec::new()
+            } else {
+                canonical_piece(&reflect(piece))
+            }
+        })
+        .collect();
+
+    let mut pairs = Vec::new();
+    for a in 0..pieces.len() {
+        for b in a + 1..pieces.len() {
+            if mirrored[a] == shapes[b] && shapes[a] != shapes[b] {
+                pairs.push((a, b));
+            }
+        }
+    }
+    pairs
+}
+
+/// Finds the pairs of pieces that are the same shape, rotations of each
+/// other, each pair listed once, lower index first.
+///
+/// The solver treats every piece given separately as distinct, so two
+/// solutions that swap such a pair both count. Giving the shape once with
+/// a count of two, as [`Puzzle::with_counts`] takes it, counts them once.
+pub fn duplicate_shapes(pieces: &[&[Coord]]) -> Vec<(usize, usize)> {
+    let shapes: Vec<Vec<Coord>> = pieces.iter().map(|piece| canonical_piece(piece)).collect();
+    let mut pairs = Vec::new();
+    for a in 0..pieces.len() {
+        for b in a + 1..pieces.len() {
+            if shapes[a] == shapes[b] {
+                pairs.push((a, b));
+            }
+        }
+    }
+    pairs
+}
+
+/// The standard form of a piece's shape: of all its orientations, each
+/// moved to the origin with cubes in sorted order, the smallest.
+///
+/// Two pieces get the same form exactly when one is a rotation of the
+/// other, however their cubes are listed or offset. An empty piece gives
+/// an empty form.
+pub fn canonical_piece(piece: &[Coord]) -> Vec<Coord> {
+    if piece.is_empty() {
+        return Vec::new();
+    }
+    all_orientations(piece)
+        .into_iter()
+        .map(|mut orientation| {
+            orientation.sort_unstable();
+            orientation
+        })
+        .min()
+        .unwrap()
+}
+
+/// Checks that a piece's cubes are joined face to face into one polycube,
+/// by flood filling from the first cube through its six neighbours.
+pub(crate) fn is_connected(piece: &[Coord]) -> bool {
+    let Some(&first) = piece.first() else {
+        return true;
+    };
+    let mut reached = vec![first];
+    let mut frontier = vec![first];
+    while let Some((x, y, z)) = frontier.pop() {
+        let neighbours = [
+            (x + 1, y, z),
+            (x - 1, y, z),
+            (x, y + 1, z),
+            (x, y - 1, z),
+            (x, y, z + 1),
+            (x, y, z - 1),
+        ];
+        for neighbour in neighbours {
+            if piece.contains(&neighbour) && !reached.contains(&neighbour) {
+                reached.push(neighbour);
+                frontier.push(neighbour);
+            }
         }
     }
+    // duplicated cubes are reached once, so compare against distinct cubes
+    let mut distinct = piece.to_vec();
+    distinct.sort_unstable();
+    distinct.dedup();
+    reached.len() == distinct.len()
+}
+
+/// Checks that a piece spans at most `dim` cells along every axis.
+fn fits_in_cube(piece: &[Coord], dim: usize) -> bool {
+    let axes: [fn(&Coord) -> i32; 3] = [|c| c.0, |c| c.1, |c| c.2];
+    axes.iter().all(|axis| {
+        let min = piece.iter().map(axis).min().unwrap_or(0);
+        let max = piece.iter().map(axis).max().unwrap_or(0);
+        (max - min) as usize <= dim.saturating_sub(1)
+    })
+}
+
+/// Loads a cube puzzle from a TOML file.
+///
+/// The file gives the cube side, an optional chiral pair (detected from the
+/// pieces when left out) and one `[[piece]]` table per piece:
+///
+/// ```toml
+/// dim = 2
+/// chiral_pair = [0, 1]
+///
+/// [[piece]]
+/// cubes = [[0, 0, 0], [1, 0, 0], [0, 1, 0], [0, 0, 1]]
+/// ```
+///
+/// Each piece is moved to start at the origin, so any offset in the file is
+/// ignored, and the result is checked like any [`PuzzleBuilder`] puzzle.
+#[cfg(feature = "native-io")]
+pub fn load_puzzle(path: &Path) -> Result<OwnedPuzzle, PuzzleError> {
+    let text = std::fs::read_to_string(path).map_err(|error| PuzzleError::Read(error.kind()))?;
+    parse_puzzle(&text)
+}
+
+/// Parses a puzzle definition in the TOML format [`load_puzzle`] reads, for
+/// callers that have the text but no file.
+pub fn parse_puzzle(text: &str) -> Result<OwnedPuzzle, PuzzleError> {
+    let document: toml_edit::DocumentMut = text
+        .parse()
+        .map_err(|error: toml_edit::TomlError| PuzzleError::InvalidFile(error.to_string()))?;
+    let invalid = |message: &str| PuzzleError::InvalidFile(message.to_string());
+    let integers = |array: &toml_edit::Array| -> Option<Vec<i64>> {
+        array.iter().map(toml_edit::Value::as_integer).collect()
+    };
+
+    let dim = document
+        .get("dim")
+        .and_then(toml_edit::Item::as_integer)
+        .and_then(|dim| usize::try_from(dim).ok())
+        .ok_or_else(|| invalid("`dim` must be a non-negative integer"))?;
+
+    let chiral_pair = match document.get("chiral_pair") {
+        None => None,
+        Some(item) => {
+            let pair = item
+                .as_array()
+                .and_then(integers)
+                .and_then(|pair| <[i64; 2]>::try_from(pair).ok())
+                .and_then(|[a, b]| Some((usize::try_from(a).ok()?, usize::try_from(b).ok()?)))
+                .ok_or_else(|| invalid("`chiral_pair` must be two piece indices"))?;
+            Some(pair)
+        }
+    };
+
+    let tables = document
+        .get("piece")
+        .and_then(toml_edit::Item::as_array_of_tables)
+        .ok_or_else(|| invalid("pieces must be given as [[piece]] tables"))?;
+    let mut pieces = Vec::with_capacity(tables.len());
+    for (piece_index, table) in tables.iter().enumerate() {
+        let cubes: Option<Vec<Coord>> = table
+            .get("cubes")
+            .and_then(toml_edit::Item::as_array)
+            .and_then(|cubes| {
+                cubes
+                    .iter()
+                    .map(|cube| {
+                        let [x, y, z] = <[i64; 3]>::try_from(integers(cube.as_array()?)?).ok()?;
+                        Some((
+                            i32::try_from(x).ok()?,
+                            i32::try_from(y).ok()?,
+                            i32::try_from(z).ok()?,
+                        ))
+                    })
+                    .collect()
+            });
+        let cubes = cubes.ok_or_else(|| {
+            PuzzleError::InvalidFile(format!(
+                "piece {piece_index} needs `cubes` as a list of [x, y, z] coordinates"
+            ))
+        })?;
+        pieces.push(cubes);
+    }
+
+    let builder = PuzzleBuilder::new(dim).pieces(pieces);
+    match chiral_pair {
+        Some(pair) => builder.chiral_pair(Some(pair)).build(),
+        None => builder.build(),
+    }
+}
+
+/// A validated cube puzzle that owns its piece data.
+///
+/// Produced by [`PuzzleBuilder`]. Solving goes through [`PuzzleOps`]: each call
+/// borrows the pieces as a [`Puzzle`] sized for the box, so runtime puzzles
+/// use the same solver as the built-in ones.
+///
+/// [`PuzzleOps`]: crate::PuzzleOps
+#[derive(Debug, Clone)]
+pub struct OwnedPuzzle {
+    dim: usize,
+    pieces: Vec<Vec<Coord>>,
+    chiral_pair: Option<(usize, usize)>,
+}
+
+impl OwnedPuzzle {
+    /// Cells per side of the cubic box.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// The pieces, in piece index order, each starting at the origin.
+    pub fn pieces(&self) -> &[Vec<Coord>] {
+        &self.pieces
+    }
+
+    /// Chiral mirror-image pair (piece indices), if any.
+    pub fn chiral_pair(&self) -> Option<(usize, usize)> {
+        self.chiral_pair
+    }
+
+    /// Calls `f` with this puzzle borrowed as a [`Puzzle`] of matching size.
+    ///
+    /// The borrowed puzzle's `NUM_PIECES` is the piece limit rather than the
+    /// piece count; everything it does goes by `num_pieces()` instead.
+    pub fn with_puzzle<R>(&self, f: impl FnOnce(&dyn crate::PuzzleOps) -> R) -> R {
+        let pieces: Vec<&[Coord]> = self.pieces.iter().map(Vec::as_slice).collect();
+
+        macro_rules! dispatch {
+            ($($dim:literal => $grid_size:literal),*) => {
+                match self.dim {
+                    $($dim => f(&CubePuzzle::<$dim, $grid_size, MAX_PIECES> {
+                        pieces: &pieces,
+                        piece_counts: &[],
+                        chiral_pair: self.chiral_pair,
+                        blocked_cells: &[],
+                    }),)*
+                    _ => unreachable!("PuzzleBuilder only accepts supported dimensions"),
+                }
+            };
+        }
+        dispatch!(1 => 1, 2 => 8, 3 => 27, 4 => 64, 5 => 125, 6 => 216)
+    }
 }
 
 /// A piece placed at specific coordinates within the grid.
 ///
-/// Uses a fixed-size array to avoid heap allocation in the solver's hot loop.
+/// Uses a fixed-size array to avoid heap allocation in the solver's hot loop;
+/// `MAX_PIECE_CUBES` is its length, matching the puzzle's parameter.
+///
+/// With the `serde` feature, serializes as `piece_index` plus the live
+/// `cubes()` list rather than the padded `positions` array.
 #[derive(Clone, Copy)]
-pub struct PlacedPiece {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(into = "PlacedPieceRepr", try_from = "PlacedPieceRepr")
+)]
+pub struct PlacedPiece<const MAX_PIECE_CUBES: usize = MAX_CUBES> {
     pub piece_index: usize,
-    pub positions: [Coord; MAX_CUBES],
+    pub positions: [Coord; MAX_PIECE_CUBES],
     pub cube_count: u8,
 }
 
-impl PlacedPiece {
+impl<const MAX_PIECE_CUBES: usize> PlacedPiece<MAX_PIECE_CUBES> {
     /// A zero-valued placeholder for fixed-size array initialization.
     pub const EMPTY: Self = Self {
         piece_index: 0,
-        positions: [(0, 0, 0); MAX_CUBES],
+        positions: [(0, 0, 0); MAX_PIECE_CUBES],
         cube_count: 0,
     };
 
@@ -71,6 +855,75 @@ impl PlacedPiece {
     pub fn cubes(&self) -> &[Coord] {
         &self.positions[..self.cube_count as usize]
     }
+
+    /// Returns the valid cube positions in sorted order.
+    ///
+    /// [`cubes`](Self::cubes) keeps the order of the orientation the piece
+    /// was placed in; two placements cover the same cells exactly when their
+    /// sorted cubes are equal.
+    pub fn sorted_cubes(&self) -> Vec<Coord> {
+        let mut cubes = self.cubes().to_vec();
+        cubes.sort_unstable();
+        cubes
+    }
+}
+
+/// Shows the live cubes only, not the padding past `cube_count`.
+impl<const MAX_PIECE_CUBES: usize> std::fmt::Debug for PlacedPiece<MAX_PIECE_CUBES> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlacedPiece")
+            .field("piece_index", &self.piece_index)
+            .field("cubes", &self.cubes())
+            .finish()
+    }
+}
+
+/// One line: `P{piece_index}: [(x, y, z), ...]`.
+impl<const MAX_PIECE_CUBES: usize> std::fmt::Display for PlacedPiece<MAX_PIECE_CUBES> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "P{}: {:?}", self.piece_index, self.cubes())
+    }
+}
+
+/// Serialized form of [`PlacedPiece`] holding only the live cubes.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PlacedPieceRepr {
+    piece_index: usize,
+    cubes: Vec<Coord>,
+}
+
+#[cfg(feature = "serde")]
+impl<const MAX_PIECE_CUBES: usize> From<PlacedPiece<MAX_PIECE_CUBES>> for PlacedPieceRepr {
+    fn from(placed: PlacedPiece<MAX_PIECE_CUBES>) -> Self {
+        Self {
+            piece_index: placed.piece_index,
+            cubes: placed.cubes().to_vec(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const MAX_PIECE_CUBES: usize> TryFrom<PlacedPieceRepr> for PlacedPiece<MAX_PIECE_CUBES> {
+    type Error = String;
+
+    fn try_from(repr: PlacedPieceRepr) -> Result<Self, Self::Error> {
+        if repr.cubes.len() > MAX_PIECE_CUBES {
+            return Err(format!(
+                "piece has {} cubes, at most {} allowed",
+                repr.cubes.len(),
+                MAX_PIECE_CUBES
+            ));
+        }
+
+        let mut positions = [(0, 0, 0); MAX_PIECE_CUBES];
+        positions[..repr.cubes.len()].copy_from_slice(&repr.cubes);
+        Ok(Self {
+            piece_index: repr.piece_index,
+            positions,
+            cube_count: repr.cubes.len() as u8,
+        })
+    }
 }
 
 /// Indices of the chiral mirror-image pair in `PIECES`.
@@ -103,7 +956,7 @@ pub const SOMA_GRID_SIZE: usize = 27;
 pub const SOMA_NUM_PIECES: usize = 7;
 
 /// Soma puzzle definition.
-pub const SOMA_PUZZLE: Puzzle<SOMA_DIM, SOMA_GRID_SIZE, SOMA_NUM_PIECES> =
+pub const SOMA_PUZZLE: CubePuzzle<SOMA_DIM, SOMA_GRID_SIZE, SOMA_NUM_PIECES> =
     Puzzle::new(PIECES, Some(CHIRAL_PAIR));
 
 /// The thirteen Bedlam cube pieces that must fit into a 4x4x4 cube.
@@ -144,5 +997,562 @@ pub const BEDLAM_GRID_SIZE: usize = 64;
 pub const BEDLAM_NUM_PIECES: usize = 13;
 
 /// Bedlam puzzle definition.
-pub const BEDLAM_PUZZLE: Puzzle<BEDLAM_DIM, BEDLAM_GRID_SIZE, BEDLAM_NUM_PIECES> =
+pub const BEDLAM_PUZZLE: CubePuzzle<BEDLAM_DIM, BEDLAM_GRID_SIZE, BEDLAM_NUM_PIECES> =
     Puzzle::new(BEDLAM_PIECES, None);
+
+/// The twelve pentominoes, as flat pieces in the z = 0 plane.
+///
+/// Coordinates are normalized so the minimum coordinates are at the origin.
+pub const PENTOMINOES: &[&[Coord]] = &[
+    // F
+    &[(1, 0, 0), (2, 0, 0), (0, 1, 0), (1, 1, 0), (1, 2, 0)],
+    // I
+    &[(0, 0, 0), (1, 0, 0), (2, 0, 0), (3, 0, 0), (4, 0, 0)],
+    // L
+    &[(0, 0, 0), (1, 0, 0), (0, 1, 0), (0, 2, 0), (0, 3, 0)],
+    // N
+    &[(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 2, 0), (1, 3, 0)],
+    // P
+    &[(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0), (0, 2, 0)],
+    // T
+    &[(1, 0, 0), (1, 1, 0), (0, 2, 0), (1, 2, 0), (2, 2, 0)],
+    // U
+    &[(0, 0, 0), (2, 0, 0), (0, 1, 0), (1, 1, 0), (2, 1, 0)],
+    // V
+    &[(0, 0, 0), (1, 0, 0), (2, 0, 0), (0, 1, 0), (0, 2, 0)],
+    // W
+    &[(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 2, 0), (2, 2, 0)],
+    // X
+    &[(1, 0, 0), (0, 1, 0), (1, 1, 0), (2, 1, 0), (1, 2, 0)],
+    // Y
+    &[(0, 0, 0), (0, 1, 0), (1, 1, 0), (0, 2, 0), (0, 3, 0)],
+    // Z
+    &[(0, 0, 0), (1, 0, 0), (1, 1, 0), (1, 2, 0), (2, 2, 0)],
+];
+
+/// Pentomino puzzle constants: a 10x6 rectangle.
+pub const PENTOMINO_WIDTH: usize = 10;
+pub const PENTOMINO_HEIGHT: usize = 6;
+pub const PENTOMINO_GRID_SIZE: usize = 60;
+pub const PENTOMINO_NUM_PIECES: usize = 12;
+
+/// Pentomino puzzle definition.
+///
+/// Flipping a flat piece over is a rotation in 3D, so no pieces are chiral.
+pub const PENTOMINO_PUZZLE: PlanarPuzzle<
+    PENTOMINO_WIDTH,
+    PENTOMINO_HEIGHT,
+    PENTOMINO_GRID_SIZE,
+    PENTOMINO_NUM_PIECES,
+> = Puzzle::new(PENTOMINOES, None);
+
+/// Pentomino strip constants: a 20x3 rectangle.
+pub const PENTOMINO_STRIP_WIDTH: usize = 20;
+pub const PENTOMINO_STRIP_HEIGHT: usize = 3;
+
+/// The twelve pentominoes in a 20x3 rectangle, which they fill in only two
+/// ways.
+pub const PENTOMINO_STRIP_PUZZLE: PlanarPuzzle<
+    PENTOMINO_STRIP_WIDTH,
+    PENTOMINO_STRIP_HEIGHT,
+    PENTOMINO_GRID_SIZE,
+    PENTOMINO_NUM_PIECES,
+> = Puzzle::new(PENTOMINOES, None);
+
+/// The Slothouber-Graatsma puzzle: six 1x2x2 slabs and three unit cubes
+/// that must fit into a 3x3x3 cube.
+///
+/// Coordinates are normalized so the minimum coordinates are at the origin.
+pub const SLOTHOUBER_GRAATSMA_PIECES: &[&[Coord]] = &[
+    // Slab (4 cubes)
+    &[(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0)],
+    // Unit cube (1 cube)
+    &[(0, 0, 0)],
+];
+
+/// Copies of each Slothouber-Graatsma piece, in `SLOTHOUBER_GRAATSMA_PIECES`
+/// order.
+pub const SLOTHOUBER_GRAATSMA_COUNTS: &[usize] = &[6, 3];
+
+/// Slothouber-Graatsma puzzle constants.
+pub const SLOTHOUBER_GRAATSMA_DIM: usize = 3;
+pub const SLOTHOUBER_GRAATSMA_GRID_SIZE: usize = 27;
+pub const SLOTHOUBER_GRAATSMA_NUM_PIECES: usize = 9;
+
+/// Slothouber-Graatsma puzzle definition.
+///
+/// The copies of each shape are interchangeable, so the puzzle's one
+/// solution up to symmetry is counted once.
+pub const SLOTHOUBER_GRAATSMA_PUZZLE: CubePuzzle<
+    SLOTHOUBER_GRAATSMA_DIM,
+    SLOTHOUBER_GRAATSMA_GRID_SIZE,
+    SLOTHOUBER_GRAATSMA_NUM_PIECES,
+> = Puzzle::with_counts(SLOTHOUBER_GRAATSMA_PIECES, SLOTHOUBER_GRAATSMA_COUNTS, None);
+
+/// The built-in puzzles by name. A new built-in puzzle needs only an entry
+/// here to be listed and chosen by name.
+///
+/// Entries go through [`PuzzleOps`], whose solutions hold pieces of at most
+/// [`MAX_CUBES`] cubes. A puzzle with larger pieces, such as Conway's cube
+/// and its eight-cube 1x2x4 and 2x2x2 pieces, is still a [`Puzzle`] with a
+/// larger `MAX_PIECE_CUBES`, but cannot be listed here.
+const REGISTRY: &[(&str, &dyn PuzzleOps)] = &[
+    ("soma", &SOMA_PUZZLE),
+    ("bedlam", &BEDLAM_PUZZLE),
+    ("pentomino", &PENTOMINO_PUZZLE),
+    ("slothouber-graatsma", &SLOTHOUBER_GRAATSMA_PUZZLE),
+    ("pentomino-strip", &PENTOMINO_STRIP_PUZZLE),
+];
+
+/// Every built-in puzzle with its name, in listing order.
+///
+/// [`PuzzleOps`] hides each puzzle's box size and piece count, so puzzles
+/// of any shape share the list; [`PuzzleOps::dims`] gives the box back.
+pub fn registry() -> &'static [(&'static str, &'static dyn PuzzleOps)] {
+    REGISTRY
+}
+
+/// The built-in puzzle registered as `name`, if there is one.
+pub fn find_puzzle(name: &str) -> Option<&'static dyn PuzzleOps> {
+    REGISTRY
+        .iter()
+        .find(|(registered, _)| *registered == name)
+        .map(|&(_, puzzle)| puzzle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PuzzleOps;
+
+    fn soma_builder() -> PuzzleBuilder {
+        PuzzleBuilder::new(SOMA_DIM)
+            .pieces(PIECES.iter().map(|piece| piece.to_vec()).collect())
+            .chiral_pair(Some(CHIRAL_PAIR))
+    }
+
+    #[test]
+    fn test_builder_solves_like_const_puzzle() {
+        let soma = soma_builder().build().unwrap();
+        let solutions = soma.solve(None);
+        assert_eq!(solutions.len(), 240);
+
+        let keys = |solutions: &[Vec<PlacedPiece>]| -> Vec<[u8; 27]> {
+            solutions
+                .iter()
+                .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+                .collect()
+        };
+        assert_eq!(keys(&solutions), keys(&SOMA_PUZZLE.solve(None)));
+    }
+
+    #[test]
+    fn test_registry_finds_puzzles_by_name() {
+        let names: Vec<&str> = registry().iter().map(|&(name, _)| name).collect();
+        assert_eq!(
+            names,
+            [
+                "soma",
+                "bedlam",
+                "pentomino",
+                "slothouber-graatsma",
+                "pentomino-strip"
+            ]
+        );
+
+        let bedlam = find_puzzle("bedlam").unwrap();
+        assert_eq!(bedlam.dims(), [4, 4, 4]);
+        assert_eq!(bedlam.num_pieces(), 13);
+        assert_eq!(find_puzzle("pentomino").unwrap().dims(), [10, 6, 1]);
+        assert_eq!(find_puzzle("soma").unwrap().solution_multiplicity(3), 3);
+        assert!(find_puzzle("Soma").is_none());
+    }
+
+    #[test]
+    fn test_builder_moves_pieces_to_the_origin() {
+        let domino = vec![(0, 0, 0), (1, 0, 0)];
+        let build = |first: Vec<Coord>| {
+            PuzzleBuilder::new(2)
+                .pieces(vec![first, domino.clone(), domino.clone(), domino.clone()])
+                .build()
+                .unwrap()
+        };
+        let at_origin = build(domino.clone());
+        for offset in [vec![(5, 5, 5), (6, 5, 5)], vec![(-1, 0, -3), (0, 0, -3)]] {
+            let offset = build(offset);
+            assert_eq!(offset.pieces(), at_origin.pieces());
+
+            let cubes = |puzzle: &OwnedPuzzle| -> Vec<Vec<Vec<Coord>>> {
+                puzzle
+                    .solve(None)
+                    .iter()
+                    .map(|solution| {
+                        solution
+                            .iter()
+                            .map(|placed| placed.cubes().to_vec())
+                            .collect()
+                    })
+                    .collect()
+            };
+            assert_eq!(cubes(&offset), cubes(&at_origin));
+        }
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_definitions() {
+        assert_eq!(
+            soma_builder()
+                .chiral_pair(Some((4, 7)))
+                .build()
+                .unwrap_err(),
+            PuzzleError::InvalidChiralPair(4, 7)
+        );
+        assert_eq!(
+            soma_builder().piece(vec![(0, 0, 0)]).build().unwrap_err(),
+            PuzzleError::VolumeMismatch {
+                piece_cells: 28,
+                box_cells: 27
+            }
+        );
+        assert_eq!(
+            PuzzleBuilder::new(7).build().unwrap_err(),
+            PuzzleError::UnsupportedDimension(7)
+        );
+
+        let long = vec![
+            (0, 0, 0),
+            (1, 0, 0),
+            (2, 0, 0),
+            (3, 0, 0),
+            (4, 0, 0),
+            (5, 0, 0),
+        ];
+        let error = PuzzleBuilder::new(2).piece(long).build().unwrap_err();
+        assert_eq!(error.to_string(), "piece 0 has 6 cubes, at most 5 allowed");
+
+        let domino = vec![(0, 0, 0), (1, 0, 0)];
+        let with_second =
+            |piece: Vec<Coord>| PuzzleBuilder::new(2).piece(domino.clone()).piece(piece);
+        assert_eq!(
+            with_second(vec![]).build().unwrap_err(),
+            PuzzleError::EmptyPiece(1)
+        );
+        // cubes meeting only along an edge are two pieces, not one
+        assert_eq!(
+            with_second(vec![(0, 0, 0), (1, 1, 0)]).build().unwrap_err(),
+            PuzzleError::Disconnected(1)
+        );
+        let bent = "dim = 2\n[[piece]]\ncubes = [[0, 0, 0], [1, 0, 0], [1, 1, 0], [1, 1, 1]]\n\
+                    [[piece]]\ncubes = [[0, 0, 0], [0, 1, 1], [1, 0, 1], [1, 1, 1]]\n";
+        assert_eq!(
+            parse_puzzle(bent).unwrap_err(),
+            PuzzleError::Disconnected(1)
+        );
+    }
+
+    #[test]
+    fn test_validate_solution() {
+        let solution = SOMA_PUZZLE.solve(Some(1)).remove(0);
+        assert_eq!(SOMA_PUZZLE.validate_solution(&solution), Ok(()));
+
+        assert_eq!(
+            SOMA_PUZZLE.validate_solution(&solution[1..]),
+            Err(ValidationError::Gap(solution[0].cubes()[0]))
+        );
+        let mut twice = solution.clone();
+        twice[1] = twice[0];
+        assert_eq!(
+            SOMA_PUZZLE.validate_solution(&twice),
+            Err(ValidationError::DuplicatePiece(solution[0].piece_index))
+        );
+        // swapping two pieces' labels keeps the cover but not the shapes
+        let mut swapped = solution.clone();
+        let (first, second) = (swapped[0].piece_index, swapped[1].piece_index);
+        swapped[0].piece_index = second;
+        swapped[1].piece_index = first;
+        assert!(matches!(
+            SOMA_PUZZLE.validate_solution(&swapped),
+            Err(ValidationError::WrongShape(_))
+        ));
+        let mut overlapping = solution.clone();
+        overlapping[1].positions[0] = overlapping[0].positions[0];
+        assert!(SOMA_PUZZLE.validate_solution(&overlapping).is_err());
+    }
+
+    #[test]
+    fn test_detect_chiral_pairs() {
+        assert_eq!(detect_chiral_pairs(PIECES), vec![CHIRAL_PAIR]);
+        // Bedlam has several mirror pairs, which one swap cannot cover
+        assert_eq!(
+            detect_chiral_pairs(BEDLAM_PIECES),
+            vec![(3, 7), (4, 5), (5, 11), (7, 12)]
+        );
+
+        // the builder fills in the pair unless told otherwise
+        let detected = PuzzleBuilder::new(SOMA_DIM)
+            .pieces(PIECES.iter().map(|piece| piece.to_vec()).collect())
+            .build()
+            .unwrap();
+        assert_eq!(detected.chiral_pair(), Some(CHIRAL_PAIR));
+        let without = soma_builder().chiral_pair(None).build().unwrap();
+        assert_eq!(without.chiral_pair(), None);
+        let bedlam = PuzzleBuilder::new(BEDLAM_DIM)
+            .pieces(BEDLAM_PIECES.iter().map(|piece| piece.to_vec()).collect())
+            .build()
+            .unwrap();
+        assert_eq!(bedlam.chiral_pair(), None);
+    }
+
+    #[test]
+    fn test_canonical_piece_ignores_rotation_and_offset() {
+        let l_piece = PIECES[0];
+        let canonical = canonical_piece(l_piece);
+        for rotate in crate::geometry::ROTATIONS {
+            // rotate and shift the cubes, listing them back to front
+            let moved: Vec<Coord> = l_piece
+                .iter()
+                .rev()
+                .map(|&cube| {
+                    let (x, y, z) = rotate(cube);
+                    (x + 5, y - 2, z + 1)
+                })
+                .collect();
+            assert_eq!(canonical_piece(&moved), canonical);
+        }
+        // a mirror image is a different shape unless it matches itself
+        let (a, b) = CHIRAL_PAIR;
+        assert_ne!(canonical_piece(PIECES[a]), canonical_piece(PIECES[b]));
+        assert_eq!(
+            canonical_piece(&reflect(PIECES[a])),
+            canonical_piece(PIECES[b])
+        );
+
+        assert!(duplicate_shapes(PIECES).is_empty());
+        // Bedlam repeats three of its shapes
+        assert_eq!(
+            duplicate_shapes(BEDLAM_PIECES),
+            vec![(2, 10), (3, 12), (4, 11)]
+        );
+    }
+
+    #[test]
+    fn test_parse_puzzle_file() {
+        let puzzle = parse_puzzle(
+            "dim = 2\n\
+             [[piece]]\n\
+             cubes = [[0, 0, 0], [1, 0, 0], [0, 1, 0], [1, 1, 0]]\n\
+             [[piece]]\n\
+             cubes = [[0, 0, 5], [1, 0, 5], [0, 1, 5], [1, 1, 5]]\n",
+        )
+        .unwrap();
+        assert_eq!(puzzle.dim(), 2);
+        assert_eq!(puzzle.pieces()[0], puzzle.pieces()[1]);
+        assert_eq!(puzzle.chiral_pair(), None);
+        assert_eq!(puzzle.solve(None).len(), 1);
+
+        let bar = "dim = 2\n[[piece]]\ncubes = [[0, 0, 0], [1, 0, 0], [2, 0, 0]]\n";
+        assert_eq!(
+            parse_puzzle(bar).unwrap_err(),
+            PuzzleError::PieceOutsideBox(0)
+        );
+        assert!(matches!(
+            parse_puzzle("[[piece]]\ncubes = [[0, 0, 0]]\n"),
+            Err(PuzzleError::InvalidFile(_))
+        ));
+        assert!(matches!(
+            parse_puzzle("dim = 1\nchiral_pair = [0]\n[[piece]]\ncubes = [[0, 0, 0]]\n"),
+            Err(PuzzleError::InvalidFile(_))
+        ));
+        assert!(matches!(
+            parse_puzzle("dim = 1\n[[piece]]\ncubes = [[0, 0]]\n"),
+            Err(PuzzleError::InvalidFile(_))
+        ));
+        #[cfg(feature = "native-io")]
+        assert_eq!(
+            load_puzzle(Path::new("no/such/puzzle.toml")).unwrap_err(),
+            PuzzleError::Read(std::io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "native-io")]
+    fn test_owned_puzzle_files_load_as_const_puzzle() {
+        let soma = soma_builder().build().unwrap();
+        let solutions = soma.solve(Some(3));
+        let dir = std::env::temp_dir().join(format!("blocker-owned-{}", std::process::id()));
+
+        soma.save_solutions_to(&dir, &solutions).unwrap();
+        let loaded =
+            crate::persistence::load_all_from::<3, 3, 3, 27, 7>(&dir.join("solutions.bin"));
+        assert_eq!(loaded.unwrap().len(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const DOMINO: &[Coord] = &[(0, 0, 0), (1, 0, 0)];
+    const MONOCUBE: &[Coord] = &[(0, 0, 0)];
+
+    #[test]
+    fn test_piece_masks_at_the_32_piece_limit() {
+        assert_eq!(full_piece_mask(0), 0);
+        assert_eq!(full_piece_mask(1), 1);
+        assert_eq!(full_piece_mask(31), u32::MAX >> 1);
+        assert_eq!(full_piece_mask(32), u32::MAX);
+
+        // copies of one cube fill a row in just one way
+        let row = Puzzle::<31, 1, 1, 31, 31>::with_counts(&[MONOCUBE], &[31], None);
+        let solutions = row.solve(None);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(row.validate_solution(&solutions[0]), Ok(()));
+
+        let row = Puzzle::<32, 1, 1, 32, 32>::with_counts(&[MONOCUBE], &[32], None);
+        let solutions = row.solve(None);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].len(), 32);
+        assert_eq!(row.validate_solution(&solutions[0]), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "NUM_PIECES must be <= 32")]
+    fn test_new_rejects_a_33rd_piece() {
+        Puzzle::<33, 1, 1, 33, 33>::with_counts(&[MONOCUBE], &[33], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "pieces must have GRID_SIZE cubes")]
+    fn test_new_rejects_pieces_short_of_the_box() {
+        Puzzle::<1, 1, 5, 5, 2>::new(&[DOMINO, DOMINO], None);
+    }
+
+    #[test]
+    fn test_partial_and_blocked_volumes() {
+        let partial = Puzzle::<1, 1, 5, 5, 2>::new_partial(&[DOMINO, DOMINO], None);
+        assert!(partial.solve(None).is_empty());
+
+        let blocked = Puzzle::<1, 1, 5, 5, 2>::with_blocked(&[DOMINO, DOMINO], None, &[2]);
+        assert_eq!(blocked.solve(None).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "one cube per open cell")]
+    fn test_with_blocked_checks_open_volume() {
+        Puzzle::<1, 1, 5, 5, 2>::with_blocked(&[DOMINO, DOMINO], None, &[1, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_roundtrip_is_stable() {
+        let solution = SOMA_PUZZLE.solve(Some(1)).remove(0);
+        let json = serde_json::to_string(&solution).unwrap();
+        assert!(json.starts_with(r#"[{"piece_index":"#));
+
+        let parsed: Vec<PlacedPiece> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        for (parsed, original) in parsed.iter().zip(&solution) {
+            assert_eq!(parsed.positions, original.positions);
+            assert_eq!(parsed.cube_count, original.cube_count);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_rejects_oversized_piece() {
+        let json = r#"{"piece_index":0,"cubes":[[0,0,0],[1,0,0],[2,0,0],[3,0,0],[4,0,0],[5,0,0]]}"#;
+        assert!(serde_json::from_str::<PlacedPiece>(json).is_err());
+    }
+
+    #[test]
+    fn test_placed_piece_formats_only_live_cubes() {
+        let mut positions = [(9, 9, 9); MAX_CUBES];
+        positions[..2].copy_from_slice(&[(0, 0, 0), (1, 0, 2)]);
+        let placed = PlacedPiece {
+            piece_index: 3,
+            positions,
+            cube_count: 2,
+        };
+        assert_eq!(
+            format!("{placed:?}"),
+            "PlacedPiece { piece_index: 3, cubes: [(0, 0, 0), (1, 0, 2)] }"
+        );
+        assert_eq!(placed.to_string(), "P3: [(0, 0, 0), (1, 0, 2)]");
+
+        let mut reversed = placed;
+        reversed.positions.swap(0, 1);
+        assert_eq!(reversed.sorted_cubes(), placed.cubes());
+    }
+
+    #[test]
+    fn test_pentominoes_pack_a_strip() {
+        let solutions = PENTOMINO_STRIP_PUZZLE.solve(None);
+        assert_eq!(solutions.len(), 2);
+        assert!(solutions
+            .iter()
+            .flatten()
+            .all(|placed| placed.cubes().iter().all(|&(_, _, z)| z == 0)));
+    }
+
+    #[test]
+    fn test_slothouber_graatsma_has_one_solution() {
+        let solutions = SLOTHOUBER_GRAATSMA_PUZZLE.solve(None);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(
+            SLOTHOUBER_GRAATSMA_PUZZLE.validate_solution(&solutions[0]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_conway_cube_packs_with_eight_cube_pieces() {
+        // 18 pieces, more than any built-in, two of whose shapes need
+        // MAX_PIECE_CUBES = 8; the 125 cells still fit a u128 mask
+        const PIECES: &[&[Coord]] = &[
+            &[
+                (0, 0, 0),
+                (1, 0, 0),
+                (2, 0, 0),
+                (3, 0, 0),
+                (0, 1, 0),
+                (1, 1, 0),
+                (2, 1, 0),
+                (3, 1, 0),
+            ],
+            &[
+                (0, 0, 0),
+                (1, 0, 0),
+                (0, 1, 0),
+                (1, 1, 0),
+                (0, 0, 1),
+                (1, 0, 1),
+                (0, 1, 1),
+                (1, 1, 1),
+            ],
+            &[(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0)],
+            &[(0, 0, 0), (1, 0, 0), (2, 0, 0)],
+        ];
+        let conway: Puzzle<5, 5, 5, 125, 18, 8> = Puzzle::with_counts(PIECES, &[13, 1, 1, 3], None);
+
+        let solutions = conway.solve(Some(1));
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(conway.validate_solution(&solutions[0]), Ok(()));
+    }
+
+    #[test]
+    fn test_rods_fill_a_box_past_128_cells() {
+        // 150 cells take the word-array mask; rods lie along x or y, or
+        // along z flush with one end
+        const ROD: &[Coord] = &[(0, 0, 0), (0, 0, 1), (0, 0, 2), (0, 0, 3), (0, 0, 4)];
+        let rods: Puzzle<5, 5, 6, 150, 30> = Puzzle::with_counts(&[ROD], &[30], None);
+
+        let solutions = rods.solve(None);
+        assert_eq!(solutions.len(), 107);
+        assert!(solutions
+            .iter()
+            .all(|solution| rods.validate_solution(solution) == Ok(())));
+    }
+
+    #[test]
+    #[ignore = "takes about 30s in release builds"]
+    fn test_pentomino_solution_count() {
+        assert_eq!(PENTOMINO_PUZZLE.solve(None).len(), 2339);
+    }
+}
@@ -1,25 +1,44 @@
 //! Optimized backtracking puzzle solver.
 //!
 //! Key optimizations:
-//! - Bitmask for occupied cells (u32 or u64 selected by grid size)
+//! - Bitmask for occupied cells (u32, u64, u128 or a u64 array selected by grid size)
 //! - Pre-computed orientation bitmasks for instant collision detection
 //! - FxHashSet for faster state deduplication
 //! - Fixed-size arrays to avoid heap allocations in hot loop
 //! - Bitmask for remaining pieces (u32 for up to 32 pieces)
+//! - Top-level branches searched in parallel with rayon
 
-use rustc_hash::FxHashSet;
+#[cfg(feature = "native-io")]
+mod checkpoint;
+mod dlx;
 
-use crate::geometry::all_orientations;
-use crate::grid::{coord_to_idx, idx_to_coord};
-use crate::pieces::{Coord, PlacedPiece, Puzzle, MAX_CUBES};
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use rustc_hash::{FxHashSet, FxHasher};
+
+use crate::geometry::cached_orientations;
+use crate::grid::{coord_to_idx, idx_to_coord, PackedKey};
+use crate::pieces::{
+    full_piece_mask, is_connected, Coord, PlacedPiece, Puzzle, PuzzleError, MAX_CUBES,
+};
 
 /// A piece orientation: the cube positions after rotation and normalization.
 type Orientation = Vec<Coord>;
 
 /// Trait for bitmask types used to track occupied grid cells.
 ///
-/// Implemented for `u32` (grids up to 32 cells) and `u64` (up to 64 cells).
-trait CellMask: Copy + Eq + std::ops::BitAnd<Output = Self> + std::ops::BitOr<Output = Self> {
+/// Implemented for `u32` (grids up to 32 cells), `u64` (up to 64 cells),
+/// `u128` (up to 128 cells) and [`WordMask`] for anything larger.
+trait CellMask:
+    Copy + Eq + Send + Sync + std::ops::BitAnd<Output = Self> + std::ops::BitOr<Output = Self>
+{
     fn zero() -> Self;
     fn all_filled(grid_size: usize) -> Self;
     fn bit(index: usize) -> Self;
@@ -57,239 +76,3056 @@ impl CellMask for u64 {
     fn is_nonzero(self) -> bool { self != 0 }
 }
 
+impl CellMask for u128 {
+    #[inline(always)]
+    fn zero() -> Self { 0 }
+    #[inline(always)]
+    fn all_filled(grid_size: usize) -> Self {
+        if grid_size == 128 { u128::MAX } else { (1u128 << grid_size) - 1 }
+    }
+    #[inline(always)]
+    fn bit(index: usize) -> Self { 1u128 << index }
+    #[inline(always)]
+    fn trailing_ones(self) -> usize { self.trailing_ones() as usize }
+    #[inline(always)]
+    fn is_nonzero(self) -> bool { self != 0 }
+}
+
+/// Number of `u64` words in the bitset used for grids over 128 cells.
+const WIDE_MASK_WORDS: usize = 4;
+
+/// Fixed-size bitset for grids too large for a primitive integer.
+///
+/// Cell `i` lives in bit `i % 64` of word `i / 64`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct WordMask<const WORDS: usize>([u64; WORDS]);
+
+impl<const WORDS: usize> std::ops::BitAnd for WordMask<WORDS> {
+    type Output = Self;
+
+    #[inline(always)]
+    fn bitand(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|word| self.0[word] & rhs.0[word]))
+    }
+}
+
+impl<const WORDS: usize> std::ops::BitOr for WordMask<WORDS> {
+    type Output = Self;
+
+    #[inline(always)]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|word| self.0[word] | rhs.0[word]))
+    }
+}
+
+impl<const WORDS: usize> CellMask for WordMask<WORDS> {
+    #[inline(always)]
+    fn zero() -> Self {
+        Self([0; WORDS])
+    }
+
+    #[inline(always)]
+    fn all_filled(grid_size: usize) -> Self {
+        Self(std::array::from_fn(|word| {
+            let low_cell = word * 64;
+            if grid_size >= low_cell + 64 {
+                u64::MAX
+            } else if grid_size <= low_cell {
+                0
+            } else {
+                (1u64 << (grid_size - low_cell)) - 1
+            }
+        }))
+    }
+
+    #[inline(always)]
+    fn bit(index: usize) -> Self {
+        let mut words = [0; WORDS];
+        words[index / 64] = 1u64 << (index % 64);
+        Self(words)
+    }
+
+    #[inline(always)]
+    fn trailing_ones(self) -> usize {
+        let mut count = 0;
+        for word in self.0 {
+            count += word.trailing_ones() as usize;
+            // a partially filled word ends the run
+            if word != u64::MAX {
+                break;
+            }
+        }
+        count
+    }
+
+    #[inline(always)]
+    fn is_nonzero(self) -> bool {
+        self.0.iter().any(|&word| word != 0)
+    }
+}
+
 /// Pre-computed placement data for a piece orientation at a specific position.
 #[derive(Clone, Copy)]
-struct Placement<M: CellMask> {
+struct Placement<M: CellMask, const MAX_PIECE_CUBES: usize = MAX_CUBES> {
     // bitmask used for fast overlap checks
     occupied_mask: M,
     // absolute cube positions used to build output solutions
-    cube_positions: [Coord; MAX_CUBES],
+    cube_positions: [Coord; MAX_PIECE_CUBES],
     // number of valid coordinates in cube_positions
     cube_count: u8,
 }
 
 /// A partial solution in the iterative backtracking search.
 #[derive(Clone, Copy)]
-struct PartialSolution<const NUM_PIECES: usize, M: CellMask> {
+struct PartialSolution<
+    const NUM_PIECES: usize,
+    M: CellMask,
+    const MAX_PIECE_CUBES: usize = MAX_CUBES,
+> {
     // placed pieces in this search path
-    placed_pieces: [PlacedPiece; NUM_PIECES],
+    placed_pieces: [PlacedPiece<MAX_PIECE_CUBES>; NUM_PIECES],
     // number of valid entries in placed_pieces
     placed_count: usize,
     // bit i set means piece i is still available
     remaining_pieces: u32,
-    // bit i set means grid cell i is occupied
+    // bit i set means grid cell i is occupied or was left empty
     occupied_cells: M,
-    // next piece index to scan in this frame
+    // cells a partial pack chose to leave empty
+    skipped_count: usize,
+    // next position in the piece scan order to try in this frame
     current_piece_index: usize,
     // next placement index for current piece at target cell
     current_orientation_index: usize,
 }
 
 // lookup by piece then target cell then valid placements for that target
-type PlacementTable<M> = Vec<Vec<Vec<Placement<M>>>>;
+type PlacementTable<M, const MAX_PIECE_CUBES: usize = MAX_CUBES> =
+    Vec<Vec<Vec<Placement<M, MAX_PIECE_CUBES>>>>;
 
-impl<const DIM: usize, const GRID_SIZE: usize, const NUM_PIECES: usize>
-    Puzzle<DIM, GRID_SIZE, NUM_PIECES>
-{
-    /// Finds unique solutions, up to an optional limit.
+// lookup by cell then (piece bit, mask) for every placement covering it
+type CellPlacements<M> = Vec<Vec<(u32, M)>>;
+
+/// Counters describing the work done by one solve.
+///
+/// Gathered with plain integer increments in the search loop, so `solve`
+/// pays nothing extra for them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SolveStats {
+    /// Partial states pushed onto the search stack (search nodes explored).
+    pub stack_pushes: u64,
+    /// Placements rejected because they overlap already-placed cubes.
+    pub overlap_rejections: u64,
+    /// Partial states skipped because a symmetric copy was already seen.
+    pub dedup_hits: u64,
+    /// Partial states skipped because an empty cell could no longer be filled.
+    pub dead_cell_prunes: u64,
+    /// Wall-clock time of the whole solve, including table construction.
+    pub elapsed: Duration,
+}
+
+impl SolveStats {
+    /// Adds another search's counters to these (elapsed time is not summed).
+    fn add_counts(&mut self, other: &SolveStats) {
+        self.stack_pushes += other.stack_pushes;
+        self.overlap_rejections += other.overlap_rejections;
+        self.dedup_hits += other.dedup_hits;
+        self.dead_cell_prunes += other.dead_cell_prunes;
+    }
+}
+
+/// Measures of how hard a puzzle is to solve, from [`Puzzle::difficulty`].
+///
+/// Larger numbers mean a harder puzzle, except for `solutions`, where many
+/// solutions make any one of them easier to find.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DifficultyMetrics {
+    /// Search nodes the plain depth-first search explores before its first
+    /// solution, or in all when there is none.
+    pub nodes_to_first_solution: u64,
+    /// Unique solutions, counted up to the limit passed to `difficulty`.
+    pub solutions: usize,
+    /// Distinct first moves up to symmetry: the placements covering the
+    /// first open cell, as the parallel search splits its work.
+    pub root_branching: usize,
+    /// Mean number of distinct orientations per piece. Pieces with more
+    /// orientations fit in more ways, which widens every step of the search.
+    pub average_orientations: f64,
+}
+
+/// The size of a puzzle's placement table, from
+/// [`Puzzle::placement_table_stats`].
+///
+/// The table lists every placement under each cell it covers, so it holds
+/// each placement once per cube of its piece.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableStats {
+    /// Ways to place each piece in the box, in piece index order.
+    pub placements_per_piece: Vec<usize>,
+    /// Placements across all pieces, each counted once.
+    pub total_placements: usize,
+    /// Entries in the table, each placement counted once per cube.
+    pub table_entries: usize,
+    /// Heap memory the table takes: its entries at the bitmask width the
+    /// puzzle solves with, plus the list headers that hold them.
+    pub approximate_bytes: usize,
+}
+
+/// Search tuning for [`Puzzle::solve_with_options`].
+///
+/// The defaults reproduce [`Puzzle::solve`] exactly.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SolveOptions {
+    /// How the search picks the next cell to fill.
+    pub heuristic: CellChoice,
+    /// Drop a partial state as soon as some empty cell has no placement of a
+    /// remaining piece that fits, instead of searching below it.
+    pub prune_dead_cells: bool,
+    /// How the search remembers the states it has already reached.
+    pub dedup: Dedup,
+    /// Which symmetries of the box make two solutions the same.
+    pub symmetry: Symmetry,
+    /// List each solution's pieces by `piece_index` rather than in the
+    /// order the search placed them, so the same solution always comes out
+    /// the same way.
+    pub sort_pieces: bool,
+    /// The order the search tries pieces in at each cell, as a permutation
+    /// of the piece indices. Left empty, pieces with more cubes go first,
+    /// ties by index, since a large piece has the fewest places left to go.
+    /// The order changes which solutions turn up first and how soon, never
+    /// which ones are found.
+    pub piece_order: Vec<usize>,
+}
+
+impl SolveOptions {
+    /// Whether the search needs the per-cell placement lists.
+    fn needs_cell_placements(&self) -> bool {
+        self.heuristic != CellChoice::FirstEmpty || self.prune_dead_cells
+    }
+}
+
+/// Rule for choosing which empty cell the search branches on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellChoice {
+    /// The lowest-index empty cell. Cheap to find, but it can branch on a
+    /// wide-open cell while a nearly enclosed one is left for later.
+    #[default]
+    FirstEmpty,
+    /// The empty cell with the fewest placements that still fit, so forced
+    /// and impossible cells are handled first. Costs a scan of every empty
+    /// cell at each step.
+    MostConstrained,
+}
+
+/// The symmetries under which two solutions count as one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Rotations and reflections of the box, so a solution and its mirror
+    /// image count once. The mirror image swaps the puzzle's chiral pair.
+    #[default]
+    RotationReflection,
+    /// Rotations of the box alone, so a solution and its mirror image count
+    /// apart, as for a physical puzzle that can be turned but not mirrored.
+    RotationOnly,
+}
+
+/// How a search recognizes partial states it has already reached, up to
+/// symmetry.
+// the hidden variant serves the bench, not as a non-exhaustive marker
+#[allow(clippy::manual_non_exhaustive)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dedup {
+    /// Keep every canonical key in a hash set. Exact, but the set grows
+    /// with every state reached, which for large puzzles can exceed memory.
+    #[default]
+    Exact,
+    /// Keep a Bloom filter of `bits` bits, set by `hashes` hash functions
+    /// per key, so memory stays fixed however long the search runs.
     ///
-    /// Automatically selects `u32` bitmasks for grids up to 32 cells and `u64`
-    /// for larger grids.
-    pub fn solve(&self, max_solutions: Option<usize>) -> Vec<Vec<PlacedPiece>> {
-        if GRID_SIZE <= 32 {
-            self.solve_with_mask::<u32>(max_solutions)
-        } else {
-            self.solve_with_mask::<u64>(max_solutions)
+    /// A false positive makes a new state look seen, and the search then
+    /// skips it and every solution below it, so some solutions may be
+    /// missed; none are returned twice. With `n` states reached the miss
+    /// rate per state is about `(1 - e^(-hashes * n / bits))^hashes`, which
+    /// stays under 1% at 10 bits and 7 hashes per state. Each parallel
+    /// branch keeps its own filter.
+    Bloom { bits: usize, hashes: u32 },
+    // like Exact, but keeps keys at a byte per cell rather than packing
+    // them; only for the dedup_keys bench, not part of the supported API
+    #[doc(hidden)]
+    ExactBytes,
+}
+
+/// Completion bookkeeping shared by parallel workers.
+///
+/// Tracks unique solutions across threads so progress reports see one
+/// running count, and stops branches once the limit is sure to be met by
+/// the branches before them.
+struct SharedLimit<const GRID_SIZE: usize, F> {
+    max_solutions: Option<usize>,
+    // canonical keys of complete solutions found by any worker, and the
+    // progress callback invoked under the same lock
+    found: Mutex<(FxHashSet<[u8; GRID_SIZE]>, F)>,
+    prefix: Mutex<BranchPrefix<GRID_SIZE>>,
+    // one flag per branch, set once the branch's results fall past the limit
+    stops: Vec<AtomicBool>,
+}
+
+/// How far the merged results, taken in branch order, are settled.
+///
+/// The first `n` merged solutions only depend on branches up to the one
+/// where the running count reaches `n`, and on that branch's solutions up
+/// to that point. Every later branch can stop without changing the result,
+/// however the threads were scheduled.
+struct BranchPrefix<const GRID_SIZE: usize> {
+    // keys merged from every branch before `frontier`, all of them finished
+    merged: FxHashSet<[u8; GRID_SIZE]>,
+    // first branch that has not finished
+    frontier: usize,
+    // keys the frontier branch has found that are not in `merged`
+    fresh: usize,
+    // keys each branch has found, until the frontier passes it
+    pending: Vec<Vec<[u8; GRID_SIZE]>>,
+    finished: Vec<bool>,
+    // set once the limit is met, after which nothing changes
+    cut: bool,
+}
+
+impl<const GRID_SIZE: usize, F: FnMut(usize)> SharedLimit<GRID_SIZE, F> {
+    fn new(max_solutions: Option<usize>, branches: usize, on_solution: F) -> Self {
+        Self {
+            max_solutions,
+            found: Mutex::new((FxHashSet::default(), on_solution)),
+            prefix: Mutex::new(BranchPrefix {
+                merged: FxHashSet::default(),
+                frontier: 0,
+                fresh: 0,
+                pending: vec![Vec::new(); branches],
+                finished: vec![false; branches],
+                cut: false,
+            }),
+            stops: (0..branches).map(|_| AtomicBool::new(false)).collect(),
         }
     }
 
-    fn solve_with_mask<M: CellMask>(
-        &self,
-        max_solutions: Option<usize>,
-    ) -> Vec<Vec<PlacedPiece>> {
-        let placement_table = Self::build_placement_table(self.pieces);
-        let num_pieces = self.pieces.len();
-
-        let mut solutions = Vec::new();
-        let mut seen_states: FxHashSet<[u8; GRID_SIZE]> = FxHashSet::default();
+    /// Records a complete solution from `branch` by its canonical key.
+    fn record(&self, branch: usize, canonical: [u8; GRID_SIZE]) {
+        self.report(canonical);
+        let Some(max) = self.max_solutions else {
+            return;
+        };
+        let mut prefix = self.prefix.lock().unwrap();
+        if !prefix.cut {
+            // keys of later branches are checked once the frontier reaches them
+            if branch == prefix.frontier && !prefix.merged.contains(&canonical) {
+                prefix.fresh += 1;
+            }
+            prefix.pending[branch].push(canonical);
+            self.advance(&mut prefix, max);
+        }
+    }
 
-        let initial_remaining = if num_pieces == 32 {
-            // avoid shifting by 32 on u32
-            u32::MAX
-        } else {
-            (1u32 << num_pieces) - 1
+    /// Marks `branch` as searched to the end or stopped.
+    fn finish(&self, branch: usize) {
+        let Some(max) = self.max_solutions else {
+            return;
         };
-        let empty_piece = PlacedPiece::EMPTY;
+        let mut prefix = self.prefix.lock().unwrap();
+        if !prefix.cut {
+            prefix.finished[branch] = true;
+            self.advance(&mut prefix, max);
+        }
+    }
 
-        // explicit dfs stack so we can resume parent states without recursion
-        let mut search_stack = vec![PartialSolution {
-            placed_pieces: [empty_piece; NUM_PIECES],
-            placed_count: 0,
-            remaining_pieces: initial_remaining,
-            occupied_cells: M::zero(),
-            current_piece_index: 0,
-            current_orientation_index: 0,
-        }];
-
-        while let Some(mut partial) = search_stack.pop() {
-            // always fill the first empty cell to keep branching consistent
-            let target_cell = match Self::find_first_empty_cell(partial.occupied_cells) {
-                Some(cell) => cell,
-                None => {
-                    // no empty cell means a complete solution
-                    let solution = partial.placed_pieces[..partial.placed_count].to_vec();
-                    solutions.push(solution);
-                    if max_solutions.is_some_and(|max| solutions.len() >= max) {
-                        return solutions;
-                    }
-                    continue;
+    /// Passes the progress callback the running count of unique solutions.
+    fn report(&self, canonical: [u8; GRID_SIZE]) {
+        let mut guard = self.found.lock().unwrap();
+        let (found, on_solution) = &mut *guard;
+
+        // late finishers after the limit must not push the count past it
+        if self.max_solutions.is_some_and(|max| found.len() >= max) {
+            return;
+        }
+        if found.insert(canonical) {
+            on_solution(found.len());
+        }
+    }
+
+    /// Moves the frontier past finished branches, stopping the frontier
+    /// branch and all after it once the merged count reaches `max`.
+    fn advance(&self, prefix: &mut BranchPrefix<GRID_SIZE>, max: usize) {
+        while prefix.frontier < prefix.pending.len() {
+            let branch = prefix.frontier;
+            if prefix.merged.len() + prefix.fresh >= max {
+                prefix.cut = true;
+                for stop in &self.stops[branch..] {
+                    stop.store(true, Ordering::Relaxed);
                 }
-            };
+                return;
+            }
+            if !prefix.finished[branch] {
+                return;
+            }
+            let keys = std::mem::take(&mut prefix.pending[branch]);
+            prefix.merged.extend(keys);
+            prefix.frontier += 1;
+            prefix.fresh = prefix.pending.get(prefix.frontier).map_or(0, |keys| {
+                keys.iter()
+                    .filter(|key| !prefix.merged.contains(*key))
+                    .count()
+            });
+        }
+    }
+}
 
-            'pieces: loop {
-                // scan remaining piece bits from the current index
-                let Some(piece_index) = (partial.current_piece_index..num_pieces)
-                    .find(|&i| (partial.remaining_pieces & (1u32 << i)) != 0)
-                else {
-                    break 'pieces;
-                };
-                partial.current_piece_index = piece_index;
+/// Resumable depth-first search that yields one complete solution at a time.
+///
+/// Owns the explicit stack and dedup set; the placement table is borrowed by
+/// parallel workers and owned by a standalone iterator.
+struct Search<
+    'a,
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+    M: CellMask,
+    const MAX_PIECE_CUBES: usize = MAX_CUBES,
+> {
+    puzzle: &'a Puzzle<'a, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>,
+    placement_table: Cow<'a, PlacementTable<M, MAX_PIECE_CUBES>>,
+    // explicit dfs stack so we can resume parent states without recursion
+    search_stack: Vec<PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>>,
+    seen_states: SeenStates<GRID_SIZE>,
+    // skip states whose canonical key was already seen
+    dedup: bool,
+    // cells a partial pack may leave empty; zero means fill the box
+    spare_cells: usize,
+    options: SolveOptions,
+    // piece indices in the order each frame scans them
+    piece_order: Cow<'a, [usize]>,
+    // only filled in when the options need per-cell placement counts
+    cell_placements: Cow<'a, CellPlacements<M>>,
+    // set by another thread to end the search at the next frame
+    stop: Option<&'a AtomicBool>,
+    // return early with the stack intact once this time passes
+    pause_at: Option<Instant>,
+    stats: SolveStats,
+}
 
-                // all placements here are precomputed to cover target_cell
-                let valid_placements = &placement_table[piece_index][target_cell];
+/// Canonical keys of the states a search has reached.
+///
+/// Keys are stored as [`PackedKey`]s when that takes less room than a byte
+/// per cell: two words for boxes of 17 to 32 cells and four for boxes of
+/// 33 to 64, as long as the pieces leave room. With [`Dedup::Bloom`] only a
+/// filter of them is kept.
+enum SeenStates<const GRID_SIZE: usize> {
+    Packed2(FxHashSet<PackedKey<2>>),
+    Packed4(FxHashSet<PackedKey<4>>),
+    Bytes(FxHashSet<[u8; GRID_SIZE]>),
+    Bloom(BloomFilter),
+}
 
-                while partial.current_orientation_index < valid_placements.len() {
-                    let placement = &valid_placements[partial.current_orientation_index];
-                    partial.current_orientation_index += 1;
+impl<const GRID_SIZE: usize> SeenStates<GRID_SIZE> {
+    /// An empty set of the kind `dedup` asks for, packed when `num_pieces`
+    /// pieces leave room for it and that makes the keys shorter.
+    fn new(num_pieces: usize, dedup: Dedup) -> Self {
+        match dedup {
+            Dedup::Bloom { bits, hashes } => Self::Bloom(BloomFilter::new(bits, hashes)),
+            Dedup::Exact if GRID_SIZE > 16 && PackedKey::<2>::fits(GRID_SIZE, num_pieces) => {
+                Self::Packed2(FxHashSet::default())
+            }
+            Dedup::Exact if GRID_SIZE > 32 && PackedKey::<4>::fits(GRID_SIZE, num_pieces) => {
+                Self::Packed4(FxHashSet::default())
+            }
+            Dedup::Exact | Dedup::ExactBytes => Self::Bytes(FxHashSet::default()),
+        }
+    }
 
-                    // any shared bit means this placement overlaps existing cubes
-                    if (partial.occupied_cells & placement.occupied_mask).is_nonzero() {
-                        continue;
-                    }
+    /// Adds the canonical key of `grid_key`, a grid from
+    /// [`Puzzle::key_grid`], returning whether it was new.
+    ///
+    /// The key is computed in the form the set keeps.
+    #[inline]
+    fn insert_grid<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const NUM_PIECES: usize,
+        const MAX_PIECE_CUBES: usize,
+    >(
+        &mut self,
+        puzzle: &Puzzle<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>,
+        grid_key: &[u8; GRID_SIZE],
+        symmetry: Symmetry,
+    ) -> bool {
+        match self {
+            Self::Packed2(keys) => keys.insert(puzzle.smallest_packed_image(grid_key, symmetry)),
+            Self::Packed4(keys) => keys.insert(puzzle.smallest_packed_image(grid_key, symmetry)),
+            Self::Bytes(keys) => keys.insert(puzzle.smallest_image(grid_key, symmetry)),
+            Self::Bloom(filter) => filter.insert(&puzzle.smallest_image(grid_key, symmetry)),
+        }
+    }
 
-                    let new_occupied = partial.occupied_cells | placement.occupied_mask;
-                    let new_piece = PlacedPiece {
-                        piece_index,
-                        positions: placement.cube_positions,
-                        cube_count: placement.cube_count,
-                    };
+    /// Adds the canonical `key`, returning whether it was new.
+    #[cfg(feature = "native-io")]
+    fn insert(&mut self, key: &[u8; GRID_SIZE]) -> bool {
+        match self {
+            Self::Packed2(keys) => keys.insert(PackedKey::pack(key)),
+            Self::Packed4(keys) => keys.insert(PackedKey::pack(key)),
+            Self::Bytes(keys) => keys.insert(*key),
+            Self::Bloom(filter) => filter.insert(key),
+        }
+    }
 
-                    let mut new_placed = partial.placed_pieces;
-                    new_placed[partial.placed_count] = new_piece;
-                    let new_count = partial.placed_count + 1;
+    #[cfg(feature = "native-io")]
+    fn len(&self) -> usize {
+        match self {
+            Self::Packed2(keys) => keys.len(),
+            Self::Packed4(keys) => keys.len(),
+            Self::Bytes(keys) => keys.len(),
+            Self::Bloom(_) => unreachable!("checkpointed searches dedup exactly"),
+        }
+    }
 
-                    // canonical key merges equivalent states under symmetry
-                    let canonical = self.canonical_key(&new_placed[..new_count]);
-                    if seen_states.contains(&canonical) {
-                        continue;
-                    }
-                    seen_states.insert(canonical);
+    /// The stored keys as byte arrays, in no particular order.
+    #[cfg(feature = "native-io")]
+    fn keys(&self) -> Box<dyn Iterator<Item = [u8; GRID_SIZE]> + '_> {
+        match self {
+            Self::Packed2(keys) => Box::new(keys.iter().map(PackedKey::unpack)),
+            Self::Packed4(keys) => Box::new(keys.iter().map(PackedKey::unpack)),
+            Self::Bytes(keys) => Box::new(keys.iter().copied()),
+            Self::Bloom(_) => unreachable!("checkpointed searches dedup exactly"),
+        }
+    }
+}
 
-                    // clear the bit for the piece we just placed
-                    let new_remaining = partial.remaining_pieces & !(1u32 << piece_index);
+/// A fixed-size set that can wrongly report a key as present but never
+/// forgets one, for [`Dedup::Bloom`].
+struct BloomFilter {
+    words: Vec<u64>,
+    hashes: u32,
+}
 
-                    // push parent first then child so child runs next
-                    search_stack.push(partial);
-                    search_stack.push(PartialSolution {
-                        placed_pieces: new_placed,
-                        placed_count: new_count,
-                        remaining_pieces: new_remaining,
-                        occupied_cells: new_occupied,
-                        current_piece_index: 0,
-                        current_orientation_index: 0,
-                    });
+impl BloomFilter {
+    /// An empty filter of at least `bits` bits (and at least 64), probed by
+    /// at least one hash.
+    fn new(bits: usize, hashes: u32) -> Self {
+        Self {
+            words: vec![0; bits.div_ceil(64).max(1)],
+            hashes: hashes.max(1),
+        }
+    }
 
-                    // branch consumed one placement so descend immediately
-                    break 'pieces;
-                }
+    /// Adds `key`, returning whether it was new, or possibly `false` for a
+    /// new key whose bits other keys happened to set.
+    #[inline]
+    fn insert(&mut self, key: &[u8]) -> bool {
+        // two independent hashes combine into as many probes as needed
+        let mut first = FxHasher::default();
+        key.hash(&mut first);
+        let mut second = FxHasher::default();
+        0x9e37_79b9_7f4a_7c15_u64.hash(&mut second);
+        key.hash(&mut second);
+        let (start, step) = (first.finish(), second.finish() | 1);
 
-                // no placement worked for this piece so try next piece
-                partial.current_piece_index += 1;
-                partial.current_orientation_index = 0;
+        let num_bits = self.words.len() as u64 * 64;
+        let mut new = false;
+        for probe in 0..u64::from(self.hashes) {
+            let bit = start.wrapping_add(probe.wrapping_mul(step)) % num_bits;
+            let (word, mask) = ((bit / 64) as usize, 1u64 << (bit % 64));
+            if self.words[word] & mask == 0 {
+                self.words[word] |= mask;
+                new = true;
             }
         }
+        new
+    }
+}
+
+/// Lazy solution stream returned by [`Puzzle::solve_iter`].
+///
+/// One variant per bitmask width, chosen from `GRID_SIZE` like `solve`.
+enum SolutionIter<
+    'a,
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+    const MAX_PIECE_CUBES: usize,
+> {
+    Narrow(Search<'a, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, u32, MAX_PIECE_CUBES>),
+    Wide(Search<'a, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, u64, MAX_PIECE_CUBES>),
+    Wider(Search<'a, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, u128, MAX_PIECE_CUBES>),
+    Widest(
+        Search<
+            'a,
+            DIM_X,
+            DIM_Y,
+            DIM_Z,
+            GRID_SIZE,
+            NUM_PIECES,
+            WordMask<WIDE_MASK_WORDS>,
+            MAX_PIECE_CUBES,
+        >,
+    ),
+}
+
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+        const MAX_PIECE_CUBES: usize,
+    > Iterator for SolutionIter<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>
+{
+    type Item = Vec<PlacedPiece<MAX_PIECE_CUBES>>;
 
-        solutions
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Narrow(search) => search.next(),
+            Self::Wide(search) => search.next(),
+            Self::Wider(search) => search.next(),
+            Self::Widest(search) => search.next(),
+        }
     }
+}
 
-    fn build_placement_table<M: CellMask>(
-        pieces: &[&[Coord]],
-    ) -> PlacementTable<M> {
-        let piece_orientations: Vec<Vec<Orientation>> =
-            pieces.iter().map(|piece| all_orientations(piece)).collect();
+/// A puzzle's placement table at the bitmask width `solve` picks for it.
+enum PlacementTables<const MAX_PIECE_CUBES: usize> {
+    Narrow(PlacementTable<u32, MAX_PIECE_CUBES>),
+    Wide(PlacementTable<u64, MAX_PIECE_CUBES>),
+    Wider(PlacementTable<u128, MAX_PIECE_CUBES>),
+    Widest(PlacementTable<WordMask<WIDE_MASK_WORDS>, MAX_PIECE_CUBES>),
+}
 
-        piece_orientations
+impl<const MAX_PIECE_CUBES: usize> PlacementTables<MAX_PIECE_CUBES> {
+    fn stats(&self) -> TableStats {
+        match self {
+            Self::Narrow(table) => table_stats(table),
+            Self::Wide(table) => table_stats(table),
+            Self::Wider(table) => table_stats(table),
+            Self::Widest(table) => table_stats(table),
+        }
+    }
+}
+
+/// Counts the placements in `placement_table` and the memory they use.
+fn table_stats<M: CellMask, const MAX_PIECE_CUBES: usize>(
+    placement_table: &PlacementTable<M, MAX_PIECE_CUBES>,
+) -> TableStats {
+    let entry_size = std::mem::size_of::<Placement<M, MAX_PIECE_CUBES>>();
+    let list_size = std::mem::size_of::<Vec<()>>();
+    let mut stats = TableStats {
+        placements_per_piece: Vec::with_capacity(placement_table.len()),
+        total_placements: 0,
+        table_entries: 0,
+        approximate_bytes: placement_table.capacity() * list_size,
+    };
+    for cells in placement_table {
+        let entries: usize = cells.iter().map(Vec::len).sum();
+        // every placement of a piece covers the same number of cells
+        let cubes = cells
             .iter()
-            .map(|orientations| {
-                (0..GRID_SIZE)
-                    .map(|target_cell| {
-                        let target_position = idx_to_coord::<DIM>(target_cell);
-                        let mut placements = Vec::new();
-
-                        for orientation in orientations {
-                            // try each cube in the orientation as the anchor on target_position
-                            for &anchor in orientation {
-                                if let Some(placement) =
-                                    Self::try_create_placement(orientation, target_position, anchor)
-                                {
-                                    placements.push(placement);
-                                }
-                            }
-                        }
+            .flatten()
+            .next()
+            .map_or(1, |placement| placement.cube_count as usize);
+        stats.placements_per_piece.push(entries / cubes);
+        stats.total_placements += entries / cubes;
+        stats.table_entries += entries;
+        stats.approximate_bytes += cells.capacity() * list_size
+            + cells.iter().map(Vec::capacity).sum::<usize>() * entry_size;
+    }
+    stats
+}
 
-                        placements
-                    })
-                    .collect()
-            })
-            .collect()
+/// A puzzle with its placement table built once, returned by
+/// [`Puzzle::prepare`].
+///
+/// Every solve on a [`Puzzle`] starts by working out where each piece can
+/// go, which is most of the cost of a short search such as `solve(Some(1))`.
+/// Solving through a prepared puzzle skips that step, so repeated calls to
+/// [`solve_random`](Self::solve_random) or
+/// [`solutions_containing`](Self::solutions_containing) only pay for the
+/// search. Results match the same calls on the puzzle itself.
+pub struct PreparedPuzzle<
+    'a,
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+    const NUM_PIECES: usize,
+    const MAX_PIECE_CUBES: usize = MAX_CUBES,
+> {
+    puzzle: &'a Puzzle<'a, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>,
+    tables: PlacementTables<MAX_PIECE_CUBES>,
+}
+
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+        const MAX_PIECE_CUBES: usize,
+    > PreparedPuzzle<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>
+{
+    /// [`Puzzle::solve`] with the prepared placement table.
+    pub fn solve(&self, max_solutions: Option<usize>) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_with_fixed(&[], max_solutions)
     }
 
-    fn try_create_placement<M: CellMask>(
-        orientation: &Orientation,
-        target: Coord,
-        anchor: Coord,
-    ) -> Option<Placement<M>> {
-        let mut occupied_mask = M::zero();
-        let mut cube_positions = [(0, 0, 0); MAX_CUBES];
-        // shift orientation so anchor lands on target
-        let offset = (
-            target.0 - anchor.0,
-            target.1 - anchor.1,
-            target.2 - anchor.2,
-        );
-        let dim = DIM as i32;
+    /// [`Puzzle::solve_with_fixed`] with the prepared placement table.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same `fixed` pieces as `Puzzle::solve_with_fixed`.
+    pub fn solve_with_fixed(
+        &self,
+        fixed: &[PlacedPiece<MAX_PIECE_CUBES>],
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.puzzle
+            .solve_with_tables(
+                &self.tables,
+                fixed,
+                max_solutions,
+                SolveOptions::default(),
+                |_| {},
+            )
+            .0
+    }
 
-        for (cube_index, &(piece_x, piece_y, piece_z)) in orientation.iter().enumerate() {
-            let absolute_x = piece_x + offset.0;
-            let absolute_y = piece_y + offset.1;
-            let absolute_z = piece_z + offset.2;
+    /// [`Puzzle::solutions_containing`] with the prepared placement table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pinned` has an unknown index or leaves the box.
+    pub fn solutions_containing(
+        &self,
+        pinned: PlacedPiece<MAX_PIECE_CUBES>,
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_with_fixed(&[pinned], max_solutions)
+    }
 
-            // reject placements that leave cube bounds
-            if !(0..dim).contains(&absolute_x)
-                || !(0..dim).contains(&absolute_y)
-                || !(0..dim).contains(&absolute_z)
-            {
-                return None;
+    /// [`Puzzle::solve_random`] with the prepared placement table.
+    pub fn solve_random(&self, seed: u64) -> Option<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.puzzle.solve_random_with_tables(&self.tables, seed)
+    }
+
+    /// [`Puzzle::legal_placements`] with the prepared placement table.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the box has more than 64 cells.
+    pub fn legal_placements(
+        &self,
+        occupied: u64,
+        remaining: u32,
+    ) -> Vec<PlacedPiece<MAX_PIECE_CUBES>> {
+        match &self.tables {
+            PlacementTables::Narrow(table) => {
+                self.puzzle.legal_placements_in(table, occupied, remaining)
+            }
+            PlacementTables::Wide(table) => {
+                self.puzzle.legal_placements_in(table, occupied, remaining)
             }
+            PlacementTables::Wider(_) | PlacementTables::Widest(_) => {
+                panic!("legal_placements needs a box of at most 64 cells")
+            }
+        }
+    }
 
-            occupied_mask = occupied_mask | M::bit(coord_to_idx::<DIM>(absolute_x, absolute_y, absolute_z));
-            cube_positions[cube_index] = (absolute_x, absolute_y, absolute_z);
+    /// [`Puzzle::placement_table_stats`] for the prepared placement table.
+    pub fn placement_table_stats(&self) -> TableStats {
+        self.tables.stats()
+    }
+
+    /// Gives piece `index` a new shape, rebuilding only that piece's part of
+    /// the placement table.
+    ///
+    /// Later solves match those of a freshly prepared puzzle with the new
+    /// piece in place, for the cost of one piece's placements instead of all
+    /// of them. The borrowed [`Puzzle`] itself keeps its old pieces.
+    ///
+    /// The new piece must be one connected polycube that fits in the box and
+    /// has as many cubes as the piece it replaces, so the pieces still fill
+    /// the box. Copies of a repeated shape and the chiral pair are tied to
+    /// other pieces, so they report [`PuzzleError::LinkedPiece`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not a piece index of the puzzle.
+    pub fn replace_piece(&mut self, index: usize, new_piece: &[Coord]) -> Result<(), PuzzleError> {
+        assert!(index < NUM_PIECES, "piece index {index} out of range");
+        if new_piece.is_empty() {
+            return Err(PuzzleError::EmptyPiece(index));
+        }
+        let old_cubes = self.puzzle.piece_slots()[index].len();
+        if new_piece.len() != old_cubes {
+            let box_cells = GRID_SIZE - self.puzzle.blocked_cells.len();
+            return Err(PuzzleError::VolumeMismatch {
+                piece_cells: box_cells - old_cubes + new_piece.len(),
+                box_cells,
+            });
+        }
+        if !is_connected(new_piece) {
+            return Err(PuzzleError::Disconnected(index));
+        }
+        // bit `index` is set on a later copy, bit `index + 1` when one follows
+        let copied = (self.puzzle.later_copies() >> index) & 0b11 != 0;
+        let chiral = self
+            .puzzle
+            .chiral_pair
+            .is_some_and(|(a, b)| index == a || index == b);
+        if copied || chiral {
+            return Err(PuzzleError::LinkedPiece(index));
         }
 
-        Some(Placement {
-            occupied_mask,
-            cube_positions,
-            cube_count: orientation.len() as u8,
-        })
+        match &mut self.tables {
+            PlacementTables::Narrow(table) => {
+                self.puzzle.replace_placements(table, index, new_piece)
+            }
+            PlacementTables::Wide(table) => self.puzzle.replace_placements(table, index, new_piece),
+            PlacementTables::Wider(table) => {
+                self.puzzle.replace_placements(table, index, new_piece)
+            }
+            PlacementTables::Widest(table) => {
+                self.puzzle.replace_placements(table, index, new_piece)
+            }
+        }
     }
+}
 
-    #[inline(always)]
-    fn find_first_empty_cell<M: CellMask>(occupied: M) -> Option<usize> {
-        if occupied == M::all_filled(GRID_SIZE) {
-            None
-        } else {
-            // with filled cells as ones trailing ones reaches the first empty bit
-            Some(occupied.trailing_ones())
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+        const MAX_PIECE_CUBES: usize,
+    > Puzzle<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>
+{
+    /// Builds the placement table once for a series of solves.
+    pub fn prepare(
+        &self,
+    ) -> PreparedPuzzle<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES> {
+        PreparedPuzzle {
+            puzzle: self,
+            tables: self.placement_tables(),
         }
     }
+
+    /// Finds unique solutions, up to an optional limit.
+    ///
+    /// The placements covering the first empty cell are searched in parallel,
+    /// each on its own rayon task with a private dedup set. Workers may
+    /// rediscover symmetric copies of each other's solutions, so the merged
+    /// results are deduplicated by canonical key in branch order, which keeps
+    /// the output deterministic.
+    ///
+    /// With a limit the result is the first `max_solutions` of that merged
+    /// order, the same on every run. A branch only stops early once the
+    /// branches before it are sure to supply the whole limit.
+    ///
+    /// Automatically selects the smallest adequate bitmask: `u32` for grids up
+    /// to 32 cells, `u64` up to 64, `u128` up to 128, and a `u64` array beyond.
+    ///
+    /// `Some(0)` asks for no solutions, so it returns an empty list at once
+    /// without building the placement table or searching.
+    pub fn solve(&self, max_solutions: Option<usize>) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_with_stats(max_solutions).0
+    }
+
+    /// Finds every unique solution, each as its
+    /// [`canonical_solution`](Self::canonical_solution).
+    ///
+    /// The representatives depend only on the solutions, not on the path the
+    /// search took to them, so any two runs give identical output.
+    pub fn solve_canonical(&self) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve(None)
+            .iter()
+            .map(|solution| self.canonical_solution(solution))
+            .collect()
+    }
+
+    /// Like [`solve`](Self::solve), but also reports search counters and timing.
+    pub fn solve_with_stats(
+        &self,
+        max_solutions: Option<usize>,
+    ) -> (Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>>, SolveStats) {
+        self.solve_with_progress(max_solutions, |_| {})
+    }
+
+    /// Like [`solve_with_stats`](Self::solve_with_stats), but calls
+    /// `on_solution` with the running count each time a new unique solution
+    /// is found.
+    ///
+    /// The callback may run on any worker thread, but never concurrently with
+    /// itself, and the counts it sees increase by one each call. With a limit,
+    /// the solution that reaches it is still reported.
+    pub fn solve_with_progress<F: FnMut(usize) + Send>(
+        &self,
+        max_solutions: Option<usize>,
+        on_solution: F,
+    ) -> (Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>>, SolveStats) {
+        self.solve_from(&[], max_solutions, SolveOptions::default(), on_solution)
+    }
+
+    /// Like [`solve_with_stats`](Self::solve_with_stats), with the search
+    /// tuned by `options`.
+    ///
+    /// Every choice of options finds the same solutions up to symmetry, though
+    /// the order and the representative of each may differ. The exception is
+    /// [`Dedup::Bloom`], which may miss some.
+    pub fn solve_with_options(
+        &self,
+        max_solutions: Option<usize>,
+        options: SolveOptions,
+    ) -> (Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>>, SolveStats) {
+        self.solve_from(&[], max_solutions, options, |_| {})
+    }
+
+    /// Builds the placement table `solve` would use and reports its size;
+    /// see [`TableStats`].
+    ///
+    /// The table grows with the box and the pieces' orientations, so this
+    /// shows what a big custom puzzle will cost in memory before any search
+    /// runs. The table is dropped again afterwards; use
+    /// [`prepare`](Self::prepare) to keep it.
+    pub fn placement_table_stats(&self) -> TableStats {
+        self.placement_tables().stats()
+    }
+
+    /// Rates how hard the puzzle is; see [`DifficultyMetrics`].
+    ///
+    /// Runs a plain search to the first solution and a deduplicated count of
+    /// up to `max_solutions` solutions. A full count of Bedlam takes
+    /// minutes, so pass a limit, or `Some(0)` to skip counting, when only
+    /// the search metrics matter.
+    pub fn difficulty(&self, max_solutions: Option<usize>) -> DifficultyMetrics {
+        if GRID_SIZE <= 32 {
+            self.difficulty_with_mask::<u32>(max_solutions)
+        } else if GRID_SIZE <= 64 {
+            self.difficulty_with_mask::<u64>(max_solutions)
+        } else if GRID_SIZE <= 128 {
+            self.difficulty_with_mask::<u128>(max_solutions)
+        } else {
+            self.difficulty_with_mask::<WordMask<WIDE_MASK_WORDS>>(max_solutions)
+        }
+    }
+
+    fn difficulty_with_mask<M: CellMask>(&self, max_solutions: Option<usize>) -> DifficultyMetrics {
+        let placement_table = Self::build_placement_table::<M>(&self.piece_slots());
+        let roots = self.expand_first_level(
+            &placement_table,
+            &SolveOptions::default(),
+            &Vec::new(),
+            self.initial_state(),
+            &mut SolveStats::default(),
+        );
+
+        // without dedup, which finds no repeats before the first solution anyway
+        let mut search = Search::new(self, Cow::Borrowed(&placement_table), self.initial_state());
+        search.dedup = false;
+        search.next();
+        let nodes_to_first_solution = search.stats.stack_pushes;
+
+        let mut search = Search::new(self, Cow::Borrowed(&placement_table), self.initial_state());
+        let solutions = match max_solutions {
+            Some(max) => search.by_ref().take(max).count(),
+            None => search.count(),
+        };
+
+        let slots = self.piece_slots();
+        let orientations: usize = slots
+            .iter()
+            .map(|piece| cached_orientations(piece).len())
+            .sum();
+        DifficultyMetrics {
+            nodes_to_first_solution,
+            solutions,
+            root_branching: roots.iter().filter(|root| root.placed_count > 0).count(),
+            average_orientations: orientations as f64 / slots.len() as f64,
+        }
+    }
+
+    /// Counts the unique solutions below each first-level branch of the
+    /// parallel search.
+    ///
+    /// Returns the placement each branch starts with, in branch order, and
+    /// how many of [`solve`](Self::solve)'s solutions come from it. Each
+    /// branch runs on its own rayon task and collects canonical keys of its
+    /// own; a rotated or mirrored copy of a solution can cover the first
+    /// cell with a different piece, so the keys are merged afterwards and a
+    /// solution reachable from several branches counts toward the first, as
+    /// in `solve`. The counts therefore add up to `solve(None).len()`. Branches
+    /// that lead nowhere are listed with a count of zero, which shows how
+    /// evenly the parallel search splits its work.
+    pub fn solution_histogram(&self) -> Vec<(PlacedPiece<MAX_PIECE_CUBES>, usize)> {
+        if GRID_SIZE <= 32 {
+            self.solution_histogram_with_mask::<u32>()
+        } else if GRID_SIZE <= 64 {
+            self.solution_histogram_with_mask::<u64>()
+        } else if GRID_SIZE <= 128 {
+            self.solution_histogram_with_mask::<u128>()
+        } else {
+            self.solution_histogram_with_mask::<WordMask<WIDE_MASK_WORDS>>()
+        }
+    }
+
+    fn solution_histogram_with_mask<M: CellMask>(
+        &self,
+    ) -> Vec<(PlacedPiece<MAX_PIECE_CUBES>, usize)> {
+        let placement_table = Self::build_placement_table::<M>(&self.piece_slots());
+        let roots = self.expand_first_level(
+            &placement_table,
+            &SolveOptions::default(),
+            &Vec::new(),
+            self.initial_state(),
+            &mut SolveStats::default(),
+        );
+
+        let branch_keys: Vec<(PlacedPiece<MAX_PIECE_CUBES>, Vec<[u8; GRID_SIZE]>)> = roots
+            .into_par_iter()
+            // a box with nothing left to place has no branches
+            .filter(|root| root.placed_count > 0)
+            .map(|root| {
+                let first = root.placed_pieces[0];
+                let search = Search::new(self, Cow::Borrowed(&placement_table), root);
+                let keys = search
+                    .map(|solution| self.canonical_key(&solution))
+                    .collect();
+                (first, keys)
+            })
+            .collect();
+
+        // count each solution toward the first branch that reaches it
+        let mut merged_keys: FxHashSet<[u8; GRID_SIZE]> = FxHashSet::default();
+        branch_keys
+            .into_iter()
+            .map(|(first, keys)| {
+                let count = keys
+                    .into_iter()
+                    .filter(|key| merged_keys.insert(*key))
+                    .count();
+                (first, count)
+            })
+            .collect()
+    }
+
+    /// Lists every way to place one of the `remaining` pieces without
+    /// covering an `occupied` or blocked cell.
+    ///
+    /// Bit `i` of `occupied` stands for cell `i` in
+    /// [`coord_to_idx`](crate::grid::coord_to_idx) order, and bit `i` of
+    /// `remaining` for piece `i`. Placements come grouped by piece, then by
+    /// their lowest cell. Only legality is checked, not whether the rest of
+    /// the pieces still fit; see [`is_completable`](Self::is_completable).
+    /// Builds the placement table on every call, so an interactive caller
+    /// should [`prepare`](Self::prepare) the puzzle once instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the box has more than 64 cells.
+    pub fn legal_placements(
+        &self,
+        occupied: u64,
+        remaining: u32,
+    ) -> Vec<PlacedPiece<MAX_PIECE_CUBES>> {
+        self.prepare().legal_placements(occupied, remaining)
+    }
+
+    fn legal_placements_in<M: CellMask + Into<u64>>(
+        &self,
+        placement_table: &PlacementTable<M, MAX_PIECE_CUBES>,
+        occupied: u64,
+        remaining: u32,
+    ) -> Vec<PlacedPiece<MAX_PIECE_CUBES>> {
+        let occupied = self
+            .blocked_cells
+            .iter()
+            .fold(occupied, |mask, &cell| mask | 1 << cell);
+        let mut legal = Vec::new();
+        for (piece_index, piece_placements) in placement_table.iter().enumerate() {
+            if remaining & (1 << piece_index) == 0 {
+                continue;
+            }
+            for (cell, placements) in piece_placements.iter().enumerate() {
+                for placement in placements {
+                    // each placement is listed under every cell it covers
+                    let mask: u64 = placement.occupied_mask.into();
+                    if mask.trailing_zeros() as usize == cell && mask & occupied == 0 {
+                        legal.push(PlacedPiece {
+                            piece_index,
+                            positions: placement.cube_positions,
+                            cube_count: placement.cube_count,
+                        });
+                    }
+                }
+            }
+        }
+        legal
+    }
+
+    /// Counts the nodes of a depth-first search that always fills the first
+    /// empty cell in `order`, for comparing cell orderings.
+    ///
+    /// Every placement of a piece into a state counts as one node. The search
+    /// has no symmetry reduction, so it walks every fill of the box, and
+    /// orderings that force choices early show up as fewer nodes. The
+    /// identity order is how [`solve`](Self::solve) picks cells.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `order` is a permutation of `0..GRID_SIZE`.
+    pub fn evaluate_cell_order(&self, order: &[usize]) -> usize {
+        let mut listed = [false; GRID_SIZE];
+        for &cell in order {
+            assert!(
+                cell < GRID_SIZE && !std::mem::replace(&mut listed[cell], true),
+                "cell order must be a permutation of 0..GRID_SIZE"
+            );
+        }
+        assert!(
+            order.len() == GRID_SIZE,
+            "cell order must be a permutation of 0..GRID_SIZE"
+        );
+
+        match &self.placement_tables() {
+            PlacementTables::Narrow(table) => self.count_nodes(table, order, self.initial_state()),
+            PlacementTables::Wide(table) => self.count_nodes(table, order, self.initial_state()),
+            PlacementTables::Wider(table) => self.count_nodes(table, order, self.initial_state()),
+            PlacementTables::Widest(table) => self.count_nodes(table, order, self.initial_state()),
+        }
+    }
+
+    /// Counts the states below `partial` when cells are filled in `order`.
+    ///
+    /// Recursion is bounded by the piece count, as in `random_completion`.
+    fn count_nodes<M: CellMask>(
+        &self,
+        placement_table: &PlacementTable<M, MAX_PIECE_CUBES>,
+        order: &[usize],
+        partial: PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>,
+    ) -> usize {
+        let Some(target_cell) = Self::first_empty_in_order(order, partial.occupied_cells) else {
+            return 0;
+        };
+
+        let available = Self::available_pieces(partial.remaining_pieces, self.later_copies());
+        let mut nodes = 0;
+        for (piece_index, piece_placements) in placement_table.iter().enumerate() {
+            if (available & (1u32 << piece_index)) == 0 {
+                continue;
+            }
+            for placement in &piece_placements[target_cell] {
+                if (partial.occupied_cells & placement.occupied_mask).is_nonzero() {
+                    continue;
+                }
+                let child = Self::place(&partial, piece_index, placement);
+                nodes += 1 + self.count_nodes(placement_table, order, child);
+            }
+        }
+        nodes
+    }
+
+    /// Finds unique solutions that keep `fixed` pieces where they are.
+    ///
+    /// The fixed pieces are placed before the search starts and are included
+    /// in every returned solution, so each one is a complete fill.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a fixed piece has an unknown index, appears twice, leaves
+    /// the box, or overlaps another fixed piece.
+    pub fn solve_with_fixed(
+        &self,
+        fixed: &[PlacedPiece<MAX_PIECE_CUBES>],
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_from(fixed, max_solutions, SolveOptions::default(), |_| {}).0
+    }
+
+    /// Finds unique solutions in which `pinned` sits exactly where it is,
+    /// up to an optional limit.
+    ///
+    /// A one-piece [`solve_with_fixed`](Self::solve_with_fixed), for hints
+    /// such as "what fits around this piece here?".
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pinned` has an unknown index or leaves the box.
+    pub fn solutions_containing(
+        &self,
+        pinned: PlacedPiece<MAX_PIECE_CUBES>,
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_with_fixed(&[pinned], max_solutions)
+    }
+
+    /// Whether the `placed` pieces can be extended to a complete solution.
+    ///
+    /// Seeds the search the way [`solve_with_fixed`](Self::solve_with_fixed)
+    /// does and stops at the first completion. Input that could never be
+    /// part of a solution (an unknown or repeated piece index, a cube outside
+    /// the box, overlapping pieces) gives `false` instead of a panic.
+    pub fn is_completable(&self, placed: &[PlacedPiece<MAX_PIECE_CUBES>]) -> bool {
+        if GRID_SIZE <= 32 {
+            self.is_completable_with_mask::<u32>(placed)
+        } else if GRID_SIZE <= 64 {
+            self.is_completable_with_mask::<u64>(placed)
+        } else if GRID_SIZE <= 128 {
+            self.is_completable_with_mask::<u128>(placed)
+        } else {
+            self.is_completable_with_mask::<WordMask<WIDE_MASK_WORDS>>(placed)
+        }
+    }
+
+    fn is_completable_with_mask<M: CellMask>(
+        &self,
+        placed: &[PlacedPiece<MAX_PIECE_CUBES>],
+    ) -> bool {
+        let Ok(start) = self.try_seeded_state::<M>(placed) else {
+            return false;
+        };
+        let placement_table = Self::build_placement_table::<M>(&self.piece_slots());
+        Search::new(self, Cow::Owned(placement_table), start)
+            .next()
+            .is_some()
+    }
+
+    /// Selects the bitmask width and runs the parallel search from `fixed`.
+    fn solve_from<F: FnMut(usize) + Send>(
+        &self,
+        fixed: &[PlacedPiece<MAX_PIECE_CUBES>],
+        max_solutions: Option<usize>,
+        options: SolveOptions,
+        on_solution: F,
+    ) -> (Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>>, SolveStats) {
+        if max_solutions == Some(0) {
+            return (Vec::new(), SolveStats::default());
+        }
+        let start = Instant::now();
+        let tables = self.placement_tables();
+        let (solutions, mut stats) =
+            self.solve_with_tables(&tables, fixed, max_solutions, options, on_solution);
+        // count the table build too
+        stats.elapsed = start.elapsed();
+        (solutions, stats)
+    }
+
+    /// Builds the placement table at the bitmask width this puzzle needs:
+    /// `u32` for grids up to 32 cells, `u64` up to 64, `u128` up to 128, and
+    /// a `u64` array beyond.
+    fn placement_tables(&self) -> PlacementTables<MAX_PIECE_CUBES> {
+        let slots = self.piece_slots();
+        if GRID_SIZE <= 32 {
+            PlacementTables::Narrow(Self::build_placement_table(&slots))
+        } else if GRID_SIZE <= 64 {
+            PlacementTables::Wide(Self::build_placement_table(&slots))
+        } else if GRID_SIZE <= 128 {
+            PlacementTables::Wider(Self::build_placement_table(&slots))
+        } else {
+            PlacementTables::Widest(Self::build_placement_table(&slots))
+        }
+    }
+
+    /// Runs the parallel search from `fixed` over already built tables.
+    fn solve_with_tables<F: FnMut(usize) + Send>(
+        &self,
+        tables: &PlacementTables<MAX_PIECE_CUBES>,
+        fixed: &[PlacedPiece<MAX_PIECE_CUBES>],
+        max_solutions: Option<usize>,
+        options: SolveOptions,
+        on_solution: F,
+    ) -> (Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>>, SolveStats) {
+        if max_solutions == Some(0) {
+            return (Vec::new(), SolveStats::default());
+        }
+        let start = Instant::now();
+        let (mut solutions, mut stats) = match tables {
+            PlacementTables::Narrow(table) => {
+                self.solve_parallel_with_mask(table, fixed, max_solutions, &options, on_solution)
+            }
+            PlacementTables::Wide(table) => {
+                self.solve_parallel_with_mask(table, fixed, max_solutions, &options, on_solution)
+            }
+            PlacementTables::Wider(table) => {
+                self.solve_parallel_with_mask(table, fixed, max_solutions, &options, on_solution)
+            }
+            PlacementTables::Widest(table) => {
+                self.solve_parallel_with_mask(table, fixed, max_solutions, &options, on_solution)
+            }
+        };
+        if options.sort_pieces {
+            for solution in &mut solutions {
+                solution.sort_by_key(|placed| placed.piece_index);
+            }
+        }
+        stats.elapsed = start.elapsed();
+        (solutions, stats)
+    }
+
+    /// Finds unique solutions on the current thread, up to an optional limit.
+    ///
+    /// Kept alongside the parallel [`solve`](Self::solve) so benchmarks can
+    /// compare the two.
+    pub fn solve_sequential(
+        &self,
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_sequential_with_dedup(max_solutions, true)
+    }
+
+    /// Finds every solution, including rotations and reflections of each
+    /// other, up to an optional limit.
+    ///
+    /// Runs the sequential search with symmetry reduction turned off, so each
+    /// distinct fill of the box is returned once. Useful for checking the
+    /// canonicalization: for a puzzle whose box symmetries act freely on its
+    /// solutions, the total is `solve().len()` times the symmetry count.
+    pub fn solve_all(
+        &self,
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_sequential_with_dedup(max_solutions, false)
+    }
+
+    fn solve_sequential_with_dedup(
+        &self,
+        max_solutions: Option<usize>,
+        dedup: bool,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        if max_solutions == Some(0) {
+            Vec::new()
+        } else if GRID_SIZE <= 32 {
+            self.solve_with_mask::<u32>(max_solutions, dedup)
+        } else if GRID_SIZE <= 64 {
+            self.solve_with_mask::<u64>(max_solutions, dedup)
+        } else if GRID_SIZE <= 128 {
+            self.solve_with_mask::<u128>(max_solutions, dedup)
+        } else {
+            self.solve_with_mask::<WordMask<WIDE_MASK_WORDS>>(max_solutions, dedup)
+        }
+    }
+
+    /// Counts unique solutions without keeping any of them.
+    ///
+    /// Runs the sequential search and drops each solution as soon as it is
+    /// found, so memory stays bounded by the search's dedup set rather than
+    /// the full solution list. The result matches `solve(None).len()`.
+    pub fn count_live(&self) -> usize {
+        self.solve_iter().count()
+    }
+
+    /// Counts unique solutions, stopping once `cap` are found.
+    ///
+    /// Cheaper than a full count when only "none", "one" or "several"
+    /// matters, since the search ends at the cap.
+    pub fn solution_multiplicity(&self, cap: usize) -> usize {
+        self.solve_iter().take(cap).count()
+    }
+
+    /// Returns the first complete fill the search reaches, or `None` if the
+    /// puzzle has none.
+    ///
+    /// Runs the sequential search on the current thread without symmetry
+    /// reduction, so no canonical keys are computed or stored along the way.
+    /// That makes it the quickest route to one solution when which one does
+    /// not matter; [`solve`](Self::solve) with a limit of one also pays for
+    /// deduplication and the parallel split.
+    pub fn any_solution(&self) -> Option<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_all(Some(1)).pop()
+    }
+
+    /// Whether the puzzle has exactly one solution up to symmetry.
+    ///
+    /// Stops searching as soon as a second solution turns up.
+    pub fn has_unique_solution(&self) -> bool {
+        self.solution_multiplicity(2) == 1
+    }
+
+    /// Lazily yields unique solutions in the same order as `solve_sequential`.
+    ///
+    /// The iterator owns the search state and only advances the search as far
+    /// as the next complete fill, so stopping early skips the remaining work.
+    pub fn solve_iter(&self) -> impl Iterator<Item = Vec<PlacedPiece<MAX_PIECE_CUBES>>> + '_ {
+        if GRID_SIZE <= 32 {
+            SolutionIter::Narrow(self.search_from_start::<u32>())
+        } else if GRID_SIZE <= 64 {
+            SolutionIter::Wide(self.search_from_start::<u64>())
+        } else if GRID_SIZE <= 128 {
+            SolutionIter::Wider(self.search_from_start::<u128>())
+        } else {
+            SolutionIter::Widest(self.search_from_start::<WordMask<WIDE_MASK_WORDS>>())
+        }
+    }
+
+    /// Finds unique solutions that satisfy `predicate`, up to an optional
+    /// limit on the matching ones.
+    ///
+    /// Each complete solution from [`solve_iter`](Self::solve_iter) is tested
+    /// before it counts toward `max_solutions`, so the search runs until that
+    /// many solutions match. The predicate sees the one representative the
+    /// search keeps for each solution, so a predicate that a rotation or
+    /// reflection can change, such as which piece covers a given cell, may
+    /// reject a solution whose symmetric copy would have matched.
+    pub fn solve_filtered<F: Fn(&[PlacedPiece<MAX_PIECE_CUBES>]) -> bool>(
+        &self,
+        predicate: F,
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        let matching = self.solve_iter().filter(|solution| predicate(solution));
+        match max_solutions {
+            Some(max) => matching.take(max).collect(),
+            None => matching.collect(),
+        }
+    }
+
+    /// Finds unique ways to pack every piece into the box, up to an optional
+    /// limit, leaving the cells the pieces do not need empty.
+    ///
+    /// Meant for puzzles built with [`Puzzle::new_partial`], whose pieces
+    /// have fewer cubes than the box has cells, so `solve` finds no fill.
+    /// Here the search may leave a cell empty instead of covering it, as
+    /// long as no more cells end up empty than the box has to spare. Empty
+    /// cells read as 0 in [`canonical_key`](Self::canonical_key), so packs
+    /// that are rotations or reflections of each other count once. For a
+    /// puzzle that fills its box this finds what `solve_sequential` does.
+    pub fn solve_partial(
+        &self,
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        if max_solutions == Some(0) {
+            Vec::new()
+        } else if GRID_SIZE <= 32 {
+            self.solve_partial_with_mask::<u32>(max_solutions)
+        } else if GRID_SIZE <= 64 {
+            self.solve_partial_with_mask::<u64>(max_solutions)
+        } else if GRID_SIZE <= 128 {
+            self.solve_partial_with_mask::<u128>(max_solutions)
+        } else {
+            self.solve_partial_with_mask::<WordMask<WIDE_MASK_WORDS>>(max_solutions)
+        }
+    }
+
+    fn solve_partial_with_mask<M: CellMask>(
+        &self,
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        let piece_cubes: usize = self.piece_slots().iter().map(|piece| piece.len()).sum();
+        let mut search = self.search_from_start::<M>();
+        search.spare_cells = GRID_SIZE - self.blocked_cells.len() - piece_cubes;
+        match max_solutions {
+            Some(max) => search.take(max).collect(),
+            None => search.collect(),
+        }
+    }
+
+    /// Finds one solution chosen at random, or `None` if the puzzle has none.
+    ///
+    /// Runs the depth-first search without deduplication, trying the
+    /// placements at each step in an order shuffled by a generator seeded
+    /// from `seed`, and stops at the first complete fill. The same seed gives
+    /// the same solution.
+    pub fn solve_random(&self, seed: u64) -> Option<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        self.solve_random_with_tables(&self.placement_tables(), seed)
+    }
+
+    fn solve_random_with_tables(
+        &self,
+        tables: &PlacementTables<MAX_PIECE_CUBES>,
+        seed: u64,
+    ) -> Option<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        match tables {
+            PlacementTables::Narrow(table) => {
+                self.random_completion(table, self.initial_state(), &mut rng)
+            }
+            PlacementTables::Wide(table) => {
+                self.random_completion(table, self.initial_state(), &mut rng)
+            }
+            PlacementTables::Wider(table) => {
+                self.random_completion(table, self.initial_state(), &mut rng)
+            }
+            PlacementTables::Widest(table) => {
+                self.random_completion(table, self.initial_state(), &mut rng)
+            }
+        }
+    }
+
+    /// Extends `partial` to a full solution, trying children in random order.
+    ///
+    /// Recursion is bounded by the piece count, so unlike [`Search`] this
+    /// keeps its state on the call stack.
+    fn random_completion<M: CellMask>(
+        &self,
+        placement_table: &PlacementTable<M, MAX_PIECE_CUBES>,
+        partial: PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>,
+        rng: &mut ChaCha8Rng,
+    ) -> Option<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        let Some(target_cell) = Self::choose_cell(
+            CellChoice::FirstEmpty,
+            &Vec::new(),
+            partial.occupied_cells,
+            partial.remaining_pieces,
+        ) else {
+            return Some(partial.placed_pieces[..partial.placed_count].to_vec());
+        };
+
+        let available = Self::available_pieces(partial.remaining_pieces, self.later_copies());
+        let mut children: Vec<(usize, &Placement<M, MAX_PIECE_CUBES>)> = placement_table
+            .iter()
+            .enumerate()
+            .filter(|&(piece_index, _)| (available & (1u32 << piece_index)) != 0)
+            .flat_map(|(piece_index, piece_placements)| {
+                piece_placements[target_cell]
+                    .iter()
+                    .map(move |placement| (piece_index, placement))
+            })
+            .filter(|(_, placement)| {
+                !(partial.occupied_cells & placement.occupied_mask).is_nonzero()
+            })
+            .collect();
+
+        // Fisher-Yates, drawing each index from the seeded generator
+        for i in (1..children.len()).rev() {
+            let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+            children.swap(i, j);
+        }
+
+        children.into_iter().find_map(|(piece_index, placement)| {
+            let child = Self::place(&partial, piece_index, placement);
+            self.random_completion(placement_table, child, rng)
+        })
+    }
+
+    fn solve_with_mask<M: CellMask>(
+        &self,
+        max_solutions: Option<usize>,
+        dedup: bool,
+    ) -> Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> {
+        let mut search = self.search_from_start::<M>();
+        search.dedup = dedup;
+        match max_solutions {
+            Some(max) => search.take(max).collect(),
+            None => search.collect(),
+        }
+    }
+
+    /// A search over the whole puzzle that owns its placement table.
+    fn search_from_start<M: CellMask>(
+        &self,
+    ) -> Search<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, M, MAX_PIECE_CUBES> {
+        let placement_table = Self::build_placement_table::<M>(&self.piece_slots());
+        Search::new(self, Cow::Owned(placement_table), self.initial_state())
+    }
+
+    fn solve_parallel_with_mask<M: CellMask, F: FnMut(usize) + Send>(
+        &self,
+        placement_table: &PlacementTable<M, MAX_PIECE_CUBES>,
+        fixed: &[PlacedPiece<MAX_PIECE_CUBES>],
+        max_solutions: Option<usize>,
+        options: &SolveOptions,
+        on_solution: F,
+    ) -> (Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>>, SolveStats) {
+        let cell_placements = if options.needs_cell_placements() {
+            Self::build_cell_placements(placement_table)
+        } else {
+            Vec::new()
+        };
+        let piece_order = self.piece_scan_order(&options.piece_order);
+        let mut stats = SolveStats::default();
+        let start = self.seeded_state(fixed);
+        let roots = self.expand_first_level(
+            placement_table,
+            options,
+            &cell_placements,
+            start,
+            &mut stats,
+        );
+
+        let limit = SharedLimit::new(max_solutions, roots.len(), on_solution);
+
+        // indexed collect keeps per-branch results in branch order
+        let branch_results: Vec<(Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>>, SolveStats)> = roots
+            .into_par_iter()
+            .enumerate()
+            .map(|(branch, root)| {
+                let mut search = Search::new(self, Cow::Borrowed(placement_table), root);
+                search.options = options.clone();
+                search.piece_order = Cow::Borrowed(&piece_order);
+                search.seen_states = SeenStates::new(self.num_pieces(), options.dedup);
+                search.cell_placements = Cow::Borrowed(&cell_placements);
+                search.stop = Some(&limit.stops[branch]);
+
+                let mut solutions = Vec::new();
+                for solution in search.by_ref() {
+                    limit.record(
+                        branch,
+                        self.canonical_key_under(&solution, options.symmetry),
+                    );
+                    solutions.push(solution);
+                }
+                limit.finish(branch);
+
+                (solutions, search.stats)
+            })
+            .collect();
+
+        // different branches can reach the same canonical solution
+        let mut merged_keys: FxHashSet<[u8; GRID_SIZE]> = FxHashSet::default();
+        let mut solutions: Vec<Vec<PlacedPiece<MAX_PIECE_CUBES>>> = Vec::new();
+        for (branch_solutions, branch_stats) in branch_results {
+            stats.add_counts(&branch_stats);
+            solutions.extend(branch_solutions.into_iter().filter(|solution| {
+                merged_keys.insert(self.canonical_key_under(solution, options.symmetry))
+            }));
+        }
+
+        if let Some(max) = max_solutions {
+            solutions.truncate(max);
+        }
+        (solutions, stats)
+    }
+
+    /// The empty starting state with every piece still available.
+    ///
+    /// Blocked cells start out occupied, so the search never places a piece
+    /// on them and treats the box as full once every other cell is covered.
+    fn initial_state<M: CellMask>(&self) -> PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES> {
+        let blocked_cells = self
+            .blocked_cells
+            .iter()
+            .fold(M::zero(), |mask, &cell| mask | M::bit(cell));
+
+        PartialSolution {
+            placed_pieces: [PlacedPiece::EMPTY; NUM_PIECES],
+            placed_count: 0,
+            remaining_pieces: full_piece_mask(self.num_pieces()),
+            occupied_cells: blocked_cells,
+            skipped_count: 0,
+            current_piece_index: 0,
+            current_orientation_index: 0,
+        }
+    }
+
+    /// The starting state with `fixed` pieces already placed.
+    fn seeded_state<M: CellMask>(
+        &self,
+        fixed: &[PlacedPiece<MAX_PIECE_CUBES>],
+    ) -> PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES> {
+        self.try_seeded_state(fixed)
+            .unwrap_or_else(|message| panic!("{message}"))
+    }
+
+    /// Like `seeded_state`, but reports an invalid piece instead of panicking.
+    fn try_seeded_state<M: CellMask>(
+        &self,
+        fixed: &[PlacedPiece<MAX_PIECE_CUBES>],
+    ) -> Result<PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>, String> {
+        let mut state = self.initial_state::<M>();
+
+        for placed in fixed {
+            if placed.piece_index >= self.num_pieces() {
+                return Err("fixed piece index out of range".to_string());
+            }
+            if placed.cube_count as usize > MAX_PIECE_CUBES {
+                return Err("fixed piece has too many cubes".to_string());
+            }
+            let piece_bit = 1u32 << placed.piece_index;
+            if state.remaining_pieces & piece_bit == 0 {
+                return Err("piece fixed more than once".to_string());
+            }
+
+            for &(x, y, z) in placed.cubes() {
+                if !((0..DIM_X as i32).contains(&x)
+                    && (0..DIM_Y as i32).contains(&y)
+                    && (0..DIM_Z as i32).contains(&z))
+                {
+                    return Err(format!("fixed piece leaves the box at ({x}, {y}, {z})"));
+                }
+                let cell = M::bit(coord_to_idx::<DIM_Y, DIM_Z>(x, y, z));
+                if (state.occupied_cells & cell).is_nonzero() {
+                    return Err(format!(
+                        "fixed piece overlaps an occupied cell at ({x}, {y}, {z})"
+                    ));
+                }
+                state.occupied_cells = state.occupied_cells | cell;
+            }
+
+            state.placed_pieces[state.placed_count] = *placed;
+            state.placed_count += 1;
+            state.remaining_pieces &= !piece_bit;
+        }
+
+        Ok(state)
+    }
+
+    /// Returns the symmetry-distinct children of `partial`, in search order.
+    ///
+    /// These are the placements covering the cell the heuristic picks,
+    /// pruned and deduplicated the same way the sequential search would
+    /// before descending into them.
+    fn expand_first_level<M: CellMask>(
+        &self,
+        placement_table: &PlacementTable<M, MAX_PIECE_CUBES>,
+        options: &SolveOptions,
+        cell_placements: &CellPlacements<M>,
+        partial: PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>,
+        stats: &mut SolveStats,
+    ) -> Vec<PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>> {
+        let Some(target_cell) = Self::choose_cell(
+            options.heuristic,
+            cell_placements,
+            partial.occupied_cells,
+            partial.remaining_pieces,
+        ) else {
+            return vec![partial];
+        };
+
+        let mut seen_states: FxHashSet<[u8; GRID_SIZE]> = FxHashSet::default();
+        let mut children = Vec::new();
+        let available = Self::available_pieces(partial.remaining_pieces, self.later_copies());
+
+        for piece_index in self.piece_scan_order(&options.piece_order) {
+            if (available & (1u32 << piece_index)) == 0 {
+                continue;
+            }
+            for placement in &placement_table[piece_index][target_cell] {
+                if (partial.occupied_cells & placement.occupied_mask).is_nonzero() {
+                    stats.overlap_rejections += 1;
+                    continue;
+                }
+                let child = Self::place(&partial, piece_index, placement);
+                if options.prune_dead_cells
+                    && Self::has_dead_cell(
+                        cell_placements,
+                        child.occupied_cells,
+                        child.remaining_pieces,
+                    )
+                {
+                    stats.dead_cell_prunes += 1;
+                    continue;
+                }
+                let canonical = self.canonical_key_under(
+                    &child.placed_pieces[..child.placed_count],
+                    options.symmetry,
+                );
+                if seen_states.insert(canonical) {
+                    stats.stack_pushes += 1;
+                    children.push(child);
+                } else {
+                    stats.dedup_hits += 1;
+                }
+            }
+        }
+
+        children
+    }
+
+    /// The piece indices in the order the search tries them: `order` when
+    /// given, otherwise the pieces with the most cubes first, ties by index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is neither empty nor a permutation of the piece
+    /// indices, since a missing piece would lose solutions.
+    fn piece_scan_order(&self, order: &[usize]) -> Vec<usize> {
+        let num_pieces = self.num_pieces();
+        if order.is_empty() {
+            let slots = self.piece_slots();
+            let mut order: Vec<usize> = (0..num_pieces).collect();
+            // stable, so equal sizes keep index order
+            order.sort_by_key(|&piece| std::cmp::Reverse(slots[piece].len()));
+            return order;
+        }
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        assert!(
+            sorted.into_iter().eq(0..num_pieces),
+            "piece order must list each piece index once"
+        );
+        order.to_vec()
+    }
+
+    /// The remaining pieces the search may place next.
+    ///
+    /// A later copy of a shape waits until the copy before it is placed, so
+    /// copies go down in index order and the search never tries the same
+    /// fill with two copies swapped.
+    #[inline(always)]
+    fn available_pieces(remaining_pieces: u32, later_copies: u32) -> u32 {
+        remaining_pieces & !((remaining_pieces << 1) & later_copies)
+    }
+
+    /// Builds the child state that results from adding `placement` to `partial`.
+    #[inline(always)]
+    fn place<M: CellMask>(
+        partial: &PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>,
+        piece_index: usize,
+        placement: &Placement<M, MAX_PIECE_CUBES>,
+    ) -> PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES> {
+        let mut placed_pieces = partial.placed_pieces;
+        placed_pieces[partial.placed_count] = PlacedPiece {
+            piece_index,
+            positions: placement.cube_positions,
+            cube_count: placement.cube_count,
+        };
+
+        PartialSolution {
+            placed_pieces,
+            placed_count: partial.placed_count + 1,
+            // clear the bit for the piece we just placed
+            remaining_pieces: partial.remaining_pieces & !(1u32 << piece_index),
+            occupied_cells: partial.occupied_cells | placement.occupied_mask,
+            skipped_count: partial.skipped_count,
+            current_piece_index: 0,
+            current_orientation_index: 0,
+        }
+    }
+
+    /// Builds the child state that leaves `cell` empty for good, as a
+    /// partial pack may.
+    #[inline(always)]
+    fn skip<M: CellMask>(
+        partial: &PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>,
+        cell: usize,
+    ) -> PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES> {
+        PartialSolution {
+            occupied_cells: partial.occupied_cells | M::bit(cell),
+            skipped_count: partial.skipped_count + 1,
+            current_piece_index: 0,
+            current_orientation_index: 0,
+            ..*partial
+        }
+    }
+
+    fn build_placement_table<M: CellMask>(
+        pieces: &[&[Coord]],
+    ) -> PlacementTable<M, MAX_PIECE_CUBES> {
+        pieces
+            .iter()
+            .map(|piece| Self::piece_placements(piece))
+            .collect()
+    }
+
+    /// One piece's slice of the placement table: for each cell, every
+    /// placement of the piece that covers it.
+    fn piece_placements<M: CellMask>(piece: &[Coord]) -> Vec<Vec<Placement<M, MAX_PIECE_CUBES>>> {
+        let orientations: Arc<[Orientation]> = cached_orientations(piece);
+        (0..GRID_SIZE)
+            .map(|target_cell| {
+                let target_position = idx_to_coord::<DIM_Y, DIM_Z>(target_cell);
+                let mut placements = Vec::new();
+
+                for orientation in orientations.iter() {
+                    // try each cube in the orientation as the anchor on target_position
+                    for &anchor in orientation {
+                        if let Some(placement) =
+                            Self::try_create_placement(orientation, target_position, anchor)
+                        {
+                            placements.push(placement);
+                        }
+                    }
+                }
+
+                placements
+            })
+            .collect()
+    }
+
+    /// Puts `piece`'s placements in slot `index` of `placement_table`,
+    /// leaving the table as it was if the piece fits nowhere in the box.
+    fn replace_placements<M: CellMask>(
+        &self,
+        placement_table: &mut PlacementTable<M, MAX_PIECE_CUBES>,
+        index: usize,
+        piece: &[Coord],
+    ) -> Result<(), PuzzleError> {
+        let placements = Self::piece_placements(piece);
+        if placements.iter().all(Vec::is_empty) {
+            return Err(PuzzleError::PieceOutsideBox(index));
+        }
+        placement_table[index] = placements;
+        Ok(())
+    }
+
+    /// Regroups `placement_table` by cell, tagging each placement with its
+    /// piece bit so the heuristics can scan one cell's options in a flat list.
+    fn build_cell_placements<M: CellMask>(
+        placement_table: &PlacementTable<M, MAX_PIECE_CUBES>,
+    ) -> CellPlacements<M> {
+        (0..GRID_SIZE)
+            .map(|cell| {
+                placement_table
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(piece_index, piece_placements)| {
+                        piece_placements[cell]
+                            .iter()
+                            .map(move |placement| (1u32 << piece_index, placement.occupied_mask))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn try_create_placement<M: CellMask>(
+        orientation: &Orientation,
+        target: Coord,
+        anchor: Coord,
+    ) -> Option<Placement<M, MAX_PIECE_CUBES>> {
+        let mut occupied_mask = M::zero();
+        let mut cube_positions = [(0, 0, 0); MAX_PIECE_CUBES];
+        // shift orientation so anchor lands on target
+        let offset = (
+            target.0 - anchor.0,
+            target.1 - anchor.1,
+            target.2 - anchor.2,
+        );
+
+        for (cube_index, &(piece_x, piece_y, piece_z)) in orientation.iter().enumerate() {
+            let absolute_x = piece_x + offset.0;
+            let absolute_y = piece_y + offset.1;
+            let absolute_z = piece_z + offset.2;
+
+            // reject placements that leave the box bounds
+            if !(0..DIM_X as i32).contains(&absolute_x)
+                || !(0..DIM_Y as i32).contains(&absolute_y)
+                || !(0..DIM_Z as i32).contains(&absolute_z)
+            {
+                return None;
+            }
+
+            let cell_index = coord_to_idx::<DIM_Y, DIM_Z>(absolute_x, absolute_y, absolute_z);
+            occupied_mask = occupied_mask | M::bit(cell_index);
+            cube_positions[cube_index] = (absolute_x, absolute_y, absolute_z);
+        }
+
+        Some(Placement {
+            occupied_mask,
+            cube_positions,
+            cube_count: orientation.len() as u8,
+        })
+    }
+
+    #[inline(always)]
+    fn find_first_empty_cell<M: CellMask>(occupied: M) -> Option<usize> {
+        if occupied == M::all_filled(GRID_SIZE) {
+            None
+        } else {
+            // with filled cells as ones trailing ones reaches the first empty bit
+            Some(occupied.trailing_ones())
+        }
+    }
+
+    /// The first cell in `order` that `occupied` leaves empty, or `None` once
+    /// the box is full. With the identity order this is
+    /// [`find_first_empty_cell`](Self::find_first_empty_cell).
+    fn first_empty_in_order<M: CellMask>(order: &[usize], occupied: M) -> Option<usize> {
+        order
+            .iter()
+            .copied()
+            .find(|&cell| !(occupied & M::bit(cell)).is_nonzero())
+    }
+
+    /// Picks the empty cell to branch on, or `None` once the box is full.
+    #[inline(always)]
+    fn choose_cell<M: CellMask>(
+        heuristic: CellChoice,
+        cell_placements: &CellPlacements<M>,
+        occupied: M,
+        remaining_pieces: u32,
+    ) -> Option<usize> {
+        let first_empty = Self::find_first_empty_cell(occupied)?;
+        if heuristic == CellChoice::FirstEmpty {
+            return Some(first_empty);
+        }
+
+        // ties keep the lowest cell, so the choice is deterministic per state
+        let mut best = (first_empty, usize::MAX);
+        for (cell, placements) in cell_placements.iter().enumerate().skip(first_empty) {
+            if (occupied & M::bit(cell)).is_nonzero() {
+                continue;
+            }
+            let fits = Self::count_fitting(placements, occupied, remaining_pieces, best.1);
+            if fits < best.1 {
+                best = (cell, fits);
+                // a cell nothing fits is a dead end; branching on it prunes now
+                if fits == 0 {
+                    break;
+                }
+            }
+        }
+        Some(best.0)
+    }
+
+    /// Whether some empty cell has no fitting placement of a remaining piece,
+    /// which means no completion of the state exists.
+    #[inline(always)]
+    fn has_dead_cell<M: CellMask>(
+        cell_placements: &CellPlacements<M>,
+        occupied: M,
+        remaining_pieces: u32,
+    ) -> bool {
+        let Some(first_empty) = Self::find_first_empty_cell(occupied) else {
+            return false;
+        };
+        cell_placements
+            .iter()
+            .enumerate()
+            .skip(first_empty)
+            .any(|(cell, placements)| {
+                !(occupied & M::bit(cell)).is_nonzero()
+                    && Self::count_fitting(placements, occupied, remaining_pieces, 1) == 0
+            })
+    }
+
+    /// Counts placements of remaining pieces that avoid `occupied`, stopping
+    /// early once the count reaches `limit`.
+    #[inline(always)]
+    fn count_fitting<M: CellMask>(
+        placements: &[(u32, M)],
+        occupied: M,
+        remaining_pieces: u32,
+        limit: usize,
+    ) -> usize {
+        let mut fits = 0;
+        for &(piece_bit, mask) in placements {
+            if remaining_pieces & piece_bit != 0 && !(occupied & mask).is_nonzero() {
+                fits += 1;
+                if fits >= limit {
+                    break;
+                }
+            }
+        }
+        fits
+    }
+}
+
+impl<
+        'a,
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+        M: CellMask,
+        const MAX_PIECE_CUBES: usize,
+    > Search<'a, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, M, MAX_PIECE_CUBES>
+{
+    fn new(
+        puzzle: &'a Puzzle<'a, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>,
+        placement_table: Cow<'a, PlacementTable<M, MAX_PIECE_CUBES>>,
+        start: PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>,
+    ) -> Self {
+        Self {
+            puzzle,
+            placement_table,
+            search_stack: vec![start],
+            seen_states: SeenStates::new(puzzle.num_pieces(), Dedup::Exact),
+            dedup: true,
+            spare_cells: 0,
+            options: SolveOptions::default(),
+            piece_order: Cow::Owned(puzzle.piece_scan_order(&[])),
+            cell_placements: Cow::Owned(Vec::new()),
+            stop: None,
+            pause_at: None,
+            stats: SolveStats::default(),
+        }
+    }
+
+    /// The grid `state` is keyed by, before any symmetry is applied.
+    ///
+    /// Midway through a partial pack the cells it left empty are marked, so
+    /// two states only merge when they leave the same cells open. A finished
+    /// pack is keyed by its pieces alone, so symmetric packs merge however
+    /// the search reached them.
+    fn state_grid(
+        &self,
+        state: &PartialSolution<NUM_PIECES, M, MAX_PIECE_CUBES>,
+    ) -> [u8; GRID_SIZE] {
+        let placed = &state.placed_pieces[..state.placed_count];
+        if self.spare_cells == 0 || state.placed_count == self.puzzle.num_pieces() {
+            return self.puzzle.key_grid(placed);
+        }
+        self.puzzle.closed_key_grid(placed, |cell| {
+            (state.occupied_cells & M::bit(cell)).is_nonzero()
+        })
+    }
+}
+
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+        M: CellMask,
+        const MAX_PIECE_CUBES: usize,
+    > Iterator for Search<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, M, MAX_PIECE_CUBES>
+{
+    type Item = Vec<PlacedPiece<MAX_PIECE_CUBES>>;
+
+    /// Advances the depth-first search until the next complete solution.
+    fn next(&mut self) -> Option<Self::Item> {
+        let num_pieces = self.puzzle.num_pieces();
+        let later_copies = self.puzzle.later_copies();
+
+        while let Some(mut partial) = self.search_stack.pop() {
+            if self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+                self.search_stack.clear();
+                return None;
+            }
+            if self.pause_at.is_some_and(|at| Instant::now() >= at) {
+                // keep the frame so the next call resumes from it
+                self.search_stack.push(partial);
+                return None;
+            }
+
+            // a partial pack is done once every piece is in, empty cells or not
+            if self.spare_cells > 0 && partial.placed_count == num_pieces {
+                return Some(partial.placed_pieces[..partial.placed_count].to_vec());
+            }
+
+            // the choice depends only on the state, so a resumed frame
+            // picks the same cell it was scanning before
+            let Some(target_cell) =
+                Puzzle::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>::choose_cell(
+                    self.options.heuristic,
+                    &self.cell_placements,
+                    partial.occupied_cells,
+                    partial.remaining_pieces,
+                )
+            else {
+                // no empty cell means a complete solution
+                return Some(partial.placed_pieces[..partial.placed_count].to_vec());
+            };
+
+            let available = Puzzle::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>::available_pieces(
+                partial.remaining_pieces,
+                later_copies,
+            );
+
+            'pieces: loop {
+                // scan available pieces from the current position in the order
+                let Some(position) = (partial.current_piece_index..num_pieces)
+                    .find(|&position| (available & (1u32 << self.piece_order[position])) != 0)
+                else {
+                    // with every piece tried here, a partial pack may still
+                    // leave the cell empty while spare cells remain
+                    if partial.skipped_count < self.spare_cells {
+                        self.search_stack.push(Puzzle::<
+                            DIM_X,
+                            DIM_Y,
+                            DIM_Z,
+                            GRID_SIZE,
+                            NUM_PIECES,
+                            MAX_PIECE_CUBES,
+                        >::skip(
+                            &partial, target_cell
+                        ));
+                        self.stats.stack_pushes += 1;
+                    }
+                    break 'pieces;
+                };
+                partial.current_piece_index = position;
+                let piece_index = self.piece_order[position];
+
+                // all placements here are precomputed to cover target_cell
+                let valid_placements = &self.placement_table[piece_index][target_cell];
+
+                while partial.current_orientation_index < valid_placements.len() {
+                    let placement = &valid_placements[partial.current_orientation_index];
+                    partial.current_orientation_index += 1;
+
+                    // any shared bit means this placement overlaps existing cubes
+                    if (partial.occupied_cells & placement.occupied_mask).is_nonzero() {
+                        self.stats.overlap_rejections += 1;
+                        continue;
+                    }
+
+                    let child = Puzzle::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>::place(
+                        &partial,
+                        piece_index,
+                        placement,
+                    );
+
+                    if self.options.prune_dead_cells
+                        && Puzzle::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, MAX_PIECE_CUBES>::has_dead_cell(
+                            &self.cell_placements,
+                            child.occupied_cells,
+                            child.remaining_pieces,
+                        )
+                    {
+                        self.stats.dead_cell_prunes += 1;
+                        continue;
+                    }
+
+                    // canonical key merges equivalent states under symmetry
+                    if self.dedup {
+                        let grid_key = self.state_grid(&child);
+                        if !self.seen_states.insert_grid(
+                            self.puzzle,
+                            &grid_key,
+                            self.options.symmetry,
+                        ) {
+                            self.stats.dedup_hits += 1;
+                            continue;
+                        }
+                    }
+
+                    // push parent first then child so child runs next
+                    self.search_stack.push(partial);
+                    self.search_stack.push(child);
+                    self.stats.stack_pushes += 1;
+
+                    // branch consumed one placement so descend immediately
+                    break 'pieces;
+                }
+
+                // no placement worked for this piece so try next piece
+                partial.current_piece_index += 1;
+                partial.current_orientation_index = 0;
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hash::FxHashSet;
+
+    use super::{CellChoice, CellMask, Dedup, SolveOptions, SolveStats, Symmetry, WordMask};
+    use crate::pieces::{
+        Coord, PlacedPiece, Puzzle, PuzzleError, BEDLAM_PUZZLE, CHIRAL_PAIR, MAX_CUBES, PIECES,
+        SLOTHOUBER_GRAATSMA_PUZZLE, SOMA_PUZZLE,
+    };
+
+    const DOMINO: &[Coord] = &[(0, 0, 0), (1, 0, 0)];
+    const L_TROMINO: &[Coord] = &[(0, 0, 0), (1, 0, 0), (0, 1, 0)];
+    const I_PENTOMINO: &[Coord] = &[(0, 0, 0), (1, 0, 0), (2, 0, 0), (3, 0, 0), (4, 0, 0)];
+
+    #[test]
+    fn test_word_mask_spans_words() {
+        let filled = WordMask::<4>::all_filled(130);
+        assert_eq!(filled.0, [u64::MAX, u64::MAX, 0b11, 0]);
+        assert_eq!(filled.trailing_ones(), 130);
+
+        let gap = WordMask::<4>::all_filled(100) | WordMask::<4>::bit(101);
+        assert_eq!(gap.trailing_ones(), 100);
+        assert!((gap & WordMask::<4>::bit(101)).is_nonzero());
+        assert!(!(gap & WordMask::<4>::bit(100)).is_nonzero());
+    }
+
+    #[test]
+    fn test_large_grids_use_wider_masks() {
+        const ROW_25: &[&[Coord]] = &[I_PENTOMINO; 25];
+        const ROW_26: &[&[Coord]] = &[I_PENTOMINO; 26];
+
+        // 125 cells fits a u128
+        let cube: Puzzle<5, 5, 5, 125, 25> = Puzzle::new(ROW_25, None);
+        assert_eq!(cube.solve(Some(1)).len(), 1);
+
+        // 130 cells needs the u64 array
+        let slab: Puzzle<2, 5, 13, 130, 26> = Puzzle::new(ROW_26, None);
+        let solutions = slab.solve(Some(1));
+        assert_eq!(solutions.len(), 1);
+        let cube_count: usize = solutions[0].iter().map(|placed| placed.cubes().len()).sum();
+        assert_eq!(cube_count, 130);
+    }
+
+    #[test]
+    fn test_flat_box_merges_box_symmetries() {
+        // two labelled dominoes fill a 2x2 square in one way up to symmetry
+        let square: Puzzle<1, 2, 2, 4, 2> = Puzzle::new(&[DOMINO, DOMINO], None);
+        assert_eq!(square.solve(None).len(), 1);
+
+        // a 2x3 rectangle has no quarter turns: three dominoes give 6 fills
+        let rectangle: Puzzle<1, 2, 3, 6, 3> = Puzzle::new(&[DOMINO, DOMINO, DOMINO], None);
+        assert_eq!(rectangle.solve(None).len(), 6);
+    }
+
+    #[test]
+    fn test_piece_copies_are_interchangeable() {
+        // three copies of one domino: the 6 labelled fills collapse to 2
+        let rectangle: Puzzle<1, 2, 3, 6, 3> = Puzzle::with_counts(&[DOMINO], &[3], None);
+        assert_eq!(rectangle.num_pieces(), 3);
+        assert_eq!(rectangle.solve(None).len(), 2);
+        assert_eq!(rectangle.solve_sequential(None).len(), 2);
+        assert_eq!(rectangle.solve_dlx(None).len(), 2);
+        // the box has 3 domino tilings and each is found once
+        assert_eq!(rectangle.solve_all(None).len(), 3);
+    }
+
+    #[test]
+    fn test_pieces_larger_than_the_default_limit() {
+        const ROD: &[Coord] = &[
+            (0, 0, 0),
+            (1, 0, 0),
+            (2, 0, 0),
+            (3, 0, 0),
+            (4, 0, 0),
+            (5, 0, 0),
+        ];
+        const TRIOMINO: &[Coord] = &[(0, 0, 0), (1, 0, 0), (2, 0, 0)];
+        let puzzle = Puzzle::<1, 2, 6, 12, 3, 6>::with_counts(&[ROD, TRIOMINO], &[1, 2], None);
+
+        let solutions = puzzle.solve(None);
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(puzzle.solve_sequential(None).len(), 1);
+        assert!(puzzle.validate_solution(&solutions[0]).is_ok());
+        let rod = solutions[0]
+            .iter()
+            .find(|placed| placed.piece_index == 0)
+            .unwrap();
+        assert_eq!(rod.cubes().len(), 6);
+    }
+
+    #[test]
+    fn test_rectangular_box_solutions_fill_the_box() {
+        // the six Soma tetracubes (all but the small L) pack a 2x3x4 box
+        const TETRACUBES: &[&[Coord]] = &[
+            PIECES[0], PIECES[1], PIECES[2], PIECES[4], PIECES[5], PIECES[6],
+        ];
+        let puzzle: Puzzle<2, 3, 4, 24, 6> = Puzzle::new(TETRACUBES, Some((3, 5)));
+
+        let solutions = puzzle.solve(None);
+        assert!(!solutions.is_empty());
+        for solution in &solutions {
+            let mut filled = [false; 24];
+            for &(x, y, z) in solution.iter().flat_map(|placed| placed.cubes()) {
+                assert!((0..2).contains(&x) && (0..3).contains(&y) && (0..4).contains(&z));
+                let cell = (x * 12 + y * 4 + z) as usize;
+                assert!(!filled[cell], "cell {cell} filled twice");
+                filled[cell] = true;
+            }
+            assert!(filled.iter().all(|&cell| cell));
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let parallel = SOMA_PUZZLE.solve(None);
+        let sequential = SOMA_PUZZLE.solve_sequential(None);
+
+        let parallel_keys: Vec<_> = parallel
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        let sequential_keys: Vec<_> = sequential
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        assert_eq!(parallel_keys, sequential_keys);
+    }
+
+    #[test]
+    fn test_solution_histogram_splits_solve_by_branch() {
+        let histogram = SOMA_PUZZLE.solution_histogram();
+        let solutions = SOMA_PUZZLE.solve(None);
+        assert_eq!(
+            histogram.iter().map(|&(_, count)| count).sum::<usize>(),
+            solutions.len()
+        );
+
+        // every branch starts by covering the first cell, and solve lists
+        // each solution's branch placement first
+        for (first, count) in histogram {
+            assert!(first.cubes().contains(&(0, 0, 0)));
+            let from_branch = solutions
+                .iter()
+                .filter(|solution| {
+                    solution[0].piece_index == first.piece_index
+                        && solution[0].cubes() == first.cubes()
+                })
+                .count();
+            assert_eq!(count, from_branch);
+        }
+    }
+
+    #[test]
+    fn test_legal_placements_skip_covered_cells_and_used_pieces() {
+        let row: Puzzle<1, 1, 6, 6, 3> = Puzzle::new(&[DOMINO, DOMINO, DOMINO], None);
+        assert_eq!(row.legal_placements(0, 0b111).len(), 3 * 5);
+        let cells = |placements: Vec<PlacedPiece>| -> Vec<(usize, Vec<Coord>)> {
+            placements
+                .iter()
+                .map(|placed| (placed.piece_index, placed.sorted_cubes()))
+                .collect()
+        };
+        assert_eq!(
+            cells(row.legal_placements(0b000100, 0b010)),
+            [
+                (1, vec![(0, 0, 0), (0, 0, 1)]),
+                (1, vec![(0, 0, 3), (0, 0, 4)]),
+                (1, vec![(0, 0, 4), (0, 0, 5)]),
+            ]
+        );
+        assert!(row.legal_placements(0, 0).is_empty());
+
+        // blocked cells count as occupied
+        let blocked: Puzzle<1, 1, 5, 5, 2> = Puzzle::with_blocked(&[DOMINO, DOMINO], None, &[2]);
+        assert_eq!(blocked.legal_placements(0, 0b11).len(), 2 * 2);
+
+        // every Soma piece fits the empty cube, and the prepared table agrees
+        let legal = SOMA_PUZZLE.prepare().legal_placements(0, 0b1111111);
+        for piece_index in 0..7 {
+            assert!(legal.iter().any(|placed| placed.piece_index == piece_index));
+        }
+        assert_eq!(
+            cells(legal),
+            cells(SOMA_PUZZLE.legal_placements(0, 0b1111111))
+        );
+    }
+
+    #[test]
+    fn test_bedlam_is_harder_than_soma() {
+        let soma = SOMA_PUZZLE.difficulty(None);
+        assert_eq!(soma.solutions, 240);
+        assert!(soma.nodes_to_first_solution > 0);
+        assert!(soma.root_branching > 1);
+        // five pieces with 12 orientations, L with 24 and the branch with 8
+        assert_eq!(soma.average_orientations, 92.0 / 7.0);
+
+        // skip the count, which takes minutes for Bedlam
+        let bedlam = BEDLAM_PUZZLE.difficulty(Some(0));
+        assert_eq!(bedlam.solutions, 0);
+        assert!(bedlam.nodes_to_first_solution > 100 * soma.nodes_to_first_solution);
+        assert!(bedlam.root_branching > soma.root_branching);
+    }
+
+    #[test]
+    fn test_solve_iter_streams_sequential_order() {
+        let sequential = SOMA_PUZZLE.solve_sequential(None);
+        let streamed: Vec<_> = SOMA_PUZZLE.solve_iter().collect();
+        assert_eq!(streamed.len(), sequential.len());
+
+        for (streamed, sequential) in streamed.iter().zip(&sequential) {
+            assert_eq!(
+                SOMA_PUZZLE.canonical_key(streamed),
+                SOMA_PUZZLE.canonical_key(sequential)
+            );
+        }
+
+        let first_three: Vec<_> = SOMA_PUZZLE.solve_iter().take(3).collect();
+        assert_eq!(first_three.len(), 3);
+    }
+
+    #[test]
+    fn test_solve_filtered_limits_matching_solutions() {
+        let covers_origin = |solution: &[PlacedPiece]| {
+            solution
+                .iter()
+                .any(|placed| placed.piece_index == 0 && placed.cubes().contains(&(0, 0, 0)))
+        };
+        let expected = SOMA_PUZZLE
+            .solve_sequential(None)
+            .into_iter()
+            .filter(|solution| covers_origin(solution))
+            .count();
+        assert!(expected > 2);
+        assert_eq!(
+            SOMA_PUZZLE.solve_filtered(covers_origin, None).len(),
+            expected
+        );
+
+        let first_two = SOMA_PUZZLE.solve_filtered(covers_origin, Some(2));
+        assert_eq!(first_two.len(), 2);
+        assert!(first_two.iter().all(|solution| covers_origin(solution)));
+    }
+
+    #[test]
+    fn test_solve_with_stats_counts_work() {
+        let (solutions, stats) = SOMA_PUZZLE.solve_with_stats(None);
+        assert_eq!(solutions.len(), 240);
+        // every solution needs one push per piece at minimum
+        assert!(stats.stack_pushes >= 240);
+        assert!(stats.overlap_rejections > 0);
+        assert!(stats.dedup_hits > 0);
+    }
+
+    #[test]
+    fn test_solve_with_progress_reports_each_solution() {
+        let mut counts = Vec::new();
+        let (solutions, _) = SOMA_PUZZLE.solve_with_progress(None, |count| counts.push(count));
+        assert_eq!(counts, (1..=solutions.len()).collect::<Vec<_>>());
+
+        let mut counts = Vec::new();
+        let (solutions, _) = SOMA_PUZZLE.solve_with_progress(Some(5), |count| counts.push(count));
+        assert_eq!(solutions.len(), 5);
+        assert_eq!(counts, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_cell_placements_transpose_the_placement_table() {
+        type Soma = Puzzle<'static, 3, 3, 3, 27, 7>;
+        let table = Soma::build_placement_table::<u32>(&SOMA_PUZZLE.piece_slots());
+        let by_cell = Soma::build_cell_placements(&table);
+
+        for (cell, placements) in by_cell.iter().enumerate() {
+            let expected: usize = table.iter().map(|piece| piece[cell].len()).sum();
+            assert_eq!(placements.len(), expected);
+            for &(piece_bit, mask) in placements {
+                assert_eq!(piece_bit.count_ones(), 1);
+                assert_ne!(mask & u32::bit(cell), 0, "placement misses cell {cell}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_most_constrained_cell_finds_same_solutions() {
+        let keys = |solutions: &[Vec<PlacedPiece>]| -> FxHashSet<[u8; 27]> {
+            solutions
+                .iter()
+                .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+                .collect()
+        };
+        let options = SolveOptions {
+            heuristic: CellChoice::MostConstrained,
+            ..SolveOptions::default()
+        };
+        let (first_empty, first_empty_stats) = SOMA_PUZZLE.solve_with_stats(None);
+        let (constrained, constrained_stats) =
+            SOMA_PUZZLE.solve_with_options(None, options.clone());
+
+        assert_eq!(constrained.len(), 240);
+        assert_eq!(keys(&constrained), keys(&first_empty));
+        assert!(constrained_stats.stack_pushes < first_empty_stats.stack_pushes);
+        assert_eq!(SOMA_PUZZLE.solve_with_options(Some(3), options).0.len(), 3);
+    }
+
+    #[test]
+    fn test_piece_order_keeps_the_solutions() {
+        // the small L tricube goes last by default
+        assert_eq!(SOMA_PUZZLE.piece_scan_order(&[]), [0, 1, 2, 4, 5, 6, 3]);
+
+        // the Soma tetracubes in a 2x3x4 box enumerate far faster than Soma
+        const TETRACUBES: &[&[Coord]] = &[
+            PIECES[0], PIECES[1], PIECES[2], PIECES[4], PIECES[5], PIECES[6],
+        ];
+        let puzzle: Puzzle<2, 3, 4, 24, 6> = Puzzle::new(TETRACUBES, Some((3, 5)));
+        let keys = |solutions: &[Vec<PlacedPiece>]| -> FxHashSet<[u8; 24]> {
+            solutions
+                .iter()
+                .map(|solution| puzzle.canonical_key(solution))
+                .collect()
+        };
+        let expected = puzzle.solve(None);
+        assert!(!expected.is_empty());
+        let expected_keys = keys(&expected);
+        let orders: [Vec<usize>; 3] = [
+            (0..6).collect(),
+            (0..6).rev().collect(),
+            vec![3, 0, 5, 1, 4, 2],
+        ];
+        for piece_order in orders {
+            let options = SolveOptions {
+                piece_order,
+                ..SolveOptions::default()
+            };
+            let (solutions, _) = puzzle.solve_with_options(None, options);
+            assert_eq!(solutions.len(), expected.len());
+            assert_eq!(keys(&solutions), expected_keys);
+        }
+
+        // later copies still wait for the copies before them
+        let reversed = SolveOptions {
+            piece_order: (0..9).rev().collect(),
+            ..SolveOptions::default()
+        };
+        let (solutions, _) = SLOTHOUBER_GRAATSMA_PUZZLE.solve_with_options(None, reversed);
+        assert_eq!(solutions.len(), 1);
+    }
+
+    #[test]
+    fn test_dead_cell_pruning_cuts_bedlam_nodes() {
+        let unpruned = SolveOptions {
+            heuristic: CellChoice::MostConstrained,
+            ..SolveOptions::default()
+        };
+        let pruned = SolveOptions {
+            prune_dead_cells: true,
+            ..unpruned.clone()
+        };
+        let (_, unpruned_stats) = BEDLAM_PUZZLE.solve_with_options(Some(1), unpruned);
+        let (solutions, pruned_stats) = BEDLAM_PUZZLE.solve_with_options(Some(1), pruned);
+
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].len(), 13);
+        assert!(pruned_stats.dead_cell_prunes > 0);
+        // roughly a third of the nodes at the time of writing
+        assert!(pruned_stats.stack_pushes * 2 < unpruned_stats.stack_pushes);
+    }
+
+    #[test]
+    fn test_dead_cell_pruning_keeps_every_solution() {
+        let options = SolveOptions {
+            prune_dead_cells: true,
+            ..SolveOptions::default()
+        };
+        let (solutions, stats) = SOMA_PUZZLE.solve_with_options(None, options);
+        let keys: FxHashSet<[u8; 27]> = solutions
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        assert_eq!(solutions.len(), 240);
+        assert_eq!(keys.len(), 240);
+        assert!(stats.dead_cell_prunes > 0);
+    }
+
+    #[test]
+    fn test_canonical_solutions_match_sequential_search() {
+        let canonical = SOMA_PUZZLE.solve_canonical();
+        let sequential = SOMA_PUZZLE.solve_sequential(None);
+        assert_eq!(canonical.len(), 240);
+        for (canonical, sequential) in canonical.iter().zip(&sequential) {
+            let again = SOMA_PUZZLE.canonical_solution(sequential);
+            assert_eq!(format!("{canonical:?}"), format!("{again:?}"));
+        }
+    }
+
+    #[test]
+    fn test_rotation_only_symmetry_counts_mirror_images_apart() {
+        let options = SolveOptions {
+            symmetry: Symmetry::RotationOnly,
+            ..SolveOptions::default()
+        };
+        let (rotations, _) = SOMA_PUZZLE.solve_with_options(None, options);
+        assert_eq!(SOMA_PUZZLE.solve(None).len(), 240);
+        // no Soma solution is its own mirror image
+        assert_eq!(rotations.len(), 480);
+
+        let keys: FxHashSet<[u8; 27]> = rotations
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key_under(solution, Symmetry::RotationOnly))
+            .collect();
+        assert_eq!(keys.len(), 480);
+        let merged: FxHashSet<[u8; 27]> = rotations
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        assert_eq!(merged.len(), 240);
+    }
+
+    #[test]
+    fn test_cell_order_changes_the_node_count() {
+        // row order covers cell 0 with either domino, then the other fills
+        // the rest; starting from cell 1 also tries the middle, a dead end
+        let row: Puzzle<4, 1, 1, 4, 2> = Puzzle::new(&[DOMINO, DOMINO], None);
+        assert_eq!(row.evaluate_cell_order(&[0, 1, 2, 3]), 4);
+        assert_eq!(row.evaluate_cell_order(&[1, 0, 2, 3]), 6);
+        assert_eq!(row.evaluate_cell_order(&[3, 2, 1, 0]), 4);
+
+        let linear: Vec<usize> = (0..27).collect();
+        let reversed: Vec<usize> = (0..27).rev().collect();
+        // reversing the cells mirrors the box through its center, which maps
+        // Soma's pieces onto themselves with the chiral pair swapped
+        let nodes = SOMA_PUZZLE.evaluate_cell_order(&linear);
+        assert!(nodes > 0);
+        assert_eq!(SOMA_PUZZLE.evaluate_cell_order(&reversed), nodes);
+    }
+
+    #[test]
+    #[should_panic(expected = "permutation of 0..GRID_SIZE")]
+    fn test_cell_order_must_list_every_cell_once() {
+        let row: Puzzle<4, 1, 1, 4, 2> = Puzzle::new(&[DOMINO, DOMINO], None);
+        row.evaluate_cell_order(&[0, 1, 1, 3]);
+    }
+
+    #[test]
+    fn test_sorted_pieces_keep_canonical_keys() {
+        let options = SolveOptions {
+            sort_pieces: true,
+            ..SolveOptions::default()
+        };
+        let (sorted, _) = SOMA_PUZZLE.solve_with_options(None, options);
+        let unsorted = SOMA_PUZZLE.solve(None);
+        assert_eq!(sorted.len(), unsorted.len());
+        for (sorted, unsorted) in sorted.iter().zip(&unsorted) {
+            assert!(sorted.is_sorted_by_key(|placed| placed.piece_index));
+            assert_eq!(
+                SOMA_PUZZLE.canonical_key(sorted),
+                SOMA_PUZZLE.canonical_key(unsorted)
+            );
+        }
+    }
+
+    #[test]
+    fn test_bloom_dedup_stays_close_to_exact() {
+        let bloom = |bits| SolveOptions {
+            dedup: Dedup::Bloom { bits, hashes: 4 },
+            ..SolveOptions::default()
+        };
+        let (solutions, stats) = SOMA_PUZZLE.solve_with_options(None, bloom(1 << 20));
+        let keys: FxHashSet<[u8; 27]> = solutions
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        assert_eq!(keys.len(), solutions.len());
+        assert!(
+            (235..=240).contains(&solutions.len()),
+            "{}",
+            solutions.len()
+        );
+        assert!(stats.dedup_hits > 0);
+
+        // a filter far too small for the search mistakes new states for seen
+        let (cramped, _) = SOMA_PUZZLE.solve_with_options(None, bloom(64));
+        assert!(cramped.len() < 240);
+    }
+
+    #[test]
+    fn test_prepared_puzzle_matches_fresh_solves() {
+        let prepared = SOMA_PUZZLE.prepare();
+        let grid =
+            |solution: &[PlacedPiece]| crate::grid::solution_to_grid::<3, 3, 3, 27>(solution);
+        let grids = |solutions: &[Vec<PlacedPiece>]| -> Vec<[u8; 27]> {
+            solutions.iter().map(|solution| grid(solution)).collect()
+        };
+
+        assert_eq!(
+            grids(&prepared.solve(None)),
+            grids(&SOMA_PUZZLE.solve(None))
+        );
+        for seed in 0..5 {
+            assert_eq!(
+                prepared.solve_random(seed).map(|solution| grid(&solution)),
+                SOMA_PUZZLE
+                    .solve_random(seed)
+                    .map(|solution| grid(&solution))
+            );
+        }
+
+        let pinned = SOMA_PUZZLE.solve(Some(1))[0][0];
+        let containing = prepared.solutions_containing(pinned, None);
+        assert!(!containing.is_empty());
+        assert_eq!(
+            grids(&containing),
+            grids(&SOMA_PUZZLE.solutions_containing(pinned, None))
+        );
+    }
+
+    #[test]
+    fn test_replace_piece_matches_a_fresh_build() {
+        // the flat square in place of the L tetracube
+        const SQUARE: &[Coord] = &[(0, 0, 0), (1, 0, 0), (0, 1, 0), (1, 1, 0)];
+        let mut pieces = PIECES.to_vec();
+        pieces[0] = SQUARE;
+        let fresh: Puzzle<3, 3, 3, 27, 7> = Puzzle::new(&pieces, SOMA_PUZZLE.chiral_pair);
+
+        let mut prepared = SOMA_PUZZLE.prepare();
+        prepared.replace_piece(0, SQUARE).unwrap();
+        let keys = |solutions: Vec<Vec<PlacedPiece>>| -> Vec<[u8; 27]> {
+            solutions
+                .iter()
+                .map(|solution| fresh.canonical_key(solution))
+                .collect()
+        };
+        let replaced = keys(prepared.solve(None));
+        assert!(!replaced.is_empty());
+        assert_eq!(replaced, keys(fresh.solve(None)));
+
+        assert_eq!(
+            prepared.replace_piece(1, &[(0, 0, 0), (1, 0, 0)]),
+            Err(PuzzleError::VolumeMismatch {
+                piece_cells: 25,
+                box_cells: 27
+            })
+        );
+        assert_eq!(
+            prepared.replace_piece(1, &[(0, 0, 0), (1, 0, 0), (0, 2, 0), (1, 2, 0)]),
+            Err(PuzzleError::Disconnected(1))
+        );
+        assert_eq!(
+            prepared.replace_piece(4, SQUARE),
+            Err(PuzzleError::LinkedPiece(4))
+        );
+        // a failed replacement leaves the table alone
+        assert_eq!(keys(prepared.solve(None)), replaced);
+    }
+
+    #[test]
+    fn test_solve_random_is_reproducible() {
+        let grid =
+            |solution: &[PlacedPiece]| crate::grid::solution_to_grid::<3, 3, 3, 27>(solution);
+        let first = SOMA_PUZZLE.solve_random(7).unwrap();
+        assert_eq!(grid(&first), grid(&SOMA_PUZZLE.solve_random(7).unwrap()));
+        assert_eq!(first.len(), 7);
+
+        // every random fill is one of the known solutions up to symmetry
+        let keys: FxHashSet<[u8; 27]> = SOMA_PUZZLE
+            .solve(None)
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        let picked: FxHashSet<[u8; 27]> = (0..20)
+            .map(|seed| SOMA_PUZZLE.canonical_key(&SOMA_PUZZLE.solve_random(seed).unwrap()))
+            .collect();
+        assert!(picked.is_subset(&keys));
+        assert!(picked.len() > 1);
+
+        let partial = Puzzle::<1, 1, 5, 5, 2>::new_partial(&[DOMINO, DOMINO], None);
+        assert!(partial.solve_random(0).is_none());
+    }
+
+    #[test]
+    fn test_count_live_matches_solve() {
+        assert_eq!(SOMA_PUZZLE.count_live(), 240);
+
+        let rectangle: Puzzle<1, 2, 3, 6, 3> = Puzzle::new(&[DOMINO, DOMINO, DOMINO], None);
+        assert_eq!(rectangle.count_live(), rectangle.solve(None).len());
+    }
+
+    #[test]
+    fn test_unique_solution_stops_at_the_cap() {
+        assert!(!SOMA_PUZZLE.has_unique_solution());
+        assert_eq!(SOMA_PUZZLE.solution_multiplicity(5), 5);
+        assert_eq!(SOMA_PUZZLE.solution_multiplicity(1000), 240);
+
+        let square: Puzzle<1, 2, 2, 4, 2> = Puzzle::new(&[DOMINO, DOMINO], None);
+        assert!(square.has_unique_solution());
+        let partial = Puzzle::<1, 1, 5, 5, 2>::new_partial(&[DOMINO, DOMINO], None);
+        assert!(!partial.has_unique_solution());
+        assert_eq!(partial.solution_multiplicity(2), 0);
+    }
+
+    #[test]
+    fn test_any_solution_is_a_valid_fill() {
+        let solution = SOMA_PUZZLE.any_solution().unwrap();
+        assert_eq!(SOMA_PUZZLE.validate_solution(&solution), Ok(()));
+
+        let blocked = Puzzle::<1, 1, 5, 5, 2>::with_blocked(&[DOMINO, DOMINO], None, &[2]);
+        let solution = blocked.any_solution().unwrap();
+        assert_eq!(blocked.validate_solution(&solution), Ok(()));
+
+        let partial = Puzzle::<1, 1, 5, 5, 2>::new_partial(&[DOMINO, DOMINO], None);
+        assert!(partial.any_solution().is_none());
+    }
+
+    #[test]
+    fn test_partial_pack_leaves_spare_cells_empty() {
+        // the Soma pieces leave nine of 36 cells empty
+        let tall = Puzzle::<3, 3, 4, 36, 7>::new_partial(PIECES, Some(CHIRAL_PAIR));
+        let packs = tall.solve_partial(Some(20));
+        assert_eq!(packs.len(), 20);
+        let keys: FxHashSet<[u8; 36]> = packs.iter().map(|pack| tall.canonical_key(pack)).collect();
+        assert_eq!(keys.len(), 20);
+        for pack in &packs {
+            assert_eq!(pack.len(), 7);
+            let grid = crate::grid::solution_to_grid::<3, 3, 4, 36>(pack);
+            assert_eq!(grid.iter().filter(|&&cell| cell == 0).count(), 9);
+        }
+
+        // every pack the unmerged search reaches is an image of one found
+        let flat = Puzzle::<2, 2, 3, 12, 3>::new_partial(&[L_TROMINO, L_TROMINO, L_TROMINO], None);
+        let packs = flat.solve_partial(None);
+        let keys: FxHashSet<[u8; 12]> = packs.iter().map(|pack| flat.canonical_key(pack)).collect();
+        assert_eq!(keys.len(), packs.len());
+        let mut search = flat.search_from_start::<u32>();
+        search.dedup = false;
+        search.spare_cells = 3;
+        let all_keys: FxHashSet<[u8; 12]> = search.map(|pack| flat.canonical_key(&pack)).collect();
+        assert_eq!(all_keys, keys);
+
+        assert_eq!(SOMA_PUZZLE.solve_partial(None).len(), 240);
+    }
+
+    #[test]
+    fn test_solve_all_includes_every_symmetric_copy() {
+        let all = SOMA_PUZZLE.solve_all(None);
+        // no Soma solution is symmetric, so each has 48 distinct images
+        assert_eq!(all.len(), 240 * 48);
+
+        let grids: FxHashSet<[u8; 27]> = all
+            .iter()
+            .map(|solution| crate::grid::solution_to_grid::<3, 3, 3, 27>(solution))
+            .collect();
+        assert_eq!(grids.len(), all.len());
+        let keys: FxHashSet<[u8; 27]> = all
+            .iter()
+            .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+            .collect();
+        assert_eq!(keys.len(), 240);
+
+        assert_eq!(SOMA_PUZZLE.solve_all(Some(10)).len(), 10);
+    }
+
+    #[test]
+    fn test_blocked_cells_stay_empty() {
+        // a blocked middle cell keeps the end-for-end flip as a symmetry
+        let middle: Puzzle<1, 1, 5, 5, 2> = Puzzle::with_blocked(&[DOMINO, DOMINO], None, &[2]);
+        assert_eq!(middle.solve(None).len(), 1);
+
+        // a blocked end cell breaks it, so both orderings are distinct
+        let end: Puzzle<1, 1, 5, 5, 2> = Puzzle::with_blocked(&[DOMINO, DOMINO], None, &[0]);
+        let solutions = end.solve(None);
+        assert_eq!(solutions.len(), 2);
+        for solution in &solutions {
+            let covers_blocked = solution
+                .iter()
+                .any(|placed| placed.cubes().contains(&(0, 0, 0)));
+            assert!(!covers_blocked);
+        }
+    }
+
+    #[test]
+    fn test_blocked_cells_limit_symmetry_merging() {
+        let puzzle: Puzzle<1, 1, 5, 5, 2> = Puzzle::with_blocked(&[DOMINO, DOMINO], None, &[0]);
+        let near = PlacedPiece {
+            piece_index: 0,
+            positions: [(0, 0, 1), (0, 0, 2), (0, 0, 0), (0, 0, 0), (0, 0, 0)],
+            cube_count: 2,
+        };
+        // the mirror image of `near`, which would put the blocked cell at the far end
+        let far = PlacedPiece {
+            positions: [(0, 0, 2), (0, 0, 3), (0, 0, 0), (0, 0, 0), (0, 0, 0)],
+            ..near
+        };
+        assert_ne!(puzzle.canonical_key(&[near]), puzzle.canonical_key(&[far]));
+    }
+
+    #[test]
+    fn test_solve_with_fixed_keeps_pinned_piece() {
+        let pinned = SOMA_PUZZLE.solve(Some(1))[0][0];
+        let solutions = SOMA_PUZZLE.solve_with_fixed(&[pinned], None);
+        assert!(!solutions.is_empty());
+
+        for solution in &solutions {
+            assert_eq!(solution.len(), 7);
+            assert!(solution.iter().any(|placed| {
+                placed.piece_index == pinned.piece_index
+                    && placed.sorted_cubes() == pinned.sorted_cubes()
+            }));
+        }
+    }
+
+    #[test]
+    fn test_solutions_containing_keep_the_pinned_cubes() {
+        let pinned = SOMA_PUZZLE.solve(Some(3))[2][4];
+        let solutions = SOMA_PUZZLE.solutions_containing(pinned, None);
+        assert!(!solutions.is_empty());
+        for solution in &solutions {
+            let placed = solution
+                .iter()
+                .find(|placed| placed.piece_index == pinned.piece_index)
+                .unwrap();
+            assert_eq!(placed.sorted_cubes(), pinned.sorted_cubes());
+        }
+        assert_eq!(SOMA_PUZZLE.solutions_containing(pinned, Some(1)).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "fixed piece overlaps an occupied cell")]
+    fn test_solve_with_fixed_rejects_overlap() {
+        let solution = SOMA_PUZZLE.solve(Some(1)).remove(0);
+        let mut clash = solution[1];
+        clash.positions[0] = solution[0].positions[0];
+        SOMA_PUZZLE.solve_with_fixed(&[solution[0], clash], None);
+    }
+
+    #[test]
+    fn test_is_completable() {
+        let solution = SOMA_PUZZLE.solve(Some(1)).remove(0);
+        assert!(SOMA_PUZZLE.is_completable(&[]));
+        assert!(SOMA_PUZZLE.is_completable(&solution[..3]));
+        assert!(SOMA_PUZZLE.is_completable(&solution));
+
+        // a domino one cell in from the end of a row strands that cell
+        let row: Puzzle<1, 1, 6, 6, 3> = Puzzle::new(&[DOMINO, DOMINO, DOMINO], None);
+        let mut positions = [(0, 0, 0); MAX_CUBES];
+        positions[1] = (0, 0, 1);
+        let end = PlacedPiece {
+            piece_index: 0,
+            positions,
+            cube_count: 2,
+        };
+        assert!(row.is_completable(&[end]));
+        positions[0] = (0, 0, 2);
+        let inset = PlacedPiece { positions, ..end };
+        assert!(!row.is_completable(&[inset]));
+
+        // invalid input is rejected rather than panicking
+        let mut clash = solution[1];
+        clash.positions[0] = solution[0].positions[0];
+        assert!(!SOMA_PUZZLE.is_completable(&[solution[0], clash]));
+        assert!(!SOMA_PUZZLE.is_completable(&[solution[0], solution[0]]));
+        let mut outside = solution[0];
+        outside.positions[0] = (3, 0, 0);
+        assert!(!SOMA_PUZZLE.is_completable(&[outside]));
+        let mut oversized = solution[0];
+        oversized.cube_count = u8::MAX;
+        assert!(!SOMA_PUZZLE.is_completable(&[oversized]));
+    }
+
+    #[test]
+    fn test_parallel_respects_limit() {
+        for limit in [1, 5, 17] {
+            let solutions = SOMA_PUZZLE.solve(Some(limit));
+            assert_eq!(solutions.len(), limit, "limit {limit} not honored");
+
+            let unique: FxHashSet<_> = solutions
+                .iter()
+                .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+                .collect();
+            assert_eq!(unique.len(), limit, "limit {limit} returned duplicates");
+        }
+    }
+
+    #[test]
+    fn test_placement_table_stats_count_each_placement_once() {
+        let row: Puzzle<1, 1, 6, 6, 3> = Puzzle::new(&[DOMINO, DOMINO, DOMINO], None);
+        let stats = row.placement_table_stats();
+        assert_eq!(stats.placements_per_piece, [5, 5, 5]);
+        assert_eq!(stats.total_placements, 15);
+        assert_eq!(stats.table_entries, 30);
+        let entries = 30 * std::mem::size_of::<super::Placement<u32>>();
+        assert!(stats.approximate_bytes > entries);
+
+        let soma = SOMA_PUZZLE.placement_table_stats();
+        assert_eq!(soma, SOMA_PUZZLE.prepare().placement_table_stats());
+        assert_eq!(
+            soma.placements_per_piece.iter().sum::<usize>(),
+            soma.total_placements
+        );
+        // the small L has three cubes; the other pieces have four
+        assert_eq!(
+            soma.table_entries,
+            4 * soma.total_placements - soma.placements_per_piece[3]
+        );
+    }
+
+    #[test]
+    fn test_limited_solve_is_deterministic() {
+        let grids = |solutions: &[Vec<PlacedPiece>]| -> Vec<[u8; 27]> {
+            solutions
+                .iter()
+                .map(|solution| crate::grid::solution_to_grid::<3, 3, 3, 27>(solution))
+                .collect()
+        };
+        let all = grids(&SOMA_PUZZLE.solve(None));
+        for max in [1, 5, 100] {
+            for _ in 0..5 {
+                assert_eq!(grids(&SOMA_PUZZLE.solve(Some(max))), all[..max]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_limit_skips_the_search() {
+        // a Bedlam search would take about a minute to reach its first fill
+        let (solutions, stats) = BEDLAM_PUZZLE.solve_with_stats(Some(0));
+        assert!(solutions.is_empty());
+        assert_eq!(stats, SolveStats::default());
+        assert!(BEDLAM_PUZZLE.solve_sequential(Some(0)).is_empty());
+        assert!(BEDLAM_PUZZLE.solve_all(Some(0)).is_empty());
+        assert!(SOMA_PUZZLE.prepare().solve(Some(0)).is_empty());
+
+        assert_eq!(SOMA_PUZZLE.solve(Some(1)).len(), 1);
+    }
 }
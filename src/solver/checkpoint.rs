@@ -0,0 +1,384 @@
+//! Checkpointing for long sequential solves.
+//!
+//! A checkpoint holds everything the sequential search needs to carry on: the
+//! solutions found so far, the explicit DFS stack and the dedup set. It is
+//! written gzip-compressed, in this layout (little endian):
+//! - 4 bytes: magic (`BLKC`)
+//! - u8: format version
+//! - 3 bytes: box extents (x, y, z)
+//! - u8: puzzle grid size
+//! - u8: puzzle piece count
+//! - u64: checkpoint interval in milliseconds
+//! - u32: solution count, then the puzzle's piece count of placed pieces each
+//! - u32: stack frame count, then per frame, bottom of the stack first:
+//!   - u8: placed piece count, then that many placed pieces
+//!   - u8: next position to scan in the search's piece order
+//!   - u32: next placement index for that piece
+//! - u32: seen state count, then grid size bytes per canonical key
+//!
+//! A placed piece is a u8 piece index, a u8 cube count and 3 bytes (x, y, z)
+//! per cube. Occupied cells and remaining pieces are rebuilt from the placed
+//! pieces rather than stored.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use super::{CellMask, Search, WordMask, WIDE_MASK_WORDS};
+use crate::persistence::temp_path;
+use crate::pieces::{PlacedPiece, Puzzle, MAX_CUBES};
+
+const CHECKPOINT_MAGIC: [u8; 4] = *b"BLKC";
+const CHECKPOINT_VERSION: u8 = 2;
+
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+    > Puzzle<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>
+{
+    /// Finds unique solutions like [`solve_sequential`](Self::solve_sequential),
+    /// writing the search state to `checkpoint` every `interval`.
+    ///
+    /// If the run is interrupted, [`resume_from`](Self::resume_from) picks it
+    /// up from the last checkpoint. The file is removed once the search
+    /// completes.
+    pub fn solve_resumable(
+        &self,
+        checkpoint: &Path,
+        interval: Duration,
+    ) -> io::Result<Vec<Vec<PlacedPiece>>> {
+        if GRID_SIZE <= 32 {
+            self.run_checkpointed(
+                self.search_from_start::<u32>(),
+                Vec::new(),
+                checkpoint,
+                interval,
+            )
+        } else if GRID_SIZE <= 64 {
+            self.run_checkpointed(
+                self.search_from_start::<u64>(),
+                Vec::new(),
+                checkpoint,
+                interval,
+            )
+        } else if GRID_SIZE <= 128 {
+            self.run_checkpointed(
+                self.search_from_start::<u128>(),
+                Vec::new(),
+                checkpoint,
+                interval,
+            )
+        } else {
+            self.run_checkpointed(
+                self.search_from_start::<WordMask<WIDE_MASK_WORDS>>(),
+                Vec::new(),
+                checkpoint,
+                interval,
+            )
+        }
+    }
+
+    /// Continues a solve from a checkpoint written by
+    /// [`solve_resumable`](Self::solve_resumable).
+    ///
+    /// Returns every solution of the run, including those found before the
+    /// checkpoint, so the result matches an uninterrupted solve. Keeps
+    /// checkpointing at the original interval.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the checkpoint is corrupt
+    /// or was written for a different puzzle.
+    pub fn resume_from(&self, checkpoint: &Path) -> io::Result<Vec<Vec<PlacedPiece>>> {
+        if GRID_SIZE <= 32 {
+            self.resume_with_mask::<u32>(checkpoint)
+        } else if GRID_SIZE <= 64 {
+            self.resume_with_mask::<u64>(checkpoint)
+        } else if GRID_SIZE <= 128 {
+            self.resume_with_mask::<u128>(checkpoint)
+        } else {
+            self.resume_with_mask::<WordMask<WIDE_MASK_WORDS>>(checkpoint)
+        }
+    }
+
+    fn resume_with_mask<M: CellMask>(
+        &self,
+        checkpoint: &Path,
+    ) -> io::Result<Vec<Vec<PlacedPiece>>> {
+        let mut file = GzDecoder::new(BufReader::new(File::open(checkpoint)?));
+        let (search, solutions, interval) = self.read_checkpoint::<M>(&mut file)?;
+        self.run_checkpointed(search, solutions, checkpoint, interval)
+    }
+
+    /// Drives `search` to completion, pausing every `interval` to checkpoint.
+    fn run_checkpointed<M: CellMask>(
+        &self,
+        mut search: Search<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, M>,
+        mut solutions: Vec<Vec<PlacedPiece>>,
+        checkpoint: &Path,
+        interval: Duration,
+    ) -> io::Result<Vec<Vec<PlacedPiece>>> {
+        loop {
+            search.pause_at = Some(Instant::now() + interval);
+            solutions.extend(search.by_ref());
+            // an empty stack means the search finished rather than paused
+            if search.search_stack.is_empty() {
+                break;
+            }
+            search.write_checkpoint(&solutions, checkpoint, interval)?;
+        }
+
+        match std::fs::remove_file(checkpoint) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(solutions),
+        }
+    }
+
+    /// Rebuilds a paused search from a decoded checkpoint stream.
+    fn read_checkpoint<M: CellMask>(
+        &self,
+        file: &mut impl Read,
+    ) -> io::Result<(
+        Search<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, M>,
+        Vec<Vec<PlacedPiece>>,
+        Duration,
+    )> {
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        let mut header = [0u8; 6];
+        file.read_exact(&mut header)?;
+        if magic != CHECKPOINT_MAGIC || header != Self::checkpoint_header() {
+            return Err(invalid_data("not a checkpoint for this puzzle"));
+        }
+        let interval = Duration::from_millis(read_u64(file)?);
+
+        let solution_count = read_u32(file)?;
+        let mut solutions = Vec::new();
+        for _ in 0..solution_count {
+            let solution = (0..self.num_pieces())
+                .map(|_| read_placed(file))
+                .collect::<io::Result<Vec<_>>>()?;
+            // seeding validates indices, bounds and overlaps
+            self.try_seeded_state::<M>(&solution)
+                .map_err(|message| invalid_data(&message))?;
+            solutions.push(solution);
+        }
+
+        let frame_count = read_u32(file)?;
+        let mut search_stack = Vec::new();
+        for _ in 0..frame_count {
+            let placed_count = read_u8(file)? as usize;
+            if placed_count > self.num_pieces() {
+                return Err(invalid_data("stack frame has too many pieces"));
+            }
+            let placed = (0..placed_count)
+                .map(|_| read_placed(file))
+                .collect::<io::Result<Vec<_>>>()?;
+            let mut frame = self
+                .try_seeded_state::<M>(&placed)
+                .map_err(|message| invalid_data(&message))?;
+            frame.current_piece_index = read_u8(file)? as usize;
+            frame.current_orientation_index = read_u32(file)? as usize;
+            search_stack.push(frame);
+        }
+
+        let placement_table = Self::build_placement_table::<M>(&self.piece_slots());
+        let mut search = Search::new(self, Cow::Owned(placement_table), self.initial_state());
+        search.search_stack = search_stack;
+
+        let seen_count = read_u32(file)?;
+        for _ in 0..seen_count {
+            let mut key = [0u8; GRID_SIZE];
+            file.read_exact(&mut key)?;
+            search.seen_states.insert(&key);
+        }
+        Ok((search, solutions, interval))
+    }
+
+    /// Version and puzzle shape, written after the magic.
+    fn checkpoint_header() -> [u8; 6] {
+        [
+            CHECKPOINT_VERSION,
+            DIM_X as u8,
+            DIM_Y as u8,
+            DIM_Z as u8,
+            GRID_SIZE as u8,
+            NUM_PIECES as u8,
+        ]
+    }
+}
+
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+        M: CellMask,
+    > Search<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES, M>
+{
+    /// Writes this search's state and `solutions` to `path`.
+    ///
+    /// Goes through a temporary file and a rename, so an interruption while
+    /// writing leaves the previous checkpoint intact.
+    fn write_checkpoint(
+        &self,
+        solutions: &[Vec<PlacedPiece>],
+        path: &Path,
+        interval: Duration,
+    ) -> io::Result<()> {
+        let temp_path = temp_path(path);
+        let mut file = GzEncoder::new(
+            BufWriter::new(File::create(&temp_path)?),
+            Compression::fast(),
+        );
+
+        file.write_all(&CHECKPOINT_MAGIC)?;
+        file.write_all(&Puzzle::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>::checkpoint_header())?;
+        file.write_all(&(interval.as_millis() as u64).to_le_bytes())?;
+
+        file.write_all(&(solutions.len() as u32).to_le_bytes())?;
+        for placed in solutions.iter().flatten() {
+            write_placed(&mut file, placed)?;
+        }
+
+        file.write_all(&(self.search_stack.len() as u32).to_le_bytes())?;
+        for frame in &self.search_stack {
+            file.write_all(&[frame.placed_count as u8])?;
+            for placed in &frame.placed_pieces[..frame.placed_count] {
+                write_placed(&mut file, placed)?;
+            }
+            file.write_all(&[frame.current_piece_index as u8])?;
+            file.write_all(&(frame.current_orientation_index as u32).to_le_bytes())?;
+        }
+
+        file.write_all(&(self.seen_states.len() as u32).to_le_bytes())?;
+        for key in self.seen_states.keys() {
+            file.write_all(&key)?;
+        }
+
+        file.finish()?.flush()?;
+        std::fs::rename(&temp_path, path)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_placed(file: &mut impl Write, placed: &PlacedPiece) -> io::Result<()> {
+    file.write_all(&[placed.piece_index as u8, placed.cube_count])?;
+    for &(x, y, z) in placed.cubes() {
+        file.write_all(&[x as u8, y as u8, z as u8])?;
+    }
+    Ok(())
+}
+
+fn read_placed(file: &mut impl Read) -> io::Result<PlacedPiece> {
+    let piece_index = read_u8(file)? as usize;
+    let cube_count = read_u8(file)?;
+    if cube_count as usize > MAX_CUBES {
+        return Err(invalid_data("placed piece has too many cubes"));
+    }
+
+    let mut positions = [(0, 0, 0); MAX_CUBES];
+    for position in positions.iter_mut().take(cube_count as usize) {
+        let mut coord = [0u8; 3];
+        file.read_exact(&mut coord)?;
+        *position = (coord[0] as i32, coord[1] as i32, coord[2] as i32);
+    }
+
+    Ok(PlacedPiece {
+        piece_index,
+        positions,
+        cube_count,
+    })
+}
+
+fn read_u8(file: &mut impl Read) -> io::Result<u8> {
+    let mut buffer = [0u8; 1];
+    file.read_exact(&mut buffer)?;
+    Ok(buffer[0])
+}
+
+fn read_u32(file: &mut impl Read) -> io::Result<u32> {
+    let mut buffer = [0u8; 4];
+    file.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_u64(file: &mut impl Read) -> io::Result<u64> {
+    let mut buffer = [0u8; 8];
+    file.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::grid::solution_to_grid;
+    use crate::pieces::{Coord, SOMA_PUZZLE};
+
+    fn checkpoint_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("blocker-{name}-{}.ckpt", std::process::id()))
+    }
+
+    fn grids(solutions: &[Vec<PlacedPiece>]) -> Vec<[u8; 27]> {
+        solutions
+            .iter()
+            .map(|solution| solution_to_grid::<3, 3, 3, 27>(solution))
+            .collect()
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted_run() {
+        let path = checkpoint_path("resume");
+
+        // interrupt a search after 50 solutions
+        let mut search = SOMA_PUZZLE.search_from_start::<u32>();
+        let early: Vec<_> = search.by_ref().take(50).collect();
+        search
+            .write_checkpoint(&early, &path, Duration::from_secs(60))
+            .unwrap();
+
+        let resumed = SOMA_PUZZLE.resume_from(&path).unwrap();
+        assert_eq!(grids(&resumed), grids(&SOMA_PUZZLE.solve_sequential(None)));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_solve_resumable_checkpoints_and_cleans_up() {
+        let path = checkpoint_path("resumable");
+        let solutions = SOMA_PUZZLE
+            .solve_resumable(&path, Duration::from_millis(200))
+            .unwrap();
+        assert_eq!(solutions.len(), 240);
+        assert!(!path.exists());
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[test]
+    fn test_resume_rejects_other_puzzles() {
+        const DOMINO: &[Coord] = &[(0, 0, 0), (1, 0, 0)];
+        let path = checkpoint_path("other-puzzle");
+
+        let search = SOMA_PUZZLE.search_from_start::<u32>();
+        search
+            .write_checkpoint(&[], &path, Duration::from_secs(60))
+            .unwrap();
+
+        let rectangle: Puzzle<1, 2, 3, 6, 3> = Puzzle::new(&[DOMINO, DOMINO, DOMINO], None);
+        let result = rectangle.resume_from(&path);
+        assert!(result.is_err_and(|error| error.kind() == io::ErrorKind::InvalidData));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+//! Exact-cover backend using Knuth's Algorithm X with dancing links.
+//!
+//! Columns are the box's open cells, which a solution must cover exactly once,
+//! plus one column per piece, covered at most once so no piece is reused. Each
+//! row is one placement from the placement table. Algorithm X enumerates every
+//! fill, symmetric copies included, so solutions are deduplicated afterward by
+//! canonical key.
+
+use rustc_hash::FxHashSet;
+
+use super::{CellMask, WordMask, WIDE_MASK_WORDS};
+use crate::grid::coord_to_idx;
+use crate::pieces::{PlacedPiece, Puzzle};
+
+/// Header of the circular list of uncovered primary columns.
+const ROOT: usize = 0;
+
+/// Sparse exact-cover matrix as four-way linked nodes stored in flat arrays.
+///
+/// Indices below the header count are column headers (with [`ROOT`] first);
+/// the rest are one node per nonzero matrix entry.
+struct Links {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    // column header of each node
+    column: Vec<usize>,
+    // matrix row of each node (unused for headers)
+    row: Vec<usize>,
+    // rows still linked into each column, indexed by header
+    size: Vec<usize>,
+}
+
+impl Links {
+    /// Creates an empty matrix with columns `1..=primary` that must be covered
+    /// and `secondary` columns after them that may be covered at most once.
+    fn new(primary: usize, secondary: usize) -> Self {
+        let headers = 1 + primary + secondary;
+        let mut links = Self {
+            left: (0..headers).collect(),
+            right: (0..headers).collect(),
+            up: (0..headers).collect(),
+            down: (0..headers).collect(),
+            column: (0..headers).collect(),
+            row: vec![usize::MAX; headers],
+            size: vec![0; headers],
+        };
+
+        // only primary headers join the root's list; secondary ones stay
+        // self-linked so the search never needs to cover them
+        let ring = primary + 1;
+        for header in 0..ring {
+            links.right[header] = (header + 1) % ring;
+            links.left[header] = (header + primary) % ring;
+        }
+        links
+    }
+
+    /// Appends a row with a one in each of `columns`.
+    fn add_row(&mut self, row: usize, columns: &[usize]) {
+        let first = self.left.len();
+        for (offset, &column) in columns.iter().enumerate() {
+            let node = first + offset;
+
+            // insert at the bottom of the column
+            let last = self.up[column];
+            self.up.push(last);
+            self.down.push(column);
+            self.down[last] = node;
+            self.up[column] = node;
+            self.column.push(column);
+            self.row.push(row);
+            self.size[column] += 1;
+
+            // circular list across the row
+            let previous = if offset == 0 {
+                first + columns.len() - 1
+            } else {
+                node - 1
+            };
+            let next = if offset + 1 == columns.len() {
+                first
+            } else {
+                node + 1
+            };
+            self.left.push(previous);
+            self.right.push(next);
+        }
+    }
+
+    /// Removes `column` and every row that intersects it.
+    fn cover(&mut self, column: usize) {
+        let (left, right) = (self.left[column], self.right[column]);
+        self.right[left] = right;
+        self.left[right] = left;
+
+        let mut row_node = self.down[column];
+        while row_node != column {
+            let mut node = self.right[row_node];
+            while node != row_node {
+                let (up, down) = (self.up[node], self.down[node]);
+                self.down[up] = down;
+                self.up[down] = up;
+                self.size[self.column[node]] -= 1;
+                node = self.right[node];
+            }
+            row_node = self.down[row_node];
+        }
+    }
+
+    /// Restores `column` and its rows, undoing [`cover`](Self::cover).
+    fn uncover(&mut self, column: usize) {
+        let mut row_node = self.up[column];
+        while row_node != column {
+            let mut node = self.left[row_node];
+            while node != row_node {
+                let (up, down) = (self.up[node], self.down[node]);
+                self.down[up] = node;
+                self.up[down] = node;
+                self.size[self.column[node]] += 1;
+                node = self.left[node];
+            }
+            row_node = self.up[row_node];
+        }
+
+        let (left, right) = (self.left[column], self.right[column]);
+        self.right[left] = column;
+        self.left[right] = column;
+    }
+
+    /// Runs Algorithm X, calling `on_solution` with the chosen rows of each
+    /// exact cover. Returns `false` once `on_solution` asks to stop.
+    fn search(
+        &mut self,
+        chosen: &mut Vec<usize>,
+        on_solution: &mut impl FnMut(&[usize]) -> bool,
+    ) -> bool {
+        if self.right[ROOT] == ROOT {
+            return on_solution(chosen);
+        }
+
+        // branch on the column with the fewest candidate rows
+        let mut best = self.right[ROOT];
+        let mut column = self.right[best];
+        while column != ROOT {
+            if self.size[column] < self.size[best] {
+                best = column;
+            }
+            column = self.right[column];
+        }
+        if self.size[best] == 0 {
+            return true;
+        }
+
+        self.cover(best);
+        let mut keep_going = true;
+        let mut row_node = self.down[best];
+        while keep_going && row_node != best {
+            chosen.push(self.row[row_node]);
+            let mut node = self.right[row_node];
+            while node != row_node {
+                self.cover(self.column[node]);
+                node = self.right[node];
+            }
+
+            keep_going = self.search(chosen, on_solution);
+
+            let mut node = self.left[row_node];
+            while node != row_node {
+                self.uncover(self.column[node]);
+                node = self.left[node];
+            }
+            chosen.pop();
+            row_node = self.down[row_node];
+        }
+        self.uncover(best);
+
+        keep_going
+    }
+}
+
+impl<
+        const DIM_X: usize,
+        const DIM_Y: usize,
+        const DIM_Z: usize,
+        const GRID_SIZE: usize,
+        const NUM_PIECES: usize,
+    > Puzzle<'_, DIM_X, DIM_Y, DIM_Z, GRID_SIZE, NUM_PIECES>
+{
+    /// Finds unique solutions with dancing links, up to an optional limit.
+    ///
+    /// Finds the same solutions as [`solve`](Self::solve) up to symmetry,
+    /// though the order and the representative of each may differ. Runs on the
+    /// current thread.
+    pub fn solve_dlx(&self, max_solutions: Option<usize>) -> Vec<Vec<PlacedPiece>> {
+        if GRID_SIZE <= 32 {
+            self.solve_dlx_with_mask::<u32>(max_solutions)
+        } else if GRID_SIZE <= 64 {
+            self.solve_dlx_with_mask::<u64>(max_solutions)
+        } else if GRID_SIZE <= 128 {
+            self.solve_dlx_with_mask::<u128>(max_solutions)
+        } else {
+            self.solve_dlx_with_mask::<WordMask<WIDE_MASK_WORDS>>(max_solutions)
+        }
+    }
+
+    fn solve_dlx_with_mask<M: CellMask>(
+        &self,
+        max_solutions: Option<usize>,
+    ) -> Vec<Vec<PlacedPiece>> {
+        if max_solutions == Some(0) {
+            return Vec::new();
+        }
+
+        // number the open cells; blocked cells get no column
+        let mut cell_columns = [None; GRID_SIZE];
+        let mut open_cells = 0;
+        for (cell, column) in cell_columns.iter_mut().enumerate() {
+            if !self.blocked_cells.contains(&cell) {
+                open_cells += 1;
+                *column = Some(open_cells);
+            }
+        }
+
+        let placement_table = Self::build_placement_table::<M>(&self.piece_slots());
+        let mut links = Links::new(open_cells, self.num_pieces());
+        let mut rows: Vec<PlacedPiece> = Vec::new();
+        for (piece_index, piece_placements) in placement_table.iter().enumerate() {
+            for (target_cell, placements) in piece_placements.iter().enumerate() {
+                for placement in placements {
+                    let cubes = &placement.cube_positions[..placement.cube_count as usize];
+                    let cells = cubes
+                        .iter()
+                        .map(|&(x, y, z)| coord_to_idx::<DIM_Y, DIM_Z>(x, y, z));
+
+                    // the table lists a placement under each of its cells;
+                    // keep only the copy filed under the lowest one
+                    if cells.clone().min() != Some(target_cell) {
+                        continue;
+                    }
+                    // no column means the placement covers a blocked cell
+                    let Some(mut row_columns) = cells
+                        .map(|cell| cell_columns[cell])
+                        .collect::<Option<Vec<_>>>()
+                    else {
+                        continue;
+                    };
+                    row_columns.push(open_cells + 1 + piece_index);
+
+                    links.add_row(rows.len(), &row_columns);
+                    rows.push(PlacedPiece {
+                        piece_index,
+                        positions: placement.cube_positions,
+                        cube_count: placement.cube_count,
+                    });
+                }
+            }
+        }
+
+        let mut seen: FxHashSet<[u8; GRID_SIZE]> = FxHashSet::default();
+        let mut solutions = Vec::new();
+        links.search(&mut Vec::with_capacity(NUM_PIECES), &mut |chosen| {
+            let solution: Vec<PlacedPiece> = chosen.iter().map(|&row| rows[row]).collect();
+            if seen.insert(self.canonical_key(&solution)) {
+                solutions.push(solution);
+            }
+            max_solutions.is_none_or(|max| solutions.len() < max)
+        });
+        solutions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hash::FxHashSet;
+
+    use crate::pieces::{Coord, Puzzle, SOMA_PUZZLE};
+
+    #[test]
+    fn test_dlx_matches_bitmask_solver() {
+        let keys = |solutions: Vec<Vec<_>>| -> FxHashSet<[u8; 27]> {
+            solutions
+                .iter()
+                .map(|solution| SOMA_PUZZLE.canonical_key(solution))
+                .collect()
+        };
+        let dlx = SOMA_PUZZLE.solve_dlx(None);
+        assert_eq!(dlx.len(), 240);
+        assert_eq!(keys(dlx), keys(SOMA_PUZZLE.solve(None)));
+
+        assert_eq!(SOMA_PUZZLE.solve_dlx(Some(7)).len(), 7);
+    }
+
+    #[test]
+    fn test_dlx_skips_blocked_cells() {
+        const DOMINO: &[Coord] = &[(0, 0, 0), (1, 0, 0)];
+        let rectangle: Puzzle<1, 2, 3, 6, 3> = Puzzle::new(&[DOMINO, DOMINO, DOMINO], None);
+        assert_eq!(rectangle.solve_dlx(None).len(), 6);
+
+        let row: Puzzle<1, 1, 5, 5, 2> = Puzzle::with_blocked(&[DOMINO, DOMINO], None, &[2]);
+        let solutions = row.solve_dlx(None);
+        assert_eq!(solutions.len(), row.solve(None).len());
+        assert!(solutions
+            .iter()
+            .flatten()
+            .all(|placed| !placed.cubes().contains(&(0, 0, 2))));
+    }
+}
@@ -1,32 +1,37 @@
 //! 3D visualization of puzzle solutions using kiss3d.
 
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat};
 use kiss3d::prelude::*;
 
+use blocker::colors::{self, Rgb};
 use blocker::grid::solution_to_grid;
 use blocker::pieces::PlacedPiece;
 
-/// Returns a distinct color for a piece index by spacing hues evenly.
-fn piece_color(piece_index: usize, num_pieces: usize) -> Color {
-    let hue = (piece_index as f32) / (num_pieces as f32);
-
-    // HSL to RGB with saturation=0.8, lightness=0.5
-    let s: f32 = 0.8;
-    let l: f32 = 0.5;
-    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
-    let h_prime = hue * 6.0;
-    let x = c * (1.0 - (h_prime % 2.0 - 1.0).abs());
-    let m = l - c / 2.0;
-
-    let (r, g, b) = match h_prime as u32 {
-        0 => (c, x, 0.0),
-        1 => (x, c, 0.0),
-        2 => (0.0, c, x),
-        3 => (0.0, x, c),
-        4 => (x, 0.0, c),
-        _ => (c, 0.0, x),
-    };
+/// Viewer key bindings, for printing before the window opens.
+pub const CONTROLS: &str = "Left/Right navigate, digits then Enter jump to a solution, \
+     Home/End first/last, [/] show one piece at a time, -/= widen/narrow the gaps, \
+     F wireframe, numpad 1-6 view along +X/-X/+Y/-Y/+Z/-Z, C compare two solutions \
+     (A/D navigate the second), W/S explode, R reset";
 
-    Color::new(r + m, g + m, b + m, 1.0)
+/// Edge length of a rendered cube in cell units; the rest of each cell is
+/// the gap between neighbouring cubes.
+const DEFAULT_CUBE_SIZE: f32 = 0.9;
+
+/// Converts a library color to a kiss3d one.
+pub fn to_color([r, g, b]: Rgb) -> Color {
+    Color::new(r, g, b, 1.0)
+}
+
+/// Returns the display color for a piece: its `palette` entry, or a computed
+/// hue when the palette does not cover every piece.
+fn piece_color(palette: &[Color], piece_index: usize, num_pieces: usize) -> Color {
+    if palette.len() >= num_pieces {
+        palette[piece_index]
+    } else {
+        to_color(colors::piece_color(piece_index, num_pieces))
+    }
 }
 
 /// Represents a rendered cube in the 3D scene.
@@ -41,15 +46,24 @@ struct RenderedCube {
 
 /// Builds the 3D scene for a solution.
 ///
-/// Grid is centered at the origin by offsetting positions by -(DIM-1)/2.
-fn build_scene<const DIM: usize, const GRID_SIZE: usize>(
+/// Grid is centered at the origin by offsetting each axis by -(extent-1)/2.
+/// Each cube is `cube_size` across, leaving a gap of `1 - cube_size`, and
+/// the whole solution is moved by `shift` so two can sit side by side.
+fn build_scene<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
     scene: &mut SceneNode3d,
     solution: &[PlacedPiece],
     num_pieces: usize,
+    palette: &[Color],
+    cube_size: f32,
+    shift: Vec3,
 ) -> (Vec<RenderedCube>, std::collections::HashMap<usize, Vec3>) {
-    const CUBE_SIZE: f32 = 0.9;
     const CELL_SPACING: f32 = 1.0;
-    let center_offset: f32 = -((DIM as f32) - 1.0) / 2.0;
+    let center_offset = shift - box_center::<DIM_X, DIM_Y, DIM_Z>();
 
     // compute piece centroids for explosion animation
     let mut piece_centroids: std::collections::HashMap<usize, Vec3> =
@@ -63,23 +77,20 @@ fn build_scene<const DIM: usize, const GRID_SIZE: usize>(
         piece_centroids.insert(placed.piece_index, position_sum / placed.cube_count as f32);
     }
 
-    let grid = solution_to_grid::<DIM, GRID_SIZE>(solution);
+    let grid = solution_to_grid::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(solution);
 
     let mut rendered_cubes = Vec::new();
-    for x in 0..DIM {
-        for y in 0..DIM {
-            for z in 0..DIM {
-                let piece_number = grid[x * DIM * DIM + y * DIM + z];
+    for x in 0..DIM_X {
+        for y in 0..DIM_Y {
+            for z in 0..DIM_Z {
+                let piece_number = grid[x * DIM_Y * DIM_Z + y * DIM_Z + z];
                 if piece_number > 0 {
                     let piece_index = (piece_number - 1) as usize;
-                    let base_position = Vec3::new(
-                        x as f32 * CELL_SPACING + center_offset,
-                        y as f32 * CELL_SPACING + center_offset,
-                        z as f32 * CELL_SPACING + center_offset,
-                    );
+                    let base_position =
+                        Vec3::new(x as f32, y as f32, z as f32) * CELL_SPACING + center_offset;
                     let node = scene
-                        .add_cube(CUBE_SIZE, CUBE_SIZE, CUBE_SIZE)
-                        .set_color(piece_color(piece_index, num_pieces))
+                        .add_cube(cube_size, cube_size, cube_size)
+                        .set_color(piece_color(palette, piece_index, num_pieces))
                         .set_position(base_position);
                     rendered_cubes.push(RenderedCube {
                         node,
@@ -94,17 +105,236 @@ fn build_scene<const DIM: usize, const GRID_SIZE: usize>(
     (rendered_cubes, piece_centroids)
 }
 
-/// Displays all solutions in an interactive 3D viewer.
-pub fn display<const DIM: usize, const GRID_SIZE: usize>(
+/// Center of the box in solver coordinates.
+fn box_center<const DIM_X: usize, const DIM_Y: usize, const DIM_Z: usize>() -> Vec3 {
+    Vec3::new(DIM_X as f32 - 1.0, DIM_Y as f32 - 1.0, DIM_Z as f32 - 1.0) / 2.0
+}
+
+/// An orbit camera far enough out to frame the whole box.
+fn box_camera<const DIM_X: usize, const DIM_Y: usize, const DIM_Z: usize>() -> OrbitCamera3d {
+    let mut camera = OrbitCamera3d::default();
+    camera.set_dist(DIM_X.max(DIM_Y).max(DIM_Z) as f32 * 2.5);
+    camera
+}
+
+/// An empty scene with the viewer's point light.
+fn lit_scene() -> SceneNode3d {
+    let mut scene = SceneNode3d::empty();
+    scene
+        .add_light(Light::point(100.0))
+        .set_position(Vec3::new(5.0, 5.0, 5.0));
+    scene
+}
+
+/// Moves every piece out from the box center along the line through its
+/// centroid, `amount` cells per unit of explosion.
+fn explode(
+    cubes: &mut [RenderedCube],
+    piece_centroids: &std::collections::HashMap<usize, Vec3>,
+    grid_center: Vec3,
+    amount: f32,
+) {
+    for cube in cubes {
+        let centroid = piece_centroids.get(&cube.piece_index).unwrap();
+        // move each piece away from center using its centroid direction
+        let explosion_direction = (*centroid - grid_center).normalize_or_zero();
+        cube.node
+            .set_position(cube.base_position + explosion_direction * amount * 2.0);
+    }
+}
+
+/// Opens a window at the given size and hides it straight away, for
+/// rendering frames that never reach the screen.
+async fn hidden_window(width: u32, height: u32) -> Window {
+    let mut window = Window::new_with_size("blocker render", width, height).await;
+    window.hide();
+    window
+}
+
+/// Renders one frame of a solution, as the viewer first shows it, and
+/// returns it encoded as a PNG.
+///
+/// The window is hidden as soon as it is created, so nothing appears on
+/// screen, though kiss3d still needs a display to create its surface.
+/// `camera_angle` turns the camera about the vertical axis, in radians.
+pub fn render_to_png<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    num_pieces: usize,
+    width: u32,
+    height: u32,
+    camera_angle: f32,
+) -> Vec<u8> {
+    let frame = pollster::block_on(render_frame::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        solution,
+        num_pieces,
+        width,
+        height,
+        camera_angle,
+    ));
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(frame)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .expect("PNG encoding into memory cannot fail");
+    png
+}
+
+async fn render_frame<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    num_pieces: usize,
+    width: u32,
+    height: u32,
+    camera_angle: f32,
+) -> image::RgbImage {
+    let mut window = hidden_window(width, height).await;
+    let mut camera = box_camera::<DIM_X, DIM_Y, DIM_Z>();
+    camera.set_yaw(camera_angle);
+    let mut scene = lit_scene();
+    build_scene::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        &mut scene,
+        solution,
+        num_pieces,
+        &[],
+        DEFAULT_CUBE_SIZE,
+        Vec3::ZERO,
+    );
+
+    window.render_3d(&mut scene, &mut camera).await;
+    window.snap_image()
+}
+
+/// Settings for [`export_gif`].
+pub struct GifOptions {
+    /// Frame width in pixels.
+    pub width: u16,
+    /// Frame height in pixels.
+    pub height: u16,
+    /// Camera angle around the vertical axis, in radians.
+    pub camera_angle: f32,
+    /// Number of frames from assembled to fully exploded.
+    pub frames: usize,
+    /// Explosion amount of the last frame, on the scale the viewer's W key
+    /// builds up.
+    pub max_explosion: f32,
+    /// How long each frame shows, in hundredths of a second.
+    pub frame_delay: u16,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self {
+            width: 480,
+            height: 360,
+            camera_angle: 0.0,
+            frames: 30,
+            max_explosion: 1.5,
+            frame_delay: 5,
+        }
+    }
+}
+
+/// Renders the explode animation of a solution as a looping GIF.
+///
+/// The pieces move from assembled to `options.max_explosion` in even steps.
+/// Frames are rendered in a hidden window, as in [`render_to_png`].
+pub fn export_gif<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    num_pieces: usize,
+    options: &GifOptions,
+) -> Result<Vec<u8>, gif::EncodingError> {
+    pollster::block_on(export_gif_async::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        solution, num_pieces, options,
+    ))
+}
+
+async fn export_gif_async<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
+    solution: &[PlacedPiece],
+    num_pieces: usize,
+    options: &GifOptions,
+) -> Result<Vec<u8>, gif::EncodingError> {
+    let (width, height) = (options.width, options.height);
+    let mut window = hidden_window(width.into(), height.into()).await;
+    let mut camera = box_camera::<DIM_X, DIM_Y, DIM_Z>();
+    camera.set_yaw(options.camera_angle);
+    let mut scene = lit_scene();
+    let (mut rendered_cubes, piece_centroids) = build_scene::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        &mut scene,
+        solution,
+        num_pieces,
+        &[],
+        DEFAULT_CUBE_SIZE,
+        Vec3::ZERO,
+    );
+    let grid_center = box_center::<DIM_X, DIM_Y, DIM_Z>();
+
+    let mut gif = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut gif, width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        // the first frame is assembled and the last fully exploded
+        let last_frame = options.frames.saturating_sub(1).max(1);
+        for frame_index in 0..options.frames {
+            let amount = options.max_explosion * frame_index as f32 / last_frame as f32;
+            explode(&mut rendered_cubes, &piece_centroids, grid_center, amount);
+            window.render_3d(&mut scene, &mut camera).await;
+
+            let pixels = window.snap_image();
+            let mut frame = gif::Frame::from_rgb(width, height, pixels.as_raw());
+            frame.delay = options.frame_delay;
+            encoder.write_frame(&frame)?;
+        }
+    }
+    Ok(gif)
+}
+
+/// Displays all solutions in an interactive 3D viewer, with pieces colored
+/// from `palette`.
+///
+/// Every piece gets an evenly spaced hue instead when the palette has fewer
+/// than `num_pieces` colors.
+pub fn display_with_palette<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
     solutions: Vec<Vec<PlacedPiece>>,
     num_pieces: usize,
+    palette: &[Color],
 ) {
-    pollster::block_on(display_async::<DIM, GRID_SIZE>(solutions, num_pieces));
+    pollster::block_on(display_async::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        solutions, num_pieces, palette,
+    ));
 }
 
-async fn display_async<const DIM: usize, const GRID_SIZE: usize>(
+async fn display_async<
+    const DIM_X: usize,
+    const DIM_Y: usize,
+    const DIM_Z: usize,
+    const GRID_SIZE: usize,
+>(
     solutions: Vec<Vec<PlacedPiece>>,
     num_pieces: usize,
+    palette: &[Color],
 ) {
     if solutions.is_empty() {
         println!("No solutions to display");
@@ -114,29 +344,39 @@ async fn display_async<const DIM: usize, const GRID_SIZE: usize>(
     let num_solutions = solutions.len();
     let mut current_solution_index = 0;
 
-    let mut window = Window::new(&format!(
-        "Solution 1/{} - [Left/Right] navigate, [W/S] explode, [R] reset",
-        num_solutions
-    ))
-    .await;
+    // digits typed toward a solution number, shown in the title until Enter
+    let mut typed_index = String::new();
+    let mut window = Window::new(&viewer_title(0, num_solutions, None, None, &typed_index)).await;
 
-    let mut camera = OrbitCamera3d::default();
-    camera.set_dist(DIM as f32 * 2.5);
-
-    let mut scene = SceneNode3d::empty();
-    scene
-        .add_light(Light::point(100.0))
-        .set_position(Vec3::new(5.0, 5.0, 5.0));
+    let mut camera = box_camera::<DIM_X, DIM_Y, DIM_Z>();
+    let mut scene = lit_scene();
 
     // keep center in solver coordinate space for explosion direction math
-    let grid_center_val = (DIM as f32 - 1.0) / 2.0;
-    let grid_center = Vec3::new(grid_center_val, grid_center_val, grid_center_val);
-    let (mut rendered_cubes, mut piece_centroids) =
-        build_scene::<DIM, GRID_SIZE>(&mut scene, &solutions[current_solution_index], num_pieces);
+    let grid_center = box_center::<DIM_X, DIM_Y, DIM_Z>();
+    let (mut rendered_cubes, mut piece_centroids) = build_scene::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+        &mut scene,
+        &solutions[current_solution_index],
+        num_pieces,
+        palette,
+        DEFAULT_CUBE_SIZE,
+        Vec3::ZERO,
+    );
+    // in compare mode, the second solution shown and its cubes
+    let mut compare_index: Option<usize> = None;
+    let mut compare_cubes = Vec::new();
+    let mut compare_centroids = std::collections::HashMap::new();
 
     let mut explosion_amount: f32 = 0.0;
     const EXPLOSION_SPEED: f32 = 0.05;
+    // cube size and wireframe mode outlive rebuilds, so they hold across solutions
+    let mut cube_size = DEFAULT_CUBE_SIZE;
+    const CUBE_SIZE_STEP: f32 = 0.05;
+    let mut wireframe = false;
+    // piece shown alone, or None to show every piece
+    let mut focused_piece: Option<usize> = None;
     let mut needs_rebuild = false;
+    let mut restyle = false;
+    let mut title_changed = false;
     let mut explode_in = false;
     let mut explode_out = false;
 
@@ -160,6 +400,80 @@ async fn display_async<const DIM: usize, const GRID_SIZE: usize>(
                             .unwrap_or(num_solutions - 1);
                         needs_rebuild = true;
                     }
+                    Key::C if pressed => {
+                        compare_index = match compare_index {
+                            None => Some((current_solution_index + 1) % num_solutions),
+                            Some(_) => None,
+                        };
+                        needs_rebuild = true;
+                    }
+                    Key::D if pressed && compare_index.is_some() => {
+                        compare_index = compare_index.map(|index| (index + 1) % num_solutions);
+                        needs_rebuild = true;
+                    }
+                    Key::A if pressed && compare_index.is_some() => {
+                        compare_index = compare_index
+                            .map(|index| index.checked_sub(1).unwrap_or(num_solutions - 1));
+                        needs_rebuild = true;
+                    }
+                    Key::Home if pressed => {
+                        current_solution_index = 0;
+                        needs_rebuild = true;
+                    }
+                    Key::End if pressed => {
+                        current_solution_index = num_solutions - 1;
+                        needs_rebuild = true;
+                    }
+                    Key::RBracket if pressed => {
+                        // past the last piece goes back to showing them all
+                        focused_piece = match focused_piece {
+                            None => Some(0),
+                            Some(piece) if piece + 1 < num_pieces => Some(piece + 1),
+                            Some(_) => None,
+                        };
+                        restyle = true;
+                    }
+                    Key::LBracket if pressed => {
+                        focused_piece = match focused_piece {
+                            None => num_pieces.checked_sub(1),
+                            Some(piece) => piece.checked_sub(1),
+                        };
+                        restyle = true;
+                    }
+                    Key::Minus if pressed => {
+                        cube_size = (cube_size - CUBE_SIZE_STEP).max(0.5);
+                        needs_rebuild = true;
+                    }
+                    Key::Equals if pressed => {
+                        cube_size = (cube_size + CUBE_SIZE_STEP).min(1.0);
+                        needs_rebuild = true;
+                    }
+                    Key::F if pressed => {
+                        wireframe = !wireframe;
+                        restyle = true;
+                    }
+                    Key::Back if pressed => {
+                        typed_index.pop();
+                        title_changed = true;
+                    }
+                    Key::Return if pressed && !typed_index.is_empty() => {
+                        // numbers past the end, however long, land on the last solution
+                        let number = typed_index.parse().unwrap_or(usize::MAX);
+                        current_solution_index = number.clamp(1, num_solutions) - 1;
+                        typed_index.clear();
+                        needs_rebuild = true;
+                    }
+                    _ if pressed => {
+                        if let Some(digit) = key_digit(key) {
+                            typed_index.push(digit);
+                            title_changed = true;
+                        } else if let Some((yaw, pitch)) = axis_view(key) {
+                            // a fresh camera drops any zoom or orbit the user applied
+                            camera = box_camera::<DIM_X, DIM_Y, DIM_Z>();
+                            camera.set_yaw(yaw);
+                            camera.set_pitch(pitch);
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -174,32 +488,147 @@ async fn display_async<const DIM: usize, const GRID_SIZE: usize>(
 
         if needs_rebuild {
             // rebuild cubes only when switching to a different solution
-            for mut cube in rendered_cubes.drain(..) {
+            for mut cube in rendered_cubes.drain(..).chain(compare_cubes.drain(..)) {
                 cube.node.remove();
             }
-            let (new_cubes, new_centroids) =
-                build_scene::<DIM, GRID_SIZE>(&mut scene, &solutions[current_solution_index], num_pieces);
-            rendered_cubes = new_cubes;
-            piece_centroids = new_centroids;
-            window.set_title(&format!(
-                "Solution {}/{} - [Left/Right] navigate, [W/S] explode, [R] reset",
-                current_solution_index + 1,
-                num_solutions
-            ));
+            // side by side, the boxes sit one cell apart either side of the origin
+            let shift = match compare_index {
+                Some(_) => Vec3::new((DIM_X as f32 + 1.0) / 2.0, 0.0, 0.0),
+                None => Vec3::ZERO,
+            };
+            (rendered_cubes, piece_centroids) = build_scene::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+                &mut scene,
+                &solutions[current_solution_index],
+                num_pieces,
+                palette,
+                cube_size,
+                -shift,
+            );
+            if let Some(index) = compare_index {
+                (compare_cubes, compare_centroids) = build_scene::<DIM_X, DIM_Y, DIM_Z, GRID_SIZE>(
+                    &mut scene,
+                    &solutions[index],
+                    num_pieces,
+                    palette,
+                    cube_size,
+                    shift,
+                );
+            }
             needs_rebuild = false;
+            restyle = true;
         }
 
-        for cube in &mut rendered_cubes {
-            let centroid = piece_centroids.get(&cube.piece_index).unwrap();
-            // move each piece away from center using its centroid direction
-            let explosion_direction = (*centroid - grid_center).normalize_or_zero();
-            cube.node.set_position(
-                cube.base_position + explosion_direction * explosion_amount * 2.0,
-            );
+        if restyle {
+            // new nodes come out solid and visible, so rebuilds restyle too
+            for cube in rendered_cubes.iter_mut().chain(&mut compare_cubes) {
+                let shown = focused_piece.is_none_or(|piece| piece == cube.piece_index);
+                cube.node.set_visible(shown);
+                cube.node.set_surface_rendering_activation(!wireframe);
+                cube.node.set_lines_width(if wireframe { 2.0 } else { 0.0 });
+            }
+            restyle = false;
+            title_changed = true;
         }
 
+        if title_changed {
+            window.set_title(&viewer_title(
+                current_solution_index,
+                num_solutions,
+                compare_index,
+                focused_piece,
+                &typed_index,
+            ));
+            title_changed = false;
+        }
+
+        explode(
+            &mut rendered_cubes,
+            &piece_centroids,
+            grid_center,
+            explosion_amount,
+        );
+        explode(
+            &mut compare_cubes,
+            &compare_centroids,
+            grid_center,
+            explosion_amount,
+        );
+
         if !window.render_3d(&mut scene, &mut camera).await {
             break;
         }
     }
 }
+
+/// The viewer's window title: the current solution, any solution compared
+/// with it, any piece shown alone and the controls, or the solution number
+/// being typed.
+fn viewer_title(
+    current_index: usize,
+    num_solutions: usize,
+    compare_index: Option<usize>,
+    focused_piece: Option<usize>,
+    typed_index: &str,
+) -> String {
+    if typed_index.is_empty() {
+        let compare = match compare_index {
+            Some(index) => format!(" vs {}/{num_solutions}", index + 1),
+            None => String::new(),
+        };
+        let focus = match focused_piece {
+            Some(piece) => format!(", piece {} only", piece + 1),
+            None => String::new(),
+        };
+        format!(
+            "Solution {}/{}{compare}{focus} - [Left/Right] navigate, [0-9, Enter] jump, \
+             [ [ ] ] pieces, [-/=] gap, [F] wireframe, [numpad 1-6] views, [C, A/D] compare, \
+             [W/S] explode, [R] reset",
+            current_index + 1,
+            num_solutions
+        )
+    } else {
+        format!(
+            "Go to solution {typed_index}_ of {num_solutions} - [Enter] jump, [Backspace] erase"
+        )
+    }
+}
+
+/// The camera yaw and pitch for a numpad key's axis-aligned view, looking
+/// at the box from +X, -X, +Y, -Y, +Z or -Z for keys 1 to 6.
+///
+/// Pitch is measured from +Y and the orbit camera clamps it just short of
+/// the poles, so the Y views are a hair off axis.
+fn axis_view(key: kiss3d::event::Key) -> Option<(f32, f32)> {
+    use kiss3d::event::Key;
+    use std::f32::consts::{FRAC_PI_2, PI};
+    const POLE: f32 = 0.01;
+    let view = match key {
+        Key::Numpad1 => (0.0, FRAC_PI_2),
+        Key::Numpad2 => (PI, FRAC_PI_2),
+        Key::Numpad3 => (0.0, POLE),
+        Key::Numpad4 => (0.0, PI - POLE),
+        Key::Numpad5 => (FRAC_PI_2, FRAC_PI_2),
+        Key::Numpad6 => (-FRAC_PI_2, FRAC_PI_2),
+        _ => return None,
+    };
+    Some(view)
+}
+
+/// The digit a number key types, if it is one.
+fn key_digit(key: kiss3d::event::Key) -> Option<char> {
+    use kiss3d::event::Key;
+    let digit = match key {
+        Key::Key0 => '0',
+        Key::Key1 => '1',
+        Key::Key2 => '2',
+        Key::Key3 => '3',
+        Key::Key4 => '4',
+        Key::Key5 => '5',
+        Key::Key6 => '6',
+        Key::Key7 => '7',
+        Key::Key8 => '8',
+        Key::Key9 => '9',
+        _ => return None,
+    };
+    Some(digit)
+}
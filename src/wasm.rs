@@ -0,0 +1,102 @@
+//! Entry points for running the solver in a browser.
+//!
+//! On `wasm32` these are exported through `wasm-bindgen`; elsewhere they are
+//! plain functions. Nothing here touches the filesystem. Solutions come back
+//! as JSON in the shape `export-js` prints: one array per solution, holding
+//! `[piece_index, [[x, y, z], ...]]` per piece.
+//!
+//! The searches run on the calling thread, since `wasm32-unknown-unknown`
+//! has neither threads nor a clock for the parallel solver's timing.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::pieces::{PlacedPiece, BEDLAM_PUZZLE, SOMA_PUZZLE};
+use crate::PuzzleOps;
+
+/// Solves a built-in puzzle (`"soma"` or `"bedlam"`) and returns the
+/// solutions as JSON, up to `max_solutions` if given.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = solveJson))]
+pub fn solve_json(puzzle: &str, max_solutions: Option<u32>) -> Result<String, String> {
+    let max_solutions = max_solutions.map(|max| max as usize);
+    let solutions = match puzzle {
+        "soma" => SOMA_PUZZLE.solve_sequential(max_solutions),
+        "bedlam" => BEDLAM_PUZZLE.solve_sequential(max_solutions),
+        _ => return Err(format!("unknown puzzle '{puzzle}'")),
+    };
+    Ok(solutions_to_json(&solutions))
+}
+
+/// Solves a built-in puzzle and returns the solutions as text, each in the
+/// z-slice layout of `grid::format_solution`, separated by blank lines.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = solveText))]
+pub fn solve_text(puzzle: &str, max_solutions: Option<u32>) -> Result<String, String> {
+    let max_solutions = max_solutions.map(|max| max as usize);
+    let formatted: Vec<String> = match puzzle {
+        "soma" => SOMA_PUZZLE
+            .solve_sequential(max_solutions)
+            .iter()
+            .map(|solution| SOMA_PUZZLE.format_solution(solution))
+            .collect(),
+        "bedlam" => BEDLAM_PUZZLE
+            .solve_sequential(max_solutions)
+            .iter()
+            .map(|solution| BEDLAM_PUZZLE.format_solution(solution))
+            .collect(),
+        _ => return Err(format!("unknown puzzle '{puzzle}'")),
+    };
+    Ok(formatted.join("\n"))
+}
+
+/// Encodes solutions as a JSON array without going through serde.
+fn solutions_to_json(solutions: &[Vec<PlacedPiece>]) -> String {
+    let solutions: Vec<String> = solutions
+        .iter()
+        .map(|solution| {
+            let pieces: Vec<String> = solution
+                .iter()
+                .map(|placed| {
+                    let cubes: Vec<String> = placed
+                        .cubes()
+                        .iter()
+                        .map(|&(x, y, z)| format!("[{},{},{}]", x, y, z))
+                        .collect();
+                    format!("[{},[{}]]", placed.piece_index, cubes.join(","))
+                })
+                .collect();
+            format!("[{}]", pieces.join(","))
+        })
+        .collect();
+    format!("[{}]", solutions.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::MAX_CUBES;
+
+    #[test]
+    fn test_solve_json_lists_each_solution() {
+        let json = solve_json("soma", Some(2)).unwrap();
+        let solutions = SOMA_PUZZLE.solve_sequential(Some(2));
+        assert_eq!(json, solutions_to_json(&solutions));
+
+        let mut positions = [(0, 0, 0); MAX_CUBES];
+        positions[1] = (0, 0, 1);
+        let domino = PlacedPiece {
+            piece_index: 1,
+            positions,
+            cube_count: 2,
+        };
+        assert_eq!(
+            solutions_to_json(&[vec![domino]]),
+            "[[[1,[[0,0,0],[0,0,1]]]]]"
+        );
+
+        assert_eq!(
+            solve_json("tangram", None).unwrap_err(),
+            "unknown puzzle 'tangram'"
+        );
+        assert_eq!(solve_text("soma", Some(0)).unwrap(), "");
+    }
+}